@@ -0,0 +1,248 @@
+//! Handlers for the commands sent to the API
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use clap::crate_version;
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use serde_json::{json, Map};
+
+use crate::{config::MiniserveConfig, listing::ListingCache, sitemap::SitemapCache};
+
+/// Commands accepted by the API, sent as JSON like `{"Invalidate": "/some/dir"}`
+#[derive(Deserialize, Debug)]
+pub enum ApiCommand {
+    /// Drops the cached listings of the given directory and everything below it, or of all
+    /// directories if no path is given
+    Invalidate(Option<String>),
+}
+
+/// Handles a command sent to the API
+pub async fn api_command(
+    req: HttpRequest,
+    command: web::Json<ApiCommand>,
+    listing_cache: web::Data<ListingCache>,
+    sitemap_cache: web::Data<SitemapCache>,
+) -> HttpResponse {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+
+    match command.into_inner() {
+        ApiCommand::Invalidate(path) => {
+            let prefix = path.map(|path| {
+                let path = percent_decode_str(&path).decode_utf8_lossy();
+                format!("{}/{}", conf.route_prefix, path.trim_matches('/'))
+            });
+            listing_cache.invalidate(prefix.as_deref());
+            sitemap_cache.invalidate();
+        }
+    }
+
+    HttpResponse::NoContent().finish()
+}
+
+/// Describes the API and the other endpoints enabled by the configuration as an OpenAPI document
+pub async fn openapi(req: HttpRequest) -> HttpResponse {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    let mut paths = Map::new();
+
+    if !conf.hide_version_footer {
+        paths.insert(
+            "/__miniserve_internal/version".to_string(),
+            json!({
+                "get": {
+                    "summary": "Version of miniserve and the features it was built with",
+                    "responses": { "200": { "description": "Version information" } }
+                }
+            }),
+        );
+    }
+
+    // Everything else is only available when serving a directory
+    if !conf.path.is_file() {
+        // The JSON index and the tree share the path of the directory
+        let mut parameters = Vec::new();
+        if conf.emit_json_index {
+            parameters.push(json!({
+                "name": "format",
+                "in": "query",
+                "description": "Lists the entries of the directory",
+                "schema": { "const": "json" }
+            }));
+        }
+        if conf.tree_api {
+            parameters.push(json!({
+                "name": "tree",
+                "in": "query",
+                "description": "Lists everything below the directory as a tree",
+                "schema": { "const": true }
+            }));
+        }
+        if !parameters.is_empty() {
+            parameters.insert(
+                0,
+                json!({
+                    "name": "path",
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" }
+                }),
+            );
+            paths.insert(
+                "/{path}".to_string(),
+                json!({
+                    "get": {
+                        "summary": "Contents of a directory as JSON",
+                        "parameters": parameters,
+                        "responses": { "200": { "description": "Directory index or tree" } }
+                    }
+                }),
+            );
+        }
+        if conf.sitemap {
+            paths.insert(
+                "/sitemap.xml".to_string(),
+                json!({
+                    "get": {
+                        "summary": "Sitemap of all served files",
+                        "responses": { "200": { "description": "Sitemap" } }
+                    }
+                }),
+            );
+        }
+        if conf.listing_cache_ttl.is_some() {
+            paths.insert(
+                "/api".to_string(),
+                json!({
+                    "post": {
+                        "summary": "Runs an API command",
+                        "requestBody": {
+                            "required": true,
+                            "content": {
+                                "application/json": {
+                                    "schema": { "$ref": "#/components/schemas/ApiCommand" }
+                                }
+                            }
+                        },
+                        "responses": { "204": { "description": "Command was run" } }
+                    }
+                }),
+            );
+        }
+        if conf.file_upload {
+            let mut fields = Map::new();
+            fields.insert(
+                "file_to_upload".to_string(),
+                json!({ "type": "string", "format": "binary" }),
+            );
+            if conf.mkdir_enabled {
+                fields.insert("mkdir".to_string(), json!({ "type": "string" }));
+            }
+            paths.insert(
+                "/upload".to_string(),
+                json!({
+                    "post": {
+                        "summary": "Uploads files or creates directories",
+                        "parameters": [{
+                            "name": "path",
+                            "in": "query",
+                            "required": true,
+                            "schema": { "type": "string" }
+                        }],
+                        "requestBody": {
+                            "required": true,
+                            "content": {
+                                "multipart/form-data": {
+                                    "schema": { "type": "object", "properties": fields }
+                                }
+                            }
+                        },
+                        "responses": {
+                            "200": { "description": "Upload succeeded" },
+                            "303": { "description": "Upload succeeded, redirecting" }
+                        }
+                    }
+                }),
+            );
+            paths.insert(
+                "/upload/progress".to_string(),
+                json!({
+                    "get": {
+                        "summary": "Progress of an upload as server-sent events",
+                        "parameters": [{
+                            "name": "upload_id",
+                            "in": "query",
+                            "required": true,
+                            "schema": { "type": "string" }
+                        }],
+                        "responses": { "200": { "description": "Event stream" } }
+                    }
+                }),
+            );
+            if conf.tus {
+                paths.insert(
+                    "/upload/tus".to_string(),
+                    json!({
+                        "post": {
+                            "summary": "Creates a resumable upload using the tus protocol",
+                            "responses": { "201": { "description": "Upload created" } }
+                        }
+                    }),
+                );
+                paths.insert(
+                    "/upload/tus/{id}".to_string(),
+                    json!({
+                        "head": {
+                            "summary": "Offset of a resumable upload",
+                            "responses": { "200": { "description": "Upload offset" } }
+                        },
+                        "patch": {
+                            "summary": "Continues a resumable upload",
+                            "responses": { "204": { "description": "Chunk was stored" } }
+                        }
+                    }),
+                );
+            }
+        }
+    }
+
+    let version = if conf.hide_version_footer {
+        "-"
+    } else {
+        crate_version!()
+    };
+    let server_url = if conf.route_prefix.is_empty() {
+        "/"
+    } else {
+        conf.route_prefix.as_str()
+    };
+    let mut document = json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "miniserve",
+            "version": version,
+        },
+        "servers": [{ "url": server_url }],
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "ApiCommand": {
+                    "oneOf": [{
+                        "type": "object",
+                        "description": "Drops the cached listings of the given directory and \
+                            everything below it, or of all directories if no path is given",
+                        "required": ["Invalidate"],
+                        "properties": { "Invalidate": { "type": ["string", "null"] } },
+                        "additionalProperties": false
+                    }]
+                }
+            }
+        }
+    });
+
+    if !conf.auth.is_empty() {
+        document["components"]["securitySchemes"] =
+            json!({ "basicAuth": { "type": "http", "scheme": "basic" } });
+        document["security"] = json!([{ "basicAuth": [] }]);
+    }
+
+    HttpResponse::Ok().json(document)
+}
@@ -1,14 +1,54 @@
+use std::collections::HashSet;
 use std::fs::File;
-use std::io::{Cursor, Read, Write};
-use std::path::{Path, PathBuf};
+use std::io::{BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
 
-use libflate::gzip::Encoder;
+use libflate::gzip::{Decoder, Encoder};
+use regex::Regex;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use strum::{Display, EnumIter, EnumString};
-use tar::Builder;
-use zip::{write, ZipWriter};
+use tar::{Archive, Builder, EntryType, Header};
+use zip::{write, ZipArchive, ZipWriter};
 
-use crate::errors::RuntimeError;
+use crate::{
+    errors::RuntimeError,
+    file_utils::{contains_symlink, matches_filters},
+};
+
+/// Name of the checksum manifest added to tarballs
+const MANIFEST_FILE_NAME: &str = "MANIFEST.sha256";
+
+/// Writes to a file, and to another writer for as long as it accepts data
+struct TeeWriter<W> {
+    file: BufWriter<File>,
+    out: Option<W>,
+}
+
+impl<W: Write> Write for TeeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write_all(buf)?;
+        if let Some(out) = &mut self.out {
+            if out.write_all(buf).is_err() {
+                // The client went away, keep writing the file only
+                self.out = None;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        if let Some(out) = &mut self.out {
+            if out.flush().is_err() {
+                self.out = None;
+            }
+        }
+        Ok(())
+    }
+}
 
 /// Available archive methods
 #[derive(Deserialize, Clone, Copy, EnumIter, EnumString, Display)]
@@ -25,6 +65,76 @@ pub enum ArchiveMethod {
     Zip,
 }
 
+/// Options controlling which content ends up in a created archive
+#[derive(Clone, Debug, Default)]
+pub struct ArchiveOptions {
+    /// If `true`, symlinks will not be followed and will just be ignored
+    pub skip_symlinks: bool,
+
+    /// If `true`, tarballs will contain a `MANIFEST.sha256` file listing the SHA-256 checksum of
+    /// every file
+    pub manifest: bool,
+
+    /// If `true`, directories which contain no files, not even in their subdirectories, are left
+    /// out
+    pub skip_empty_dirs: bool,
+
+    /// If `true`, the content of the directory is placed at the root of the archive instead of in
+    /// a top-level folder named after the directory
+    pub flat: bool,
+
+    /// If `true`, only the files directly inside the directory are included, without any
+    /// subdirectories. Only supported by zip archives.
+    pub files_only: bool,
+
+    /// Zip archives whose content is larger than this many bytes are assembled in a temporary
+    /// file instead of in memory
+    pub memory_limit: Option<u64>,
+
+    /// Only files whose names match one of these globs are included, unless there are none
+    pub filters: Vec<Regex>,
+}
+
+impl ArchiveOptions {
+    /// Checks whether the file at `path` is included according to the filters
+    fn includes_file(&self, path: &Path) -> bool {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        matches_filters(&file_name, &self.filters)
+    }
+}
+
+/// Limits protecting the disk from archives that decompress to far more data than was uploaded
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExtractionLimits {
+    /// Maximum number of bytes all entries of an archive may decompress to
+    pub max_total_size: Option<u64>,
+
+    /// Maximum ratio between the decompressed size of all entries and the size of the archive
+    pub max_ratio: Option<u64>,
+}
+
+impl ExtractionLimits {
+    /// Returns how many bytes an archive of `archive_len` bytes may decompress to
+    fn budget(self, archive_len: u64) -> u64 {
+        let ratio_limit = self
+            .max_ratio
+            .map(|ratio| ratio.saturating_mul(archive_len));
+        match (self.max_total_size, ratio_limit) {
+            (Some(size), Some(ratio)) => size.min(ratio),
+            (Some(limit), None) | (None, Some(limit)) => limit,
+            (None, None) => u64::MAX,
+        }
+    }
+}
+
+/// Kinds of archive entries that can be extracted
+#[derive(Clone, Copy, PartialEq)]
+enum ExtractedEntryKind {
+    Directory,
+    File,
+    Symlink,
+}
+
 impl ArchiveMethod {
     pub fn extension(self) -> String {
         match self {
@@ -44,6 +154,14 @@ impl ArchiveMethod {
         .to_string()
     }
 
+    /// Whether the archive can be worth compressing again with `--compress-response`
+    ///
+    /// Gzipped tarballs are already compressed, and zip archives are mostly made of compressed
+    /// formats, so only regular tarballs are left to the compression middleware.
+    pub fn is_compressible(self) -> bool {
+        matches!(self, Self::Tar)
+    }
+
     pub fn is_enabled(self, tar_enabled: bool, tar_gz_enabled: bool, zip_enabled: bool) -> bool {
         match self {
             Self::TarGz => tar_gz_enabled,
@@ -54,13 +172,11 @@ impl ArchiveMethod {
 
     /// Make an archive out of the given directory, and write the output to the given writer.
     ///
-    /// Recursively includes all files and subdirectories.
-    ///
-    /// If `skip_symlinks` is `true`, symlinks fill not be followed and will just be ignored.
+    /// Recursively includes all files and subdirectories, as restricted by `options`.
     pub fn create_archive<T, W>(
         self,
         dir: T,
-        skip_symlinks: bool,
+        options: &ArchiveOptions,
         out: W,
     ) -> Result<(), RuntimeError>
     where
@@ -69,21 +185,508 @@ impl ArchiveMethod {
     {
         let dir = dir.as_ref();
         match self {
-            Self::TarGz => tar_gz(dir, skip_symlinks, out),
-            Self::Tar => tar_dir(dir, skip_symlinks, out),
-            Self::Zip => zip_dir(dir, skip_symlinks, out),
+            Self::TarGz => tar_gz(dir, options, out),
+            Self::Tar => tar_dir(dir, options, out),
+            Self::Zip => zip_dir(dir, options, out),
+        }
+    }
+
+    /// Returns the file name under which the archive of `dir` is cached
+    ///
+    /// The name changes whenever anything below the directory is added, removed or modified, so
+    /// that outdated archives aren't served.
+    pub fn cached_archive_name(self, dir: &Path, options: &ArchiveOptions) -> Option<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "{}\0{}\0{options:?}\0",
+            dir.display(),
+            self.extension()
+        ));
+        hash_dir_entries(
+            dir,
+            Path::new(""),
+            options,
+            &mut HashSet::new(),
+            &mut hasher,
+        )
+        .ok()?;
+        Some(format!(
+            "{}.{}",
+            hex::encode(hasher.finalize()),
+            self.extension()
+        ))
+    }
+
+    /// Make an archive out of the given directory and write it to `cache_file`, while also
+    /// streaming it to the given writer.
+    ///
+    /// The archive is completed even if the writer stops accepting data, so that interrupted
+    /// downloads can be resumed from the cached file.
+    pub fn create_cached_archive<W>(
+        self,
+        dir: &Path,
+        options: &ArchiveOptions,
+        cache_file: &Path,
+        out: W,
+    ) -> Result<(), RuntimeError>
+    where
+        W: std::io::Write,
+    {
+        // Concurrent requests for the same archive each write their own partial file
+        static PARTIAL_FILES: AtomicUsize = AtomicUsize::new(0);
+        let mut partial_file = cache_file.as_os_str().to_owned();
+        partial_file.push(format!(
+            ".{}.partial",
+            PARTIAL_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let partial_file = PathBuf::from(partial_file);
+
+        let file = File::create(&partial_file).map_err(|e| {
+            RuntimeError::IoError("Failed to create the cached archive".to_string(), e)
+        })?;
+        let mut tee = TeeWriter {
+            file: BufWriter::new(file),
+            out: Some(out),
+        };
+
+        let result = self
+            .create_archive(dir, options, &mut tee)
+            .and_then(|()| {
+                tee.file.flush().map_err(|e| {
+                    RuntimeError::IoError("Failed to write the cached archive".to_string(), e)
+                })
+            })
+            .and_then(|()| {
+                std::fs::rename(&partial_file, cache_file).map_err(|e| {
+                    RuntimeError::IoError("Failed to store the cached archive".to_string(), e)
+                })
+            });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&partial_file);
+        }
+        result
+    }
+
+    /// Detects the archive method of an uploaded file from its name
+    pub fn from_file_name(file_name: &Path) -> Option<Self> {
+        let file_name = file_name.file_name()?.to_str()?.to_lowercase();
+        if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if file_name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if file_name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Extract the archive at `archive` into `target_dir`.
+    ///
+    /// All entries are validated before anything is written. Entries escaping `target_dir` are
+    /// rejected, as are hidden entries unless `allow_hidden` is `true` and symlinks unless
+    /// `allow_symlinks` is `true`. Symlinks pointing outside of `target_dir` are always rejected.
+    /// Existing files are only replaced if `overwrite_files` is `true`.
+    ///
+    /// Extraction is aborted as soon as the decompressed data exceeds `limits`, in which case the
+    /// files and directories created so far are removed again.
+    pub fn extract_archive(
+        self,
+        archive: &Path,
+        target_dir: &Path,
+        allow_hidden: bool,
+        allow_symlinks: bool,
+        overwrite_files: bool,
+        limits: ExtractionLimits,
+    ) -> Result<(), RuntimeError> {
+        let mut symlinks = HashSet::new();
+        let mut entries = Vec::new();
+
+        for (path, kind) in self.list_entries(archive)? {
+            let path = validate_entry_path(&path, allow_hidden)?;
+
+            // Entries must not be written through a symlink from the same archive
+            if path.ancestors().skip(1).any(|p| symlinks.contains(p)) {
+                return Err(RuntimeError::ArchiveExtractionError(format!(
+                    "{} traverses through a symlink",
+                    path.display()
+                )));
+            }
+            if kind == ExtractedEntryKind::Symlink {
+                if !allow_symlinks {
+                    return Err(RuntimeError::ArchiveExtractionError(format!(
+                        "{} is a symlink",
+                        path.display()
+                    )));
+                }
+                symlinks.insert(path.clone());
+            }
+
+            let dest = target_dir.join(&path);
+            if !allow_symlinks {
+                match contains_symlink(&dest) {
+                    Err(err) => Err(RuntimeError::InsufficientPermissionsError(err.to_string()))?,
+                    Ok(true) => Err(RuntimeError::ArchiveExtractionError(format!(
+                        "{} traverses through a symlink",
+                        path.display()
+                    )))?,
+                    Ok(false) => (),
+                }
+            }
+            if kind != ExtractedEntryKind::Directory
+                && !overwrite_files
+                && dest.symlink_metadata().is_ok()
+            {
+                return Err(RuntimeError::DuplicateFileError);
+            }
+
+            entries.push((path, kind));
+        }
+
+        let archive_len = archive.metadata().map_err(extraction_error)?.len();
+        let mut created = Vec::new();
+        let result = self.unpack_entries(
+            archive,
+            target_dir,
+            entries,
+            limits.budget(archive_len),
+            &mut created,
+        );
+        if result.is_err() {
+            remove_created_paths(&created);
+        }
+        result
+    }
+
+    /// List the raw paths and kinds of all entries of the archive at `archive`
+    fn list_entries(
+        self,
+        archive: &Path,
+    ) -> Result<Vec<(PathBuf, ExtractedEntryKind)>, RuntimeError> {
+        match self {
+            Self::TarGz | Self::Tar => {
+                let mut archive = self.open_tar(archive)?;
+                archive
+                    .entries()
+                    .map_err(extraction_error)?
+                    .map(|entry| {
+                        let entry = entry.map_err(extraction_error)?;
+                        let path = entry.path().map_err(extraction_error)?.into_owned();
+                        let kind = match entry.header().entry_type() {
+                            EntryType::Directory => ExtractedEntryKind::Directory,
+                            EntryType::Regular | EntryType::Continuous => ExtractedEntryKind::File,
+                            EntryType::Symlink => ExtractedEntryKind::Symlink,
+                            other => {
+                                return Err(RuntimeError::ArchiveExtractionError(format!(
+                                    "{} has unsupported entry type {other:?}",
+                                    path.display()
+                                )))
+                            }
+                        };
+                        Ok((path, kind))
+                    })
+                    .collect()
+            }
+            Self::Zip => {
+                let mut archive = open_zip(archive)?;
+                (0..archive.len())
+                    .map(|i| {
+                        let file = archive.by_index_raw(i).map_err(extraction_error)?;
+                        let kind = if file.is_dir() {
+                            ExtractedEntryKind::Directory
+                        } else if file.is_symlink() {
+                            ExtractedEntryKind::Symlink
+                        } else {
+                            ExtractedEntryKind::File
+                        };
+                        Ok((PathBuf::from(file.name()), kind))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Write the previously validated `entries` of the archive at `archive` to their destinations
+    /// in `target_dir`
+    ///
+    /// At most `budget` bytes are written in total. Every path that didn't exist before is added
+    /// to `created`.
+    fn unpack_entries(
+        self,
+        archive: &Path,
+        target_dir: &Path,
+        entries: Vec<(PathBuf, ExtractedEntryKind)>,
+        mut budget: u64,
+        created: &mut Vec<PathBuf>,
+    ) -> Result<(), RuntimeError> {
+        match self {
+            Self::TarGz | Self::Tar => {
+                let mut archive = self.open_tar(archive)?;
+                let archive_entries = archive.entries().map_err(extraction_error)?;
+                for (entry, (path, kind)) in archive_entries.zip(entries) {
+                    let mut entry = entry.map_err(extraction_error)?;
+                    let link_target = match kind {
+                        ExtractedEntryKind::Symlink => entry
+                            .link_name()
+                            .map_err(extraction_error)?
+                            .map(|target| validate_link_target(&path, &target))
+                            .transpose()?,
+                        _ => None,
+                    };
+                    let dest = target_dir.join(path);
+                    write_entry(&dest, kind, &mut entry, link_target, &mut budget, created)?;
+                }
+            }
+            Self::Zip => {
+                let mut archive = open_zip(archive)?;
+                for (i, (path, kind)) in entries.into_iter().enumerate() {
+                    let mut file = archive.by_index(i).map_err(extraction_error)?;
+                    let link_target = match kind {
+                        ExtractedEntryKind::Symlink => {
+                            let mut target = String::new();
+                            file.read_to_string(&mut target).map_err(extraction_error)?;
+                            Some(validate_link_target(&path, Path::new(&target))?)
+                        }
+                        _ => None,
+                    };
+                    let dest = target_dir.join(path);
+                    write_entry(&dest, kind, &mut file, link_target, &mut budget, created)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Open the tarball at `archive`, decompressing it if needed
+    fn open_tar(self, archive: &Path) -> Result<Archive<Box<dyn Read>>, RuntimeError> {
+        let file = File::open(archive).map_err(extraction_error)?;
+        let reader: Box<dyn Read> = match self {
+            Self::TarGz => Box::new(Decoder::new(file).map_err(extraction_error)?),
+            _ => Box::new(file),
+        };
+        Ok(Archive::new(reader))
+    }
+}
+
+/// Open the zip archive at `archive`
+fn open_zip(archive: &Path) -> Result<ZipArchive<File>, RuntimeError> {
+    let file = File::open(archive).map_err(extraction_error)?;
+    ZipArchive::new(file).map_err(extraction_error)
+}
+
+/// Wrap any error occurring while reading an uploaded archive
+fn extraction_error(err: impl std::fmt::Display) -> RuntimeError {
+    RuntimeError::ArchiveExtractionError(err.to_string())
+}
+
+/// Ensure that the path of an archive entry stays within the extraction directory
+///
+/// Absolute paths and paths containing `..` are rejected, as are hidden paths unless
+/// `allow_hidden` is `true`.
+fn validate_entry_path(path: &Path, allow_hidden: bool) -> Result<PathBuf, RuntimeError> {
+    let mut validated = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => {
+                if !allow_hidden && name.to_string_lossy().starts_with('.') {
+                    return Err(RuntimeError::ArchiveExtractionError(format!(
+                        "{} is a hidden path",
+                        path.display()
+                    )));
+                }
+                validated.push(name);
+            }
+            Component::CurDir => (),
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(RuntimeError::ArchiveExtractionError(format!(
+                    "{} escapes the target directory",
+                    path.display()
+                )))
+            }
+        }
+    }
+
+    if validated.as_os_str().is_empty() {
+        return Err(RuntimeError::ArchiveExtractionError(format!(
+            "{} is not a valid path",
+            path.display()
+        )));
+    }
+
+    Ok(validated)
+}
+
+/// Ensure that the target of a symlink at `path`, relative to the extraction directory, stays
+/// within it
+///
+/// Absolute targets are rejected. `..` is only allowed at the start of the target, as it would
+/// otherwise resolve relative to wherever the preceding components point to if they're symlinks.
+fn validate_link_target(path: &Path, target: &Path) -> Result<PathBuf, RuntimeError> {
+    let escapes = || {
+        RuntimeError::ArchiveExtractionError(format!(
+            "{} links to {} outside of the target directory",
+            path.display(),
+            target.display()
+        ))
+    };
+
+    // The number of directories the target can go up before leaving the extraction directory
+    let mut depth = path.components().count().saturating_sub(1);
+    let mut descended = false;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => descended = true,
+            Component::CurDir => (),
+            Component::ParentDir if !descended && depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(escapes())
+            }
+        }
+    }
+
+    Ok(target.to_path_buf())
+}
+
+/// Write a single archive entry to `dest`
+///
+/// The content of files is deducted from `budget`, and extraction fails once it's used up.
+/// Paths that didn't exist before are added to `created`.
+fn write_entry(
+    dest: &Path,
+    kind: ExtractedEntryKind,
+    reader: &mut dyn Read,
+    link_target: Option<PathBuf>,
+    budget: &mut u64,
+    created: &mut Vec<PathBuf>,
+) -> Result<(), RuntimeError> {
+    let io_error =
+        |err| RuntimeError::IoError(format!("Failed to extract {}", dest.display()), err);
+
+    if kind == ExtractedEntryKind::Directory {
+        return create_dir_all_recorded(dest, created).map_err(io_error);
+    }
+    if let Some(parent) = dest.parent() {
+        create_dir_all_recorded(parent, created).map_err(io_error)?;
+    }
+    if dest.symlink_metadata().is_err() {
+        created.push(dest.to_path_buf());
+    }
+
+    match (kind, link_target) {
+        (ExtractedEntryKind::Symlink, Some(link_target)) => {
+            // Replace any existing file, as creating a symlink would fail otherwise
+            if dest.symlink_metadata().is_ok() {
+                std::fs::remove_file(dest).map_err(io_error)?;
+            }
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(link_target, dest).map_err(io_error)?;
+            #[cfg(windows)]
+            std::os::windows::fs::symlink_file(link_target, dest).map_err(io_error)?;
+        }
+        (ExtractedEntryKind::Symlink, None) => {
+            return Err(RuntimeError::ArchiveExtractionError(format!(
+                "{} is a symlink without target",
+                dest.display()
+            )))
+        }
+        _ => {
+            let mut file = File::create(dest).map_err(io_error)?;
+            // Read one byte past the budget to tell whether the entry exceeds it
+            let mut limited = reader.take(budget.saturating_add(1));
+            let written = std::io::copy(&mut limited, &mut file).map_err(io_error)?;
+            if written > *budget {
+                return Err(RuntimeError::ArchiveExtractionLimitError(format!(
+                    "{} exceeds the limit for decompressed data",
+                    dest.display()
+                )));
+            }
+            *budget -= written;
         }
     }
+
+    Ok(())
+}
+
+/// Create `dir` and its missing parents, adding the created directories to `created`
+fn create_dir_all_recorded(dir: &Path, created: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let missing: Vec<_> = dir
+        .ancestors()
+        .take_while(|ancestor| ancestor.symlink_metadata().is_err())
+        .map(Path::to_path_buf)
+        .collect();
+    std::fs::create_dir_all(dir)?;
+    created.extend(missing.into_iter().rev());
+    Ok(())
+}
+
+/// Remove the files and directories left behind by an aborted extraction, newest first
+fn remove_created_paths(created: &[PathBuf]) {
+    for path in created.iter().rev() {
+        let result = match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir(path),
+            Ok(_) => std::fs::remove_file(path),
+            Err(_) => continue,
+        };
+        if let Err(err) = result {
+            log::warn!("Failed to remove {}: {err}", path.display());
+        }
+    }
+}
+
+/// Feeds the relative path, size and modification time of every entry below `dir` into `hasher`
+fn hash_dir_entries(
+    dir: &Path,
+    relative_dir: &Path,
+    options: &ArchiveOptions,
+    visited: &mut HashSet<PathBuf>,
+    hasher: &mut Sha256,
+) -> std::io::Result<()> {
+    // Guard against symlink loops
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let metadata = if options.skip_symlinks {
+            entry.metadata()
+        } else {
+            std::fs::metadata(entry.path())
+        };
+        // Broken symlinks can't end up in the archive either
+        let Ok(metadata) = metadata else {
+            continue;
+        };
+        let modified = metadata
+            .modified()?
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |modified| modified.as_nanos());
+        let relative_path = relative_dir.join(entry.file_name());
+        hasher.update(format!(
+            "{}\0{}\0{modified}\0",
+            relative_path.display(),
+            metadata.len()
+        ));
+
+        if metadata.is_dir() {
+            hash_dir_entries(&entry.path(), &relative_path, options, visited, hasher)?;
+        }
+    }
+
+    Ok(())
 }
 
 /// Write a gzipped tarball of `dir` in `out`.
-fn tar_gz<W>(dir: &Path, skip_symlinks: bool, out: W) -> Result<(), RuntimeError>
+fn tar_gz<W>(dir: &Path, options: &ArchiveOptions, out: W) -> Result<(), RuntimeError>
 where
     W: std::io::Write,
 {
     let mut out = Encoder::new(out).map_err(|e| RuntimeError::IoError("GZIP".to_string(), e))?;
 
-    tar_dir(dir, skip_symlinks, &mut out)?;
+    tar_dir(dir, options, &mut out)?;
 
     out.finish()
         .into_result()
@@ -115,7 +718,7 @@ where
 /// ├── f
 /// └── g
 /// ```
-fn tar_dir<W>(dir: &Path, skip_symlinks: bool, out: W) -> Result<(), RuntimeError>
+fn tar_dir<W>(dir: &Path, options: &ArchiveOptions, out: W) -> Result<(), RuntimeError>
 where
     W: std::io::Write,
 {
@@ -129,17 +732,26 @@ where
         )
     })?;
 
-    tar(dir, directory.to_string(), skip_symlinks, out)
+    let inner_folder = if options.flat {
+        String::new()
+    } else {
+        directory.to_string()
+    };
+
+    tar(dir, inner_folder, options, out)
         .map_err(|e| RuntimeError::ArchiveCreationError("tarball".to_string(), Box::new(e)))
 }
 
 /// Writes a tarball of `dir` in `out`.
 ///
-/// The content of `src_dir` will be saved in the archive as a folder named `inner_folder`.
+/// The content of `src_dir` will be saved in the archive as a folder named `inner_folder`, or at
+/// the root of the archive if `inner_folder` is empty.
+///
+/// If `options.manifest` is `true`, a checksum manifest is written into `inner_folder` first.
 fn tar<W>(
     src_dir: &Path,
     inner_folder: String,
-    skip_symlinks: bool,
+    options: &ArchiveOptions,
     out: W,
 ) -> Result<(), RuntimeError>
 where
@@ -147,20 +759,57 @@ where
 {
     let mut tar_builder = Builder::new(out);
 
-    tar_builder.follow_symlinks(!skip_symlinks);
+    tar_builder.follow_symlinks(!options.skip_symlinks);
 
-    // Recursively adds the content of src_dir into the archive stream
-    tar_builder
-        .append_dir_all(inner_folder, src_dir)
-        .map_err(|e| {
-            RuntimeError::IoError(
-                format!(
-                    "Failed to append the content of {} to the TAR archive",
-                    src_dir.to_str().unwrap_or("file")
-                ),
-                e,
+    if options.manifest {
+        let manifest = create_manifest(src_dir, options)?;
+
+        let mut header = Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        );
+        header.set_cksum();
+
+        tar_builder
+            .append_data(
+                &mut header,
+                Path::new(&inner_folder).join(MANIFEST_FILE_NAME),
+                manifest.as_bytes(),
             )
-        })?;
+            .map_err(|e| {
+                RuntimeError::IoError(
+                    "Failed to append the manifest to the TAR archive".to_string(),
+                    e,
+                )
+            })?;
+    }
+
+    // Recursively adds the content of src_dir into the archive stream
+    let appended = if options.skip_empty_dirs || !options.filters.is_empty() {
+        append_dir_selectively(
+            &mut tar_builder,
+            Path::new(&inner_folder),
+            src_dir,
+            options,
+            &mut HashSet::new(),
+        )
+    } else {
+        tar_builder.append_dir_all(inner_folder, src_dir)
+    };
+    appended.map_err(|e| {
+        RuntimeError::IoError(
+            format!(
+                "Failed to append the content of {} to the TAR archive",
+                src_dir.to_str().unwrap_or("file")
+            ),
+            e,
+        )
+    })?;
 
     // Finish the archive
     tar_builder.into_inner().map_err(|e| {
@@ -170,9 +819,155 @@ where
     Ok(())
 }
 
+/// Recursively appends `dir` as `archive_path` to a tarball, leaving out files not matching the
+/// filters and, if `options.skip_empty_dirs` is `true`, directories which contain no files.
+fn append_dir_selectively<W>(
+    tar_builder: &mut Builder<W>,
+    archive_path: &Path,
+    dir: &Path,
+    options: &ArchiveOptions,
+    visited: &mut HashSet<PathBuf>,
+) -> std::io::Result<()>
+where
+    W: std::io::Write,
+{
+    // Guard against symlink loops
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(());
+    }
+
+    // The root of the archive itself has no entry
+    if archive_path != Path::new("") {
+        tar_builder.append_dir(archive_path, dir)?;
+    }
+
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let entry_path = entry.path();
+        let entry_archive_path = archive_path.join(entry.file_name());
+        if is_dir(&entry, options.skip_symlinks)? {
+            if !options.skip_empty_dirs
+                || contains_files(&entry_path, options, &mut HashSet::new())?
+            {
+                append_dir_selectively(
+                    tar_builder,
+                    &entry_archive_path,
+                    &entry_path,
+                    options,
+                    visited,
+                )?;
+            }
+        } else if options.includes_file(&entry_path) {
+            tar_builder.append_path_with_name(&entry_path, &entry_archive_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks whether a directory entry is a directory, following symlinks unless `skip_symlinks`
+/// is `true`
+fn is_dir(entry: &std::fs::DirEntry, skip_symlinks: bool) -> std::io::Result<bool> {
+    if skip_symlinks {
+        Ok(entry.file_type()?.is_dir())
+    } else {
+        Ok(entry.path().is_dir())
+    }
+}
+
+/// Checks whether `dir` or any of its subdirectories contains files matching the filters
+fn contains_files(
+    dir: &Path,
+    options: &ArchiveOptions,
+    visited: &mut HashSet<PathBuf>,
+) -> std::io::Result<bool> {
+    // Guard against symlink loops
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(false);
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let contains = if is_dir(&entry, options.skip_symlinks)? {
+            contains_files(&entry.path(), options, visited)?
+        } else {
+            options.includes_file(&entry.path())
+        };
+        if contains {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Creates a manifest in the format of `sha256sum` listing the checksums of all files in `dir`.
+///
+/// Paths are relative to `dir`, so the manifest can be checked with `sha256sum -c` from within
+/// the extracted directory.
+fn create_manifest(dir: &Path, options: &ArchiveOptions) -> Result<String, RuntimeError> {
+    let mut manifest = String::new();
+    let mut paths_queue: Vec<PathBuf> = vec![dir.to_path_buf()];
+
+    while let Some(current_dir) = paths_queue.pop() {
+        let mut entries = std::fs::read_dir(&current_dir)
+            .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| RuntimeError::IoError("Could not read directory".to_string(), e))?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        // Pushed in reverse so the directories are visited in alphabetical order
+        for entry in entries.into_iter().rev() {
+            let entry_path = entry.path();
+            let is_symlink = entry
+                .file_type()
+                .map_err(|e| RuntimeError::IoError("Could not get file type".to_string(), e))?
+                .is_symlink();
+            if is_symlink && options.skip_symlinks {
+                continue;
+            }
+
+            let metadata = std::fs::metadata(&entry_path)
+                .map_err(|e| RuntimeError::IoError("Could not get file metadata".to_string(), e))?;
+            if metadata.is_dir() {
+                paths_queue.push(entry_path);
+            } else if metadata.is_file() && options.includes_file(&entry_path) {
+                let mut hasher = Sha256::new();
+                let mut file = File::open(&entry_path)
+                    .map_err(|e| RuntimeError::IoError("Could not open file".to_string(), e))?;
+                std::io::copy(&mut file, &mut hasher).map_err(|e| {
+                    RuntimeError::IoError("Could not read from file".to_string(), e)
+                })?;
+
+                let relative_path = entry_path
+                    .strip_prefix(dir)
+                    .map_err(|_| {
+                        RuntimeError::ArchiveCreationDetailError(
+                            "Could not strip base directory".to_string(),
+                        )
+                    })?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                manifest.push_str(&format!(
+                    "{}  {}\n",
+                    hex::encode(hasher.finalize()),
+                    relative_path
+                ));
+            }
+        }
+    }
+
+    Ok(manifest)
+}
+
 /// Write a zip of `dir` in `out`.
 ///
-/// The target directory will be saved as a top-level directory in the archive.
+/// The target directory will be saved as a top-level directory in the archive, unless
+/// `archive_options.flat` is set.
 ///
 /// For example, consider this directory structure:
 ///
@@ -196,7 +991,7 @@ where
 fn create_zip_from_directory<W>(
     out: W,
     directory: &Path,
-    skip_symlinks: bool,
+    archive_options: &ArchiveOptions,
 ) -> Result<(), RuntimeError>
 where
     W: std::io::Write + std::io::Seek,
@@ -217,13 +1012,14 @@ where
         let current_dir = next.as_path();
         let directory_entry_iterator = std::fs::read_dir(current_dir)
             .map_err(|e| RuntimeError::IoError("Could not read directory".to_string(), e))?;
-        let zip_directory = Path::new(zip_root_folder_name).join(
-            current_dir.strip_prefix(directory).map_err(|_| {
-                RuntimeError::ArchiveCreationDetailError(
-                    "Could not append base directory".to_string(),
-                )
-            })?,
-        );
+        let relative_dir = current_dir.strip_prefix(directory).map_err(|_| {
+            RuntimeError::ArchiveCreationDetailError("Could not append base directory".to_string())
+        })?;
+        let zip_directory = if archive_options.flat {
+            relative_dir.to_path_buf()
+        } else {
+            Path::new(zip_root_folder_name).join(relative_dir)
+        };
 
         for entry in directory_entry_iterator {
             let entry_path = entry
@@ -237,13 +1033,16 @@ where
             let entry_metadata = std::fs::metadata(entry_path.clone())
                 .map_err(|e| RuntimeError::IoError("Could not get file metadata".to_string(), e))?;
 
-            if entry_metadata.file_type().is_symlink() && skip_symlinks {
+            if entry_metadata.file_type().is_symlink() && archive_options.skip_symlinks {
                 continue;
             }
             let current_entry_name = entry_path.file_name().ok_or_else(|| {
                 RuntimeError::InvalidPathError("Invalid file or directory name".to_string())
             })?;
             if entry_metadata.is_file() {
+                if !archive_options.includes_file(&entry_path) {
+                    continue;
+                }
                 let mut f = File::open(&entry_path)
                     .map_err(|e| RuntimeError::IoError("Could not open file".to_string(), e))?;
                 f.read_to_end(&mut buffer).map_err(|e| {
@@ -263,7 +1062,14 @@ where
                     )
                 })?;
                 buffer.clear();
-            } else if entry_metadata.is_dir() {
+            } else if entry_metadata.is_dir() && !archive_options.files_only {
+                if archive_options.skip_empty_dirs
+                    && !contains_files(&entry_path, archive_options, &mut HashSet::new()).map_err(
+                        |e| RuntimeError::IoError("Could not read directory".to_string(), e),
+                    )?
+                {
+                    continue;
+                }
                 let relative_path = zip_directory.join(current_entry_name).into_os_string();
                 zip_writer
                     .add_directory(relative_path.to_string_lossy(), options)
@@ -286,26 +1092,67 @@ where
 /// Writes a zip of `dir` in `out`.
 ///
 /// The content of `src_dir` will be saved in the archive as the  folder named .
-fn zip_data<W>(src_dir: &Path, skip_symlinks: bool, mut out: W) -> Result<(), RuntimeError>
+fn zip_data<W>(src_dir: &Path, options: &ArchiveOptions, mut out: W) -> Result<(), RuntimeError>
 where
     W: std::io::Write,
 {
-    let mut data = Vec::new();
-    let memory_file = Cursor::new(&mut data);
-    create_zip_from_directory(memory_file, src_dir, skip_symlinks).map_err(|e| {
+    let creation_error = |e| {
         RuntimeError::ArchiveCreationError(
             "Failed to create the ZIP archive".to_string(),
             Box::new(e),
         )
-    })?;
+    };
+    let write_error = |e| RuntimeError::IoError("Failed to write the ZIP archive".to_string(), e);
+
+    // If the size can't be estimated, err on the side of not running out of memory
+    let exceeds_memory_limit = options.memory_limit.is_some_and(|limit| {
+        estimated_zip_size(src_dir, options, &mut HashSet::new()).map_or(true, |size| size > limit)
+    });
+    if exceeds_memory_limit {
+        let mut file = tempfile::tempfile().map_err(|e| {
+            RuntimeError::IoError("Failed to create a temporary file".to_string(), e)
+        })?;
+        create_zip_from_directory(&mut file, src_dir, options).map_err(creation_error)?;
+        file.rewind().map_err(write_error)?;
+        std::io::copy(&mut file, &mut out).map_err(write_error)?;
+        return Ok(());
+    }
+
+    let mut data = Vec::new();
+    let memory_file = Cursor::new(&mut data);
+    create_zip_from_directory(memory_file, src_dir, options).map_err(creation_error)?;
 
-    out.write_all(data.as_mut_slice())
-        .map_err(|e| RuntimeError::IoError("Failed to write the ZIP archive".to_string(), e))?;
+    out.write_all(data.as_mut_slice()).map_err(write_error)?;
 
     Ok(())
 }
 
-fn zip_dir<W>(dir: &Path, skip_symlinks: bool, out: W) -> Result<(), RuntimeError>
+/// Adds up the sizes of the files a zip of `dir` would contain
+fn estimated_zip_size(
+    dir: &Path,
+    options: &ArchiveOptions,
+    visited: &mut HashSet<PathBuf>,
+) -> std::io::Result<u64> {
+    // Guard against symlink loops
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(0);
+    }
+
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if is_dir(&entry, options.skip_symlinks)? {
+            if !options.files_only {
+                size += estimated_zip_size(&entry.path(), options, visited)?;
+            }
+        } else if options.includes_file(&entry.path()) {
+            size += entry.path().metadata().map_or(0, |metadata| metadata.len());
+        }
+    }
+    Ok(size)
+}
+
+fn zip_dir<W>(dir: &Path, options: &ArchiveOptions, out: W) -> Result<(), RuntimeError>
 where
     W: std::io::Write,
 {
@@ -319,6 +1166,6 @@ where
         )
     })?;
 
-    zip_data(dir, skip_symlinks, out)
+    zip_data(dir, options, out)
         .map_err(|e| RuntimeError::ArchiveCreationError("zip".to_string(), Box::new(e)))
 }
@@ -1,12 +1,21 @@
+use std::convert::Infallible;
 use std::net::IpAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method, StatusCode, Uri,
+};
+use bytesize::ByteSize;
 use clap::{Parser, ValueEnum, ValueHint};
+use regex::Regex;
 
 use crate::auth;
+use crate::file_utils;
+use crate::i18n::Language;
 use crate::listing::{SortingMethod, SortingOrder};
-use crate::renderer::ThemeSlug;
+use crate::renderer::{ListingStyle, ThemeSlug};
 
 #[derive(ValueEnum, Clone)]
 pub enum MediaType {
@@ -15,6 +24,46 @@ pub enum MediaType {
     Video,
 }
 
+/// TLS protocol versions that can be required as a minimum
+#[derive(ValueEnum, Clone, Copy)]
+pub enum TlsVersion {
+    #[value(name = "1.2")]
+    Tls12,
+
+    #[value(name = "1.3")]
+    Tls13,
+}
+
+/// How entries of a listing can be grouped
+#[derive(ValueEnum, Clone, Copy)]
+pub enum GroupBy {
+    /// Directories, then symlinked directories, then files, then symlinked files
+    Type,
+}
+
+/// Which robots.txt to serve
+#[derive(Clone, Debug)]
+pub enum Robots {
+    /// Allow crawlers to access everything
+    Allow,
+
+    /// Disallow crawlers to access anything
+    Deny,
+
+    /// Serve the contents of this file
+    File(PathBuf),
+}
+
+/// Where clients are sent after uploading
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UploadRedirect {
+    /// Respond with 200 OK instead of redirecting
+    None,
+
+    /// Redirect to this location
+    Location(String),
+}
+
 #[derive(Parser)]
 #[command(name = "miniserve", author, about, version)]
 pub struct CliArgs {
@@ -22,10 +71,54 @@ pub struct CliArgs {
     #[arg(short = 'v', long = "verbose", env = "MINISERVE_VERBOSE")]
     pub verbose: bool,
 
+    /// Log every file that has been served completely, along with its size, the client and user
+    ///
+    /// Unlike --verbose, this doesn't enable the access log.
+    #[arg(long = "log-downloads", env = "MINISERVE_LOG_DOWNLOADS")]
+    pub log_downloads: bool,
+
+    /// Don't print the startup banner, the bound addresses and URLs to stdout
+    ///
+    /// Warnings and errors are still logged.
+    #[arg(long = "quiet", env = "MINISERVE_QUIET")]
+    pub quiet: bool,
+
+    /// Shut down after this many seconds without any request
+    ///
+    /// Handy for one-off transfers, so that the server doesn't keep running once it's done.
+    #[arg(
+        long = "shutdown-after-idle",
+        value_name = "SECONDS",
+        env = "MINISERVE_SHUTDOWN_AFTER_IDLE"
+    )]
+    pub shutdown_after_idle: Option<u64>,
+
     /// Which path to serve
     #[arg(value_hint = ValueHint::AnyPath, env = "MINISERVE_PATH")]
     pub path: Option<PathBuf>,
 
+    /// Read stdin until it's closed and serve it as a single file
+    ///
+    /// Handy for piping, as in `cmd | miniserve --from-stdin`. Uploads and listings are
+    /// unavailable in this mode.
+    #[arg(
+        long = "from-stdin",
+        conflicts_with_all = ["path", "allowed_upload_dir"],
+        env = "MINISERVE_FROM_STDIN"
+    )]
+    pub from_stdin: bool,
+
+    /// Name of the file served with --from-stdin
+    #[arg(
+        long = "download-name",
+        value_name = "NAME",
+        default_value = "stdin",
+        value_parser = parse_file_name,
+        requires = "from_stdin",
+        env = "MINISERVE_DOWNLOAD_NAME"
+    )]
+    pub download_name: String,
+
     /// The name of a directory index file to serve, like "index.html"
     ///
     /// Normally, when miniserve serves a directory, it creates a listing for that directory.
@@ -49,6 +142,28 @@ pub struct CliArgs {
     #[arg(long, env = "MINISERVE_PRETTY_URLS")]
     pub pretty_urls: bool,
 
+    /// Activate static site mode, for hosting sites generated by tools like Hugo or Jekyll
+    ///
+    /// This turns on --pretty-urls, serves "index.html" as --index unless another one is given
+    /// and serves the site's "404.html" with a 404 status for paths that don't exist.
+    #[arg(
+        long = "static-site",
+        conflicts_with = "spa",
+        env = "MINISERVE_STATIC_SITE"
+    )]
+    pub static_site: bool,
+
+    /// Look up requested paths ignoring their case if they don't exist exactly
+    ///
+    /// This helps when serving content authored on a case-insensitive filesystem, where links
+    /// like `/Image.PNG` work for a file named `image.png`.
+    #[arg(
+        long = "case-insensitive",
+        conflicts_with_all = ["spa", "pretty_urls", "static_site"],
+        env = "MINISERVE_CASE_INSENSITIVE"
+    )]
+    pub case_insensitive: bool,
+
     /// Port to use
     #[arg(
         short = 'p',
@@ -68,6 +183,18 @@ pub struct CliArgs {
     )]
     pub interfaces: Vec<IpAddr>,
 
+    /// Only show the URLs and QR codes of this address at startup
+    ///
+    /// Can be given multiple times. All addresses the server is available at are shown by
+    /// default.
+    #[arg(
+        long = "display-interface",
+        value_parser(parse_interface),
+        num_args(1),
+        env = "MINISERVE_DISPLAY_INTERFACE"
+    )]
+    pub display_interfaces: Vec<IpAddr>,
+
     /// Set authentication
     ///
     /// Currently supported formats:
@@ -93,10 +220,59 @@ pub struct CliArgs {
     #[arg(long, value_hint = ValueHint::FilePath, env = "MINISERVE_AUTH_FILE", verbatim_doc_comment)]
     pub auth_file: Option<PathBuf>,
 
+    /// Read authentication values from the contents of an environment variable
+    ///
+    /// The variable uses the same newline-separated format as --auth-file.
+    #[arg(long, value_name = "VARNAME", env = "MINISERVE_AUTH_FILE_ENV")]
+    pub auth_file_env: Option<String>,
+
+    /// Lock clients out after this many failed authentication attempts
+    ///
+    /// Locked out clients are answered with 429 Too Many Requests until --auth-lockout has passed
+    /// since their last failed attempt. Clients are told apart by the IP address of their
+    /// connection, as forwarded addresses can't be trusted. Behind a reverse proxy, all clients
+    /// therefore share the address of the proxy and are locked out together.
+    #[arg(
+        long = "auth-max-attempts",
+        value_parser = clap::value_parser!(u32).range(1..),
+        env = "MINISERVE_AUTH_MAX_ATTEMPTS"
+    )]
+    pub auth_max_attempts: Option<u32>,
+
+    /// How long clients are locked out after too many failed authentication attempts, e.g. 10m
+    #[arg(
+        long = "auth-lockout",
+        value_name = "DURATION",
+        default_value = "5m",
+        requires = "auth_max_attempts",
+        value_parser(humantime::parse_duration),
+        env = "MINISERVE_AUTH_LOCKOUT"
+    )]
+    pub auth_lockout: Duration,
+
     /// Use a specific route prefix
     #[arg(long = "route-prefix", env = "MINISERVE_ROUTE_PREFIX")]
     pub route_prefix: Option<String>,
 
+    /// Redirect requests to `/` to the route prefix
+    #[arg(
+        long = "redirect-root-to-prefix",
+        requires = "route_prefix",
+        env = "MINISERVE_REDIRECT_ROOT_TO_PREFIX"
+    )]
+    pub redirect_root_to_prefix: bool,
+
+    /// Use this base URL for generated links instead of the one of the request
+    ///
+    /// Useful behind a reverse proxy that serves miniserve under a subpath and strips it before
+    /// passing requests on, e.g. `--external-base-url https://example.com/files`.
+    #[arg(
+        long = "external-base-url",
+        value_parser(parse_external_base_url),
+        env = "MINISERVE_EXTERNAL_BASE_URL"
+    )]
+    pub external_base_url: Option<Uri>,
+
     /// Generate a random 6-hexdigit route
     #[arg(
         long = "random-route",
@@ -153,6 +329,63 @@ pub struct CliArgs {
     )]
     pub color_scheme_dark: ThemeSlug,
 
+    /// Themes offered by the theme selector
+    ///
+    /// Defaults to all themes. The default color schemes have to be among them.
+    #[arg(
+        long = "themes",
+        value_name = "THEMES",
+        value_delimiter = ',',
+        ignore_case = true,
+        env = "MINISERVE_THEMES"
+    )]
+    pub themes: Vec<ThemeSlug>,
+
+    /// Serve this favicon while the given theme is active, e.g. `--favicon-for monokai=dark.svg`
+    ///
+    /// Can be given multiple times. Themes without a favicon of their own use the default one.
+    #[arg(
+        long = "favicon-for",
+        value_name = "THEME=FILE",
+        value_parser(parse_themed_favicon),
+        num_args(1),
+        env = "MINISERVE_FAVICON_FOR"
+    )]
+    pub themed_favicons: Vec<(ThemeSlug, PathBuf)>,
+
+    /// Layout of the file listing
+    #[arg(
+        long = "listing-style",
+        default_value = "table",
+        ignore_case = true,
+        env = "MINISERVE_LISTING_STYLE"
+    )]
+    pub listing_style: ListingStyle,
+
+    /// Language of the web UI
+    #[arg(
+        long = "lang",
+        default_value = "en",
+        ignore_case = true,
+        env = "MINISERVE_LANG"
+    )]
+    pub lang: Language,
+
+    /// Pick the language of the web UI from the Accept-Language header if it is supported
+    ///
+    /// Falls back to the language given by --lang otherwise.
+    #[arg(
+        long = "follow-accept-language",
+        env = "MINISERVE_FOLLOW_ACCEPT_LANGUAGE"
+    )]
+    pub follow_accept_language: bool,
+
+    /// Add a Subresource Integrity hash to the stylesheet link
+    ///
+    /// Browsers will refuse to apply the stylesheet if it was tampered with.
+    #[arg(long = "css-integrity", env = "MINISERVE_CSS_INTEGRITY")]
+    pub css_integrity: bool,
+
     /// Enable QR code display
     #[arg(short = 'q', long = "qrcode", env = "MINISERVE_QRCODE")]
     pub qrcode: bool,
@@ -175,6 +408,16 @@ pub struct CliArgs {
     )]
     pub mkdir_enabled: bool,
 
+    /// Fail with 409 Conflict when creating a directory that already exists
+    ///
+    /// By default, creating an existing directory succeeds without changing anything.
+    #[arg(
+        long = "mkdir-error-on-exists",
+        requires = "mkdir_enabled",
+        env = "MINISERVE_MKDIR_ERROR_ON_EXISTS"
+    )]
+    pub mkdir_error_on_exists: bool,
+
     /// Specify uploadable media types
     #[arg(
         short = 'm',
@@ -202,6 +445,281 @@ pub struct CliArgs {
     )]
     pub overwrite_files: bool,
 
+    /// Give up reading a directory after this many milliseconds
+    ///
+    /// Protects against hanging on unresponsive network mounts. The entries read until then are
+    /// shown along with a warning that the listing is incomplete.
+    #[arg(
+        long = "io-timeout",
+        value_name = "MILLISECONDS",
+        env = "MINISERVE_IO_TIMEOUT"
+    )]
+    pub io_timeout: Option<u64>,
+
+    /// Reuse directory listings for this many seconds
+    ///
+    /// Cached listings are still refreshed as soon as the directory itself is modified. They can
+    /// also be dropped by posting `{"Invalidate": "/some/dir"}` (or `null` for all directories)
+    /// to `/api`.
+    #[arg(
+        long = "listing-cache-ttl",
+        value_name = "SECONDS",
+        env = "MINISERVE_LISTING_CACHE_TTL"
+    )]
+    pub listing_cache_ttl: Option<u64>,
+
+    /// Render only this many entries of a listing at first and load the rest while scrolling
+    ///
+    /// Further batches are available with the `page` query parameter, e.g. `?page=1&raw=true`.
+    #[arg(
+        long = "lazy-listing",
+        value_name = "ENTRIES",
+        value_parser = clap::value_parser!(u64).range(1..),
+        env = "MINISERVE_LAZY_LISTING"
+    )]
+    pub lazy_listing: Option<u64>,
+
+    /// Append a line for every upload and created directory to this file
+    ///
+    /// Each line contains the timestamp, client IP, user, action, path and number of bytes.
+    #[arg(long = "audit-log", value_hint = ValueHint::FilePath, env = "MINISERVE_AUDIT_LOG")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Maximum size of request bodies, e.g. 100MB
+    ///
+    /// Larger requests, including uploads, are rejected with 413 Payload Too Large.
+    #[arg(long = "max-request-body", env = "MINISERVE_MAX_REQUEST_BODY")]
+    pub max_request_body: Option<ByteSize>,
+
+    /// Maximum number of uploads processed at the same time
+    ///
+    /// Further uploads wait for a short while and are rejected with 503 Service Unavailable if
+    /// no upload finishes in the meantime.
+    #[arg(
+        long = "max-concurrent-uploads",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_MAX_CONCURRENT_UPLOADS"
+    )]
+    pub max_concurrent_uploads: Option<u32>,
+
+    /// Maximum number of simultaneous connections per worker thread
+    ///
+    /// miniserve starts one worker thread per CPU core, so the total limit is this number times
+    /// the number of cores. Further connections wait until existing ones are closed. This also
+    /// limits the number of concurrent TLS handshakes.
+    // actix-server never resumes accepting connections with a limit of 1
+    #[arg(
+        long = "max-connections",
+        value_parser = clap::value_parser!(u32).range(2..),
+        env = "MINISERVE_MAX_CONNECTIONS"
+    )]
+    pub max_connections: Option<u32>,
+
+    /// Require a CSRF token for uploads and directory creation
+    ///
+    /// The token is embedded in the forms of the listing. Other clients can fetch it from any
+    /// listing page and send it in the `X-Csrf-Token` header. Requests without a valid token
+    /// are rejected with 403 Forbidden.
+    #[arg(
+        long = "upload-csrf",
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_UPLOAD_CSRF"
+    )]
+    pub upload_csrf: bool,
+
+    /// Only accept uploads from pages on this host, as told by the Origin or Referer header
+    ///
+    /// Can be given multiple times, e.g. `--upload-allowed-referer example.com`. A port can be
+    /// given as well, like `example.com:8080`. Uploads without either header are rejected too.
+    #[arg(
+        long = "upload-allowed-referer",
+        requires = "allowed_upload_dir",
+        num_args(1),
+        env = "MINISERVE_UPLOAD_ALLOWED_REFERER"
+    )]
+    pub upload_allowed_referers: Vec<String>,
+
+    /// Redirect to this location after uploads, or `none` to respond with 200 OK instead
+    ///
+    /// By default, clients are redirected back to the page they uploaded from. Clients can choose
+    /// the redirect of a single upload with the `X-Upload-Redirect` header, which takes the same
+    /// values.
+    #[arg(
+        long = "upload-redirect",
+        value_name = "none|URL",
+        requires = "allowed_upload_dir",
+        value_parser(parse_upload_redirect),
+        env = "MINISERVE_UPLOAD_REDIRECT"
+    )]
+    pub upload_redirect: Option<UploadRedirect>,
+
+    /// Require authentication for uploads and directory creation
+    ///
+    /// The rest of the site stays public: anonymous visitors can browse and download, but the
+    /// upload form is hidden from them and their uploads are rejected. Instead, the listing links
+    /// to `?login=true`, which asks for credentials. Requires --auth.
+    #[arg(
+        long = "upload-requires-auth",
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_UPLOAD_REQUIRES_AUTH"
+    )]
+    pub upload_requires_auth: bool,
+
+    /// Create the target directory of uploads if it doesn't exist yet
+    ///
+    /// By default, uploads to directories that don't exist are rejected.
+    #[arg(
+        long = "create-upload-dirs",
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_CREATE_UPLOAD_DIRS"
+    )]
+    pub create_upload_dirs: bool,
+
+    /// Only allow uploads to directories containing a `.miniserve-upload` marker file
+    ///
+    /// This lets the owners of directories decide whether uploads are allowed. Directories still
+    /// have to be allowed by --upload-files as well.
+    #[arg(
+        long = "sidecar-upload-control",
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_SIDECAR_UPLOAD_CONTROL"
+    )]
+    pub sidecar_upload_control: bool,
+
+    /// Stream uploaded files into the standard input of this shell command
+    ///
+    /// Uploaded files are only kept if the command exits successfully, which allows e.g. scanning
+    /// them with `--upload-pipe "clamscan -"`. Resumable uploads are streamed into it once they're
+    /// complete. The start of the standard error of a failing command is returned to the client.
+    #[arg(
+        long = "upload-pipe",
+        value_name = "COMMAND",
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_UPLOAD_PIPE"
+    )]
+    pub upload_pipe: Option<String>,
+
+    /// Accept resumable uploads following the tus 1.0 protocol at `/upload/tus`
+    ///
+    /// Uploads are created in the directory given by the `path` query parameter, just like
+    /// regular uploads. This allows using tus clients like Uppy. Unfinished uploads are discarded
+    /// once they haven't been resumed for a day.
+    #[arg(long = "tus", requires = "allowed_upload_dir", env = "MINISERVE_TUS")]
+    pub tus: bool,
+
+    /// Store uploaded files under a lowercase, filesystem-safe version of their name
+    ///
+    /// For example, `My File (1).TXT` is stored as `my-file-1.txt`.
+    #[arg(
+        long = "slugify-uploads",
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_SLUGIFY_UPLOADS"
+    )]
+    pub slugify_uploads: bool,
+
+    /// Only accept uploads whose SHA-256 checksum is listed in this manifest
+    ///
+    /// The manifest uses the format of `sha256sum`, with one `<checksum>  <file name>` line per
+    /// file. Uploads of files that aren't listed or don't match their checksum are rejected.
+    #[arg(
+        long = "upload-hash-manifest",
+        value_hint = ValueHint::FilePath,
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_UPLOAD_HASH_MANIFEST"
+    )]
+    pub upload_hash_manifest: Option<PathBuf>,
+
+    /// Set the permissions of uploaded files, in octal (e.g. 0644)
+    ///
+    /// Only has an effect on unix platforms.
+    #[arg(
+        long = "upload-file-mode",
+        value_parser(parse_file_mode),
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_UPLOAD_FILE_MODE"
+    )]
+    pub upload_file_mode: Option<u32>,
+
+    /// Set the permissions of directories created through uploads, in octal (e.g. 0755)
+    ///
+    /// Only has an effect on unix platforms.
+    #[arg(
+        long = "upload-dir-mode",
+        value_parser(parse_file_mode),
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_UPLOAD_DIR_MODE"
+    )]
+    pub upload_dir_mode: Option<u32>,
+
+    /// Reject uploads of files whose names are longer than this many bytes
+    ///
+    /// Useful for filesystems that limit the length of file names, which would otherwise make such
+    /// uploads fail with an obscure error. The limit applies to each directory of the uploaded
+    /// path as well. It also covers the temporary name a file is written under while it's
+    /// verified (12 bytes longer) or uploaded with tus (28 bytes longer).
+    #[arg(
+        long = "max-filename-length",
+        value_name = "BYTES",
+        requires = "allowed_upload_dir",
+        value_parser = clap::value_parser!(u64).range(13..),
+        env = "MINISERVE_MAX_FILENAME_LENGTH"
+    )]
+    pub max_filename_length: Option<u64>,
+
+    /// Store uploaded files once per content, so that identical uploads take up space only once
+    ///
+    /// The content of uploads is stored in the `.blobs` directory of the served directory, named
+    /// after its SHA-256 checksum. The uploaded files are hard links to these blobs. Blobs are
+    /// kept when the files linking to them are deleted. Hard links can't cross filesystems, so
+    /// uploads to directories on another filesystem than `.blobs` are stored as copies instead.
+    #[arg(
+        long = "cas-upload",
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_CAS_UPLOAD"
+    )]
+    pub cas_upload: bool,
+
+    /// Extract uploaded zip and tar archives into the upload directory
+    ///
+    /// Entries escaping the upload directory are rejected, and hidden entries and symlinks are
+    /// subject to the same restrictions as regular uploads.
+    #[arg(
+        long = "extract-uploads",
+        requires = "allowed_upload_dir",
+        env = "MINISERVE_EXTRACT_UPLOADS"
+    )]
+    pub extract_uploads: bool,
+
+    /// Delete uploaded archives after they have been extracted
+    #[arg(
+        long = "delete-extracted-archives",
+        requires = "extract_uploads",
+        env = "MINISERVE_DELETE_EXTRACTED_ARCHIVES"
+    )]
+    pub delete_extracted_archives: bool,
+
+    /// Abort extracting an uploaded archive once its content exceeds this size, e.g. 1GB
+    ///
+    /// Files and directories created by the aborted extraction are removed again.
+    #[arg(
+        long = "extract-max-total-size",
+        requires = "extract_uploads",
+        env = "MINISERVE_EXTRACT_MAX_TOTAL_SIZE"
+    )]
+    pub extract_max_total_size: Option<ByteSize>,
+
+    /// Abort extracting an uploaded archive once its content exceeds this multiple of its size
+    ///
+    /// This protects against zip bombs, which decompress to vastly more data than they take up.
+    /// Files and directories created by the aborted extraction are removed again.
+    #[arg(
+        long = "extract-max-ratio",
+        requires = "extract_uploads",
+        env = "MINISERVE_EXTRACT_MAX_RATIO"
+    )]
+    pub extract_max_ratio: Option<u64>,
+
     /// Enable uncompressed tar archive generation
     #[arg(short = 'r', long = "enable-tar", env = "MINISERVE_ENABLE_TAR")]
     pub enable_tar: bool,
@@ -213,10 +731,61 @@ pub struct CliArgs {
     /// Enable zip archive generation
     ///
     /// WARNING: Zipping large directories can result in out-of-memory exception
-    /// because zip generation is done in memory and cannot be sent on the fly
+    /// because zip generation is done in memory and cannot be sent on the fly.
+    /// See --archive-memory-limit.
     #[arg(short = 'z', long = "enable-zip", env = "MINISERVE_ENABLE_ZIP")]
     pub enable_zip: bool,
 
+    /// Enable downloading the files of a directory as a zip, leaving out its subdirectories
+    ///
+    /// The files are placed at the root of the archive. Like with --enable-zip, the archive is
+    /// generated in memory.
+    #[arg(long = "enable-flat-zip", env = "MINISERVE_ENABLE_FLAT_ZIP")]
+    pub enable_flat_zip: bool,
+
+    /// Add a MANIFEST.sha256 file listing the checksums of all files to tar archives
+    ///
+    /// The checksums can be verified with `sha256sum -c MANIFEST.sha256` after extraction.
+    #[arg(long = "archive-manifest", env = "MINISERVE_ARCHIVE_MANIFEST")]
+    pub archive_manifest: bool,
+
+    /// Leave directories which contain no files out of generated archives
+    #[arg(
+        long = "archive-skip-empty-dirs",
+        env = "MINISERVE_ARCHIVE_SKIP_EMPTY_DIRS"
+    )]
+    pub archive_skip_empty_dirs: bool,
+
+    /// Don't allow downloading the whole served directory as an archive
+    ///
+    /// Archives of its subdirectories can still be downloaded.
+    #[arg(long = "no-root-archive", env = "MINISERVE_NO_ROOT_ARCHIVE")]
+    pub no_root_archive: bool,
+
+    /// Assemble zip archives of directories larger than this in a temporary file, e.g. 100MB
+    ///
+    /// By default, zip archives are always assembled in memory before being sent.
+    #[arg(long = "archive-memory-limit", env = "MINISERVE_ARCHIVE_MEMORY_LIMIT")]
+    pub archive_memory_limit: Option<ByteSize>,
+
+    /// Keep generated archives in this directory and serve them from there
+    ///
+    /// Cached archives can be downloaded with range requests, so that interrupted downloads can
+    /// be resumed. They are recreated when the archived directory is modified, but outdated
+    /// archives are not removed.
+    #[arg(
+        long = "archive-cache-dir",
+        value_hint = ValueHint::DirPath,
+        env = "MINISERVE_ARCHIVE_CACHE_DIR"
+    )]
+    pub archive_cache_dir: Option<PathBuf>,
+
+    /// Place the content of a directory at the root of generated archives
+    ///
+    /// By default, it is wrapped in a top-level folder named after the directory.
+    #[arg(long = "archive-flat", env = "MINISERVE_ARCHIVE_FLAT")]
+    pub archive_flat: bool,
+
     /// Compress response
     ///
     /// WARNING: Enabling this option may slow down transfers due to CPU overhead, so it is
@@ -231,14 +800,45 @@ pub struct CliArgs {
     )]
     pub compress_response: bool,
 
+    /// Gzip generated directory listings only, leaving file downloads uncompressed
+    ///
+    /// This saves bandwidth for large listings without spending CPU on every file transfer.
+    #[arg(long = "compress-listings", env = "MINISERVE_COMPRESS_LISTINGS")]
+    pub compress_listings: bool,
+
     /// List directories first
     #[arg(short = 'D', long = "dirs-first", env = "MINISERVE_DIRS_FIRST")]
     pub dirs_first: bool,
 
+    /// Group listed entries, keeping the active sorting within each group
+    ///
+    /// "type" lists directories, then symlinked directories, then files, then symlinked files.
+    /// This is a finer-grained version of --dirs-first.
+    #[arg(long = "group-by", value_enum, env = "MINISERVE_GROUP_BY")]
+    pub group_by: Option<GroupBy>,
+
+    /// List entries with these names first, regardless of the active sorting
+    ///
+    /// The active sorting still applies among pinned entries, e.g. with
+    /// `--pin-first README.md,index.html`.
+    #[arg(
+        long = "pin-first",
+        value_name = "NAMES",
+        value_delimiter = ',',
+        env = "MINISERVE_PIN_FIRST"
+    )]
+    pub pin_first: Vec<String>,
+
     /// Shown instead of host in page title and heading
     #[arg(short = 't', long = "title", env = "MINISERVE_TITLE")]
     pub title: Option<String>,
 
+    /// Show a message in a dismissible banner atop every listing
+    ///
+    /// Useful for outage notices or usage policies, e.g. "Files are deleted nightly at 2am".
+    #[arg(long = "banner", value_name = "MESSAGE", env = "MINISERVE_BANNER")]
+    pub banner: Option<String>,
+
     /// Inserts custom headers into the responses. Specify each header as a 'Header:Value' pair.
     /// This parameter can be used multiple times to add multiple headers.
     ///
@@ -253,6 +853,103 @@ pub struct CliArgs {
     )]
     pub header: Vec<HeaderMap>,
 
+    /// Register a custom route returning a static response. Specify each route as a
+    /// 'path=status:body' pair. This parameter can be used multiple times to add multiple routes.
+    ///
+    /// Example:
+    /// --route "/ping=200:pong" --route "/healthz=204:"
+    #[arg(
+        long = "route",
+        value_parser(parse_custom_route),
+        num_args(1),
+        env = "MINISERVE_ROUTE"
+    )]
+    pub custom_routes: Vec<CustomRoute>,
+
+    /// Add a link to the footer of listings. Specify each link as a 'text=url' pair, where the
+    /// url is either a path starting with a slash or an http(s) URL. This parameter can be used
+    /// multiple times to add multiple links.
+    ///
+    /// Example:
+    /// --footer-link "Home=https://example.com" --footer-link "Help=/help.html"
+    #[arg(
+        long = "footer-link",
+        value_parser(parse_footer_link),
+        num_args(1),
+        env = "MINISERVE_FOOTER_LINK"
+    )]
+    pub footer_links: Vec<FooterLink>,
+
+    /// Reject requests using this HTTP method with 405 Method Not Allowed.
+    /// This parameter can be used multiple times to disable multiple methods.
+    ///
+    /// Example:
+    /// --disable-method TRACE --disable-method DELETE
+    #[arg(
+        long = "disable-method",
+        value_name = "METHOD",
+        value_parser(parse_method),
+        num_args(1),
+        env = "MINISERVE_DISABLE_METHOD"
+    )]
+    pub disabled_methods: Vec<Method>,
+
+    /// Comma-separated list of host names the server may be reached at, e.g. example.local
+    ///
+    /// Requests with any other Host header are rejected, which protects against DNS rebinding.
+    /// By default, all hosts are allowed.
+    #[arg(
+        long = "allowed-hosts",
+        value_name = "HOSTS",
+        value_delimiter = ',',
+        env = "MINISERVE_ALLOWED_HOSTS"
+    )]
+    pub allowed_hosts: Vec<String>,
+
+    /// Header carrying the ID of every request, e.g. X-Request-Id
+    ///
+    /// IDs sent by clients or proxies are kept, and new ones are generated for requests without
+    /// one. The ID is returned in the same header of the response and written to the access log.
+    #[arg(
+        long = "request-id-header",
+        value_name = "HEADER",
+        env = "MINISERVE_REQUEST_ID_HEADER"
+    )]
+    pub request_id_header: Option<HeaderName>,
+
+    /// Leave query strings out of the access log
+    ///
+    /// Query strings of requests and referers are replaced with a placeholder, since they may
+    /// carry secrets like signed links. Authorization headers are never logged either way.
+    #[arg(long = "log-redact", env = "MINISERVE_LOG_REDACT")]
+    pub log_redact: bool,
+
+    /// Send this value in the Server header of all responses
+    #[arg(
+        long = "server-header",
+        value_name = "VALUE",
+        conflicts_with = "no_server_header",
+        env = "MINISERVE_SERVER_HEADER"
+    )]
+    pub server_header: Option<HeaderValue>,
+
+    /// Strip the Server header from all responses, including one set with --header
+    #[arg(long = "no-server-header", env = "MINISERVE_NO_SERVER_HEADER")]
+    pub no_server_header: bool,
+
+    /// Link entries and breadcrumbs relative to the listed directory
+    ///
+    /// Saved listing pages then keep working when opened from a local copy of the directories.
+    #[arg(long = "relative-links", env = "MINISERVE_RELATIVE_LINKS")]
+    pub relative_links: bool,
+
+    /// Collapse chains of directories that only contain a single directory
+    ///
+    /// Listings link such directories straight to the deepest directory of the chain, and the
+    /// breadcrumbs show the chain as a single path.
+    #[arg(long = "collapse-single-dirs", env = "MINISERVE_COLLAPSE_SINGLE_DIRS")]
+    pub collapse_single_dirs: bool,
+
     /// Visualize symlinks in directory listing
     #[arg(
         short = 'l',
@@ -261,7 +958,112 @@ pub struct CliArgs {
     )]
     pub show_symlink_info: bool,
 
+    /// Show broken symlinks in directory listing
+    ///
+    /// They are shown with their (unresolvable) target but can't be followed.
+    #[arg(long = "show-broken-symlinks", env = "MINISERVE_SHOW_BROKEN_SYMLINKS")]
+    pub show_broken_symlinks: bool,
+
+    /// Hide files that were last modified longer ago than this, e.g. 7d or 12h
+    ///
+    /// Directories are always listed.
+    #[arg(
+        long = "hide-files-older-than",
+        value_name = "AGE",
+        value_parser(humantime::parse_duration),
+        env = "MINISERVE_HIDE_FILES_OLDER_THAN"
+    )]
+    pub hide_files_older_than: Option<Duration>,
+
+    /// Only list and serve files whose names match this glob, e.g. "*.pdf"
+    ///
+    /// Can be given multiple times to allow files matching any of the globs. `*` matches any
+    /// number of characters and `?` a single one. Directories are always listed. The filter also
+    /// applies to archives of directories, directory trees and the sitemap.
+    #[arg(
+        long = "filter",
+        value_name = "GLOB",
+        value_parser(parse_filter),
+        num_args(1),
+        env = "MINISERVE_FILTER"
+    )]
+    pub filters: Vec<Regex>,
+
+    /// Also refuse to serve files hidden by --hide-files-older-than when requested directly
+    #[arg(
+        long = "block-old-files",
+        requires = "hide_files_older_than",
+        env = "MINISERVE_BLOCK_OLD_FILES"
+    )]
+    pub block_old_files: bool,
+
+    /// Show file sizes in SI units (kB, MB, base 1000) instead of IEC units (KiB, MiB, base 1024)
+    #[arg(long = "si-units", env = "MINISERVE_SI_UNITS")]
+    pub si_units: bool,
+
+    /// Show the content type of files in the directory listing
+    ///
+    /// The content type is guessed from the file extension.
+    #[arg(long = "show-mimetype", env = "MINISERVE_SHOW_MIMETYPE")]
+    pub show_mimetype: bool,
+
+    /// Show a link next to every file in the directory listing that always downloads it
+    ///
+    /// Unlike the regular link, it never opens files in the browser. Any file can be downloaded
+    /// like this by adding `?download-file=true` to its URL.
+    #[arg(long = "show-download-links", env = "MINISERVE_SHOW_DOWNLOAD_LINKS")]
+    pub show_download_links: bool,
+
+    /// Show these extended attributes of files in the directory listing
+    ///
+    /// For instance, `--show-xattrs user.comment` shows the comments set on files. Attributes
+    /// that aren't set or aren't valid UTF-8 are skipped. This has no effect on platforms without
+    /// extended attributes.
+    #[arg(
+        long = "show-xattrs",
+        value_name = "NAMES",
+        value_delimiter = ',',
+        env = "MINISERVE_SHOW_XATTRS"
+    )]
+    pub show_xattrs: Vec<String>,
+
+    /// Show the free and total space of the filesystem of the served directory in the footer
+    ///
+    /// This lets users check whether there is room for their uploads. Only supported on Unix.
+    #[arg(long = "show-disk-space", env = "MINISERVE_SHOW_DISK_SPACE")]
+    pub show_disk_space: bool,
+
+    /// Serve audio and video files ready for streaming in the browser
+    ///
+    /// Common media files are served with the content type browsers expect and are never
+    /// compressed, so that players can seek in them using range requests.
+    #[arg(
+        long = "enable-media-streaming",
+        env = "MINISERVE_ENABLE_MEDIA_STREAMING"
+    )]
+    pub media_streaming: bool,
+
+    /// Detect the content type of served files from their first bytes
+    ///
+    /// This overrides the type guessed from the file extension for known binary formats, which
+    /// helps to serve files with wrong or missing extensions.
+    #[arg(long = "mime-sniff", env = "MINISERVE_MIME_SNIFF")]
+    pub mime_sniff: bool,
+
+    /// Serve HTML files as plain text, so that browsers show their source instead of rendering them
+    ///
+    /// This also prevents uploaded HTML files from running scripts in the context of this server.
+    #[arg(long = "serve-html-as-text", env = "MINISERVE_SERVE_HTML_AS_TEXT")]
+    pub serve_html_as_text: bool,
+
+    /// Preview images, videos and text files in the listing instead of navigating to them
+    #[arg(long = "preview", env = "MINISERVE_PREVIEW")]
+    pub preview: bool,
+
     /// Hide version footer
+    ///
+    /// This also disables the `/__miniserve_internal/version` route, which reports the version
+    /// and build of miniserve as JSON.
     #[arg(
         short = 'F',
         long = "hide-version-footer",
@@ -290,25 +1092,203 @@ pub struct CliArgs {
     pub print_manpage: bool,
 
     /// TLS certificate to use
+    ///
+    /// Can be provided multiple times together with the same number of --tls-key, in which case
+    /// the certificate is picked by the server name the client asks for (SNI). The first
+    /// certificate is used for clients not sending a server name.
     #[cfg(feature = "tls")]
     #[arg(long = "tls-cert", requires = "tls_key", value_hint = ValueHint::FilePath, env = "MINISERVE_TLS_CERT")]
-    pub tls_cert: Option<PathBuf>,
+    pub tls_cert: Vec<PathBuf>,
 
     /// TLS private key to use
+    ///
+    /// Can be provided multiple times, each key belongs to the --tls-cert at the same position.
     #[cfg(feature = "tls")]
     #[arg(long = "tls-key", requires = "tls_cert", value_hint = ValueHint::FilePath, env = "MINISERVE_TLS_KEY")]
-    pub tls_key: Option<PathBuf>,
+    pub tls_key: Vec<PathBuf>,
+
+    /// Minimum TLS protocol version to accept
+    ///
+    /// Raise it to 1.3 to refuse clients that only speak TLS 1.2.
+    #[cfg(feature = "tls")]
+    #[arg(
+        long = "tls-min-version",
+        requires = "tls_cert",
+        value_enum,
+        default_value = "1.2",
+        env = "MINISERVE_TLS_MIN_VERSION"
+    )]
+    pub tls_min_version: TlsVersion,
+
+    /// Comma-separated list of TLS cipher suites to offer, e.g. TLS13_AES_256_GCM_SHA384
+    ///
+    /// Defaults to all cipher suites supported by rustls.
+    #[cfg(feature = "tls")]
+    #[arg(
+        long = "tls-cipher-suites",
+        requires = "tls_cert",
+        value_delimiter = ',',
+        env = "MINISERVE_TLS_CIPHER_SUITES"
+    )]
+    pub tls_cipher_suites: Vec<String>,
+
+    /// Also listen for plain HTTP on this port, redirecting all requests to HTTPS
+    #[cfg(feature = "tls")]
+    #[arg(
+        long = "redirect-http",
+        value_name = "PORT",
+        requires = "tls_cert",
+        env = "MINISERVE_REDIRECT_HTTP"
+    )]
+    pub redirect_http: Option<u16>,
+
+    /// Verify the served files against this manifest of SHA-256 checksums at startup
+    ///
+    /// The manifest uses the format of `sha256sum`, with one `<checksum>  <file name>` line per
+    /// file, relative to the served directory. miniserve refuses to start if a listed file is
+    /// missing or doesn't match its checksum.
+    #[arg(
+        long = "verify-manifest",
+        value_hint = ValueHint::FilePath,
+        env = "MINISERVE_VERIFY_MANIFEST"
+    )]
+    pub verify_manifest: Option<PathBuf>,
+
+    /// Only warn about files that don't match the manifest given by --verify-manifest
+    #[arg(
+        long = "verify-manifest-warn-only",
+        requires = "verify_manifest",
+        env = "MINISERVE_VERIFY_MANIFEST_WARN_ONLY"
+    )]
+    pub verify_manifest_warn_only: bool,
+
+    /// Serve a generated sitemap.xml enumerating all served files
+    #[arg(long, env = "MINISERVE_SITEMAP")]
+    pub sitemap: bool,
+
+    /// Serve a robots.txt controlling crawler access
+    ///
+    /// Either `allow` to allow crawling everything, `deny` to disallow crawling anything, or the
+    /// path of a file to serve. Takes precedence over a robots.txt in the served directory.
+    #[arg(
+        long = "robots",
+        value_name = "allow|deny|FILE",
+        value_parser(parse_robots),
+        env = "MINISERVE_ROBOTS"
+    )]
+    pub robots: Option<Robots>,
+
+    /// When serving a single file, show a landing page with a download link instead of the file
+    #[arg(long = "single-file-landing", env = "MINISERVE_SINGLE_FILE_LANDING")]
+    pub single_file_landing: bool,
+
+    /// Add Open Graph meta tags to pages so chat apps show link previews
+    #[arg(long = "open-graph", env = "MINISERVE_OPEN_GRAPH")]
+    pub open_graph: bool,
+
+    /// Switch to this user (name or uid) after binding to the network interfaces
+    ///
+    /// Allows binding to privileged ports as root without serving files as root.
+    #[cfg(unix)]
+    #[arg(long = "user", env = "MINISERVE_USER")]
+    pub user: Option<String>,
+
+    /// Switch to this group (name or gid) after binding to the network interfaces
+    ///
+    /// Defaults to the primary group of --user.
+    #[cfg(unix)]
+    #[arg(long = "group", env = "MINISERVE_GROUP")]
+    pub group: Option<String>,
+
+    /// Change the root directory to the served directory after binding to the network interfaces
+    ///
+    /// Requires running as root.
+    #[cfg(unix)]
+    #[arg(
+        long = "chroot",
+        conflicts_with_all = ["archive_cache_dir", "audit_log"],
+        env = "MINISERVE_CHROOT"
+    )]
+    pub chroot: bool,
+
+    /// Show titles and descriptions from a `.meta.json` file in each directory
+    ///
+    /// The file maps entry names to objects with optional "title" and "description" keys and
+    /// enables sorting by title.
+    #[arg(long = "metadata-sidecar", env = "MINISERVE_METADATA_SIDECAR")]
+    pub metadata_sidecar: bool,
+
+    /// Serve the full recursive tree below a directory as JSON when requested with `?tree=true`
+    ///
+    /// Very deep or large trees are truncated, which is indicated in the response.
+    #[arg(long = "tree-api", env = "MINISERVE_TREE_API")]
+    pub tree_api: bool,
+
+    /// Serve directory listings as JSON when requested with `?format=json`
+    ///
+    /// HTML listings then advertise their JSON form in a `Link` header, so that crawlers can
+    /// discover it.
+    #[arg(long = "emit-json-index", env = "MINISERVE_EMIT_JSON_INDEX")]
+    pub emit_json_index: bool,
 
     /// Enable README.md rendering in directories
     #[arg(long, env = "MINISERVE_README")]
     pub readme: bool,
 
+    /// Show a README generated from this Markdown template in directories without one
+    ///
+    /// The placeholders `{dir}`, `{file_count}` and `{total_size}` are replaced by the path of
+    /// the directory, the number of files in it and their total size. READMEs of directories are
+    /// rendered as with --readme.
+    #[arg(
+        long = "auto-readme",
+        value_name = "TEMPLATE",
+        env = "MINISERVE_AUTO_README"
+    )]
+    pub auto_readme: Option<String>,
+
+    /// Write the rendered listing of a directory into a file of this name whenever it is requested
+    ///
+    /// This allows another web server to serve the generated pages. They're rendered for an
+    /// anonymous visitor in the default sorting. Files of that name that miniserve didn't write
+    /// are left alone, while the written ones aren't listed.
+    #[arg(
+        long = "write-index",
+        value_name = "FILENAME",
+        value_parser(parse_file_name),
+        env = "MINISERVE_WRITE_INDEX"
+    )]
+    pub write_index: Option<String>,
+
+    /// Show the contents of a .description.txt or .description.html file above the listing
+    ///
+    /// The HTML variant is escaped unless --trust-description-html is given.
+    #[arg(long = "descriptions", env = "MINISERVE_DESCRIPTIONS")]
+    pub descriptions: bool,
+
+    /// Render .description.html files as HTML instead of escaping them
+    ///
+    /// Only use this if everyone who can write to the served directory is trusted.
+    #[arg(
+        long = "trust-description-html",
+        requires = "descriptions",
+        env = "MINISERVE_TRUST_DESCRIPTION_HTML"
+    )]
+    pub trust_description_html: bool,
+
     /// Disable indexing
     ///
     /// This will prevent directory listings from being generated
     /// and return an error instead.
     #[arg(short = 'I', long, env = "MINISERVE_DISABLE_INDEXING")]
     pub disable_indexing: bool,
+
+    /// Refuse to list directories nested deeper than this below the served directory
+    ///
+    /// The served directory itself is at depth 0. Files in deeper directories can still be
+    /// downloaded directly.
+    #[arg(long = "max-depth", value_name = "DEPTH", env = "MINISERVE_MAX_DEPTH")]
+    pub max_depth: Option<usize>,
 }
 
 /// Checks whether an interface is valid, i.e. it can be parsed into an IP address
@@ -316,6 +1296,69 @@ fn parse_interface(src: &str) -> Result<IpAddr, std::net::AddrParseError> {
     src.parse::<IpAddr>()
 }
 
+/// Checks whether a base URL is absolute, i.e. it has a scheme and a host
+fn parse_external_base_url(src: &str) -> Result<Uri, String> {
+    src.parse::<Uri>()
+        .ok()
+        .filter(|uri| uri.scheme().is_some() && uri.authority().is_some())
+        .ok_or_else(|| {
+            format!("{src} is not an absolute URL. Expected e.g. https://example.com/files")
+        })
+}
+
+/// Parse the robots.txt to serve, anything but `allow` and `deny` is treated as a file path
+fn parse_robots(src: &str) -> Result<Robots, Infallible> {
+    Ok(match src {
+        "allow" => Robots::Allow,
+        "deny" => Robots::Deny,
+        path => Robots::File(PathBuf::from(path)),
+    })
+}
+
+/// Parse where to redirect after uploads, anything but `none` is treated as a location
+pub fn parse_upload_redirect(src: &str) -> Result<UploadRedirect, Infallible> {
+    Ok(match src {
+        "none" => UploadRedirect::None,
+        location => UploadRedirect::Location(location.to_string()),
+    })
+}
+
+/// Parse a theme and the path of the favicon shown while it's active
+fn parse_themed_favicon(src: &str) -> Result<(ThemeSlug, PathBuf), String> {
+    let (theme, path) = src
+        .split_once('=')
+        .filter(|(_, path)| !path.is_empty())
+        .ok_or_else(|| format!("{src} is not a valid themed favicon. Expected THEME=FILE"))?;
+    let theme = ThemeSlug::from_str(theme, true)?;
+    Ok((theme, PathBuf::from(path)))
+}
+
+/// Parse a glob matching file names
+fn parse_filter(src: &str) -> Result<Regex, String> {
+    file_utils::glob_to_regex(src).map_err(|e| format!("{src} is not a valid glob: {e}"))
+}
+
+/// Parse octal file permissions
+fn parse_file_mode(src: &str) -> Result<u32, String> {
+    u32::from_str_radix(src, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| format!("{src} is not a valid octal file mode. Expected e.g. 0644"))
+}
+
+/// Checks whether a file name is a plain name, i.e. it doesn't point to another directory
+fn parse_file_name(src: &str) -> Result<String, String> {
+    Some(src)
+        .filter(|name| !matches!(*name, "" | "." | "..") && !name.contains(['/', '\\']))
+        .map(str::to_string)
+        .ok_or_else(|| format!("{src} is not a plain file name. Expected e.g. index.html"))
+}
+
+fn parse_method(src: &str) -> Result<Method, String> {
+    Method::from_bytes(src.to_uppercase().as_bytes())
+        .map_err(|_| format!("{src} is not a valid HTTP method"))
+}
+
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum AuthParseError {
     /// Might occur if the HTTP credential string does not respect the expected format
@@ -398,6 +1441,98 @@ pub fn parse_header(src: &str) -> Result<HeaderMap, httparse::Error> {
     Ok(header_map)
 }
 
+/// A route returning a static response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomRoute {
+    /// Path of the route, always starting with a slash
+    pub path: String,
+
+    /// Status code of the response
+    pub status: StatusCode,
+
+    /// Body of the response
+    pub body: String,
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum CustomRouteParseError {
+    /// Might occur if the route string does not respect the expected format
+    #[error("Invalid format for custom route. Expected path=status:body")]
+    InvalidRouteFormat,
+
+    /// Might occur if the status is not a valid HTTP status code
+    #[error("{0} is not a valid HTTP status code")]
+    InvalidStatusCode(String),
+}
+
+/// Parse a custom route definition
+pub fn parse_custom_route(src: &str) -> Result<CustomRoute, CustomRouteParseError> {
+    use CustomRouteParseError as E;
+
+    let (path, response) = src.split_once('=').ok_or(E::InvalidRouteFormat)?;
+    let (status, body) = response.split_once(':').ok_or(E::InvalidRouteFormat)?;
+
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return Err(E::InvalidRouteFormat);
+    }
+
+    let status = status
+        .parse::<u16>()
+        .ok()
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .ok_or_else(|| E::InvalidStatusCode(status.to_owned()))?;
+
+    Ok(CustomRoute {
+        path: format!("/{path}"),
+        status,
+        body: body.to_owned(),
+    })
+}
+
+/// A link shown in the footer of listings
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FooterLink {
+    /// Text of the link
+    pub text: String,
+
+    /// Target of the link
+    pub url: String,
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum FooterLinkParseError {
+    /// Might occur if the link string does not respect the expected format
+    #[error("Invalid format for footer link. Expected text=url")]
+    InvalidLinkFormat,
+
+    /// Might occur if the url is neither an absolute path nor an http(s) URL
+    #[error("{0} is not a valid link. Expected a path starting with / or an http(s) URL")]
+    InvalidUrl(String),
+}
+
+/// Parse a footer link definition
+pub fn parse_footer_link(src: &str) -> Result<FooterLink, FooterLinkParseError> {
+    use FooterLinkParseError as E;
+
+    let (text, url) = src.split_once('=').ok_or(E::InvalidLinkFormat)?;
+    let (text, url) = (text.trim(), url.trim());
+    if text.is_empty() {
+        return Err(E::InvalidLinkFormat);
+    }
+
+    let is_valid = url.parse::<Uri>().is_ok()
+        && (url.starts_with('/') || url.starts_with("http://") || url.starts_with("https://"));
+    if !is_valid {
+        return Err(E::InvalidUrl(url.to_owned()));
+    }
+
+    Ok(FooterLink {
+        text: text.to_owned(),
+        url: url.to_owned(),
+    })
+}
+
 #[rustfmt::skip]
 #[cfg(test)]
 mod tests {
@@ -459,4 +1594,86 @@ mod tests {
         let err = parse_auth(auth_string).unwrap_err();
         assert_eq!(format!("{err}"), err_msg.to_owned());
     }
+
+    #[rstest(
+        route_string, path, status, body,
+        case("/ping=200:pong", "/ping", 200, "pong"),
+        case("health/=204:", "/health", 204, ""),
+        case("/teapot=418:a:b", "/teapot", 418, "a:b")
+    )]
+    fn parse_custom_route_valid(route_string: &str, path: &str, status: u16, body: &str) {
+        assert_eq!(
+            parse_custom_route(route_string).unwrap(),
+            CustomRoute {
+                path: path.to_owned(),
+                status: StatusCode::from_u16(status).unwrap(),
+                body: body.to_owned(),
+            },
+        );
+    }
+
+    #[rstest(
+        route_string, err_msg,
+        case("/ping", "Invalid format for custom route. Expected path=status:body"),
+        case("/ping=200", "Invalid format for custom route. Expected path=status:body"),
+        case("/=200:pong", "Invalid format for custom route. Expected path=status:body"),
+        case("/ping=abc:pong", "abc is not a valid HTTP status code"),
+    )]
+    fn parse_custom_route_invalid(route_string: &str, err_msg: &str) {
+        let err = parse_custom_route(route_string).unwrap_err();
+        assert_eq!(format!("{err}"), err_msg.to_owned());
+    }
+
+    #[rstest(
+        link_string, text, url,
+        case("Home=https://example.com", "Home", "https://example.com"),
+        case("Help=/help.html", "Help", "/help.html"),
+        case("Search=/search?q=a", "Search", "/search?q=a")
+    )]
+    fn parse_footer_link_valid(link_string: &str, text: &str, url: &str) {
+        assert_eq!(
+            parse_footer_link(link_string).unwrap(),
+            FooterLink {
+                text: text.to_owned(),
+                url: url.to_owned(),
+            },
+        );
+    }
+
+    #[rstest(
+        link_string, err_msg,
+        case("Home", "Invalid format for footer link. Expected text=url"),
+        case("=/help.html", "Invalid format for footer link. Expected text=url"),
+        case("Evil=javascript:alert(1)", "javascript:alert(1) is not a valid link. Expected a path starting with / or an http(s) URL"),
+        case("Help=help.html", "help.html is not a valid link. Expected a path starting with / or an http(s) URL"),
+    )]
+    fn parse_footer_link_invalid(link_string: &str, err_msg: &str) {
+        let err = parse_footer_link(link_string).unwrap_err();
+        assert_eq!(format!("{err}"), err_msg.to_owned());
+    }
+
+    #[rstest(
+        mode_string, mode,
+        case("0644", Some(0o644)),
+        case("755", Some(0o755)),
+        case("2775", Some(0o2775)),
+        case("0888", None),
+        case("17777", None),
+        case("rw-r--r--", None)
+    )]
+    fn parse_file_mode_values(mode_string: &str, mode: Option<u32>) {
+        assert_eq!(parse_file_mode(mode_string).ok(), mode);
+    }
+
+    #[rstest(
+        method_string, method,
+        case("TRACE", Some(Method::TRACE)),
+        case("delete", Some(Method::DELETE)),
+        case("PROPFIND", Some(Method::from_bytes(b"PROPFIND").unwrap())),
+        case("GET POST", None),
+        case("", None)
+    )]
+    fn parse_method_values(method_string: &str, method: Option<Method>) {
+        assert_eq!(parse_method(method_string).ok(), method);
+    }
 }
@@ -0,0 +1,79 @@
+//! Audit log of the changes made to the served directory
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use actix_web::{HttpMessage, HttpRequest};
+use chrono::Local;
+use strum::Display;
+
+use crate::auth::CurrentUser;
+
+/// Changes recorded in the audit log
+#[derive(Clone, Copy, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum AuditAction {
+    /// A file was uploaded
+    Upload,
+
+    /// A directory was created
+    Mkdir,
+}
+
+/// Append-only audit log, if enabled
+pub struct AuditLog {
+    file: Option<Mutex<File>>,
+    root: PathBuf,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `log_path` for appending, creating it if needed
+    ///
+    /// Logged paths are relative to `root`.
+    pub fn open(log_path: Option<&Path>, root: &Path) -> std::io::Result<Self> {
+        let file = log_path
+            .map(|log_path| OpenOptions::new().create(true).append(true).open(log_path))
+            .transpose()?
+            .map(Mutex::new);
+
+        Ok(Self {
+            file,
+            root: root.canonicalize()?,
+        })
+    }
+
+    /// Appends a line for a change made by `req` to the audit log
+    ///
+    /// The line consists of the timestamp, client IP, user, action, path and number of bytes,
+    /// separated by tabs.
+    pub fn record(&self, req: &HttpRequest, action: AuditAction, path: &Path, bytes: Option<u64>) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        let client = req
+            .peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let user = req
+            .extensions()
+            .get::<CurrentUser>()
+            .map(|user| user.name.clone())
+            .unwrap_or_else(|| "-".to_string());
+        let path = path.strip_prefix(&self.root).unwrap_or(path);
+        let bytes = bytes.map_or_else(|| "-".to_string(), |bytes| bytes.to_string());
+
+        let line = format!(
+            "{}\t{client}\t{user}\t{action}\t/{}\t{bytes}\n",
+            Local::now().to_rfc3339(),
+            path.display(),
+        );
+
+        // The file is unbuffered, so every line is written out right away
+        if let Err(e) = file.lock().unwrap().write_all(line.as_bytes()) {
+            log::error!("Failed to write to the audit log: {e}");
+        }
+    }
+}
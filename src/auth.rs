@@ -1,4 +1,9 @@
-use actix_web::{dev::ServiceRequest, HttpMessage};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{dev::ServiceRequest, web, HttpMessage};
 use actix_web_httpauth::extractors::basic::BasicAuth;
 use sha2::{Digest, Sha256, Sha512};
 
@@ -69,27 +74,155 @@ pub fn get_hash<T: Digest>(text: &str) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+/// Get the CSRF token of uploads for `user`, signed with `secret`
+pub fn csrf_token(secret: &str, user: Option<&CurrentUser>) -> String {
+    let user = user.map(|user| user.name.as_str()).unwrap_or_default();
+    hex::encode(hmac_sha256(secret.as_bytes(), user.as_bytes()))
+}
+
+/// Return `true` if `token` is the CSRF token of uploads for `user`
+pub fn verify_csrf_token(secret: &str, user: Option<&CurrentUser>, token: &str) -> bool {
+    let expected = csrf_token(secret, user);
+
+    // Compare in constant time to not leak how much of the token is right
+    expected.len() == token.len()
+        && expected
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Compute the HMAC-SHA256 of `message` as described in RFC 2104
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        let digest = Sha256::digest(key);
+        block[..digest.len()].copy_from_slice(&digest);
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner_pad = block.map(|b| b ^ 0x36);
+    let outer_pad = block.map(|b| b ^ 0x5c);
+
+    let inner = Sha256::new()
+        .chain_update(inner_pad)
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(outer_pad)
+        .chain_update(inner)
+        .finalize()
+        .to_vec()
+}
+
 pub struct CurrentUser {
     pub name: String,
 }
 
+/// Failed authentication attempts of a single client
+struct FailedAttempts {
+    count: u32,
+    last: Instant,
+}
+
+/// Locks clients out after too many failed authentication attempts, if configured
+pub struct AuthThrottle {
+    max_attempts: Option<u32>,
+    lockout: Duration,
+    clients: Mutex<HashMap<IpAddr, FailedAttempts>>,
+}
+
+impl AuthThrottle {
+    pub fn new(max_attempts: Option<u32>, lockout: Duration) -> Self {
+        Self {
+            max_attempts,
+            lockout,
+            clients: Mutex::default(),
+        }
+    }
+
+    /// Checks whether `client` may attempt to authenticate, which it may not if it failed too
+    /// often within the lockout period.
+    ///
+    /// Clients whose lockout period has passed are dropped.
+    fn is_allowed(&self, client: IpAddr) -> bool {
+        let Some(max_attempts) = self.max_attempts else {
+            return true;
+        };
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|_, attempts| attempts.last.elapsed() < self.lockout);
+        !matches!(clients.get(&client), Some(attempts) if attempts.count >= max_attempts)
+    }
+
+    /// Counts a failed attempt of `client`
+    fn record_failure(&self, client: IpAddr) {
+        if self.max_attempts.is_some() {
+            let mut clients = self.clients.lock().unwrap();
+            let attempts = clients.entry(client).or_insert(FailedAttempts {
+                count: 0,
+                last: Instant::now(),
+            });
+            attempts.count += 1;
+            attempts.last = Instant::now();
+        }
+    }
+
+    /// Forgets the attempts of `client` after it authenticated successfully
+    fn record_success(&self, client: IpAddr) {
+        if self.max_attempts.is_some() {
+            self.clients.lock().unwrap().remove(&client);
+        }
+    }
+}
+
 pub async fn handle_auth(
     req: ServiceRequest,
     cred: BasicAuth,
 ) -> actix_web::Result<ServiceRequest, (actix_web::Error, ServiceRequest)> {
     let required_auth = &req.app_data::<crate::MiniserveConfig>().unwrap().auth;
+    let throttle = req
+        .app_data::<web::Data<AuthThrottle>>()
+        .cloned()
+        .zip(req.peer_addr().map(|addr| addr.ip()));
+
+    if let Some((throttle, client)) = &throttle {
+        if !throttle.is_allowed(*client) {
+            return Err((RuntimeError::TooManyAuthAttemptsError.into(), req));
+        }
+    }
 
     req.extensions_mut().insert(CurrentUser {
         name: cred.user_id().to_string(),
     });
 
     if match_auth(&cred.into(), required_auth) {
+        if let Some((throttle, client)) = throttle {
+            throttle.record_success(client);
+        }
         Ok(req)
     } else {
+        if let Some((throttle, client)) = throttle {
+            throttle.record_failure(client);
+        }
         Err((RuntimeError::InvalidHttpCredentials.into(), req))
     }
 }
 
+/// Like [`handle_auth`], but lets requests without credentials through anonymously
+pub async fn handle_optional_auth(
+    req: ServiceRequest,
+    cred: Option<BasicAuth>,
+) -> actix_web::Result<ServiceRequest, (actix_web::Error, ServiceRequest)> {
+    match cred {
+        Some(cred) => handle_auth(req, cred).await,
+        None => Ok(req),
+    }
+}
+
 #[rustfmt::skip]
 #[cfg(test)]
 mod tests {
@@ -118,6 +251,26 @@ mod tests {
         assert_eq!(received, expected);
     }
 
+    #[rstest(
+        key, message, hmac,
+        case(&b"Jefe"[..], "what do ya want for nothing?", "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+        case(&[0xaa; 131][..], "Test Using Larger Than Block-Size Key - Hash Key First", "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+    )]
+    fn test_hmac_sha256(key: &[u8], message: &str, hmac: &str) {
+        assert_eq!(hex::encode(hmac_sha256(key, message.as_bytes())), hmac);
+    }
+
+    #[test]
+    fn test_csrf_token_is_tied_to_user() {
+        let user = CurrentUser { name: "obi".to_owned() };
+        let token = csrf_token("secret", Some(&user));
+
+        assert!(verify_csrf_token("secret", Some(&user), &token));
+        assert!(!verify_csrf_token("secret", None, &token));
+        assert!(!verify_csrf_token("other secret", Some(&user), &token));
+        assert!(!verify_csrf_token("secret", Some(&user), &token[1..]));
+    }
+
     /// Helper function that creates a `RequiredAuth` structure and encrypt `password` if necessary
     fn create_required_auth(username: &str, password: &str, encrypt: &str) -> RequiredAuth {
         use RequiredAuthPassword::*;
@@ -1,22 +1,39 @@
 use std::{
+    collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
-use actix_web::http::header::HeaderMap;
+use actix_web::http::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method, Uri,
+};
 use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::ValueEnum;
+use regex::Regex;
+use sha2::{Digest, Sha256, Sha384};
 
 #[cfg(feature = "tls")]
 use rustls_pemfile as pemfile;
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+#[cfg(feature = "tls")]
+use crate::args::TlsVersion;
 
 use crate::{
-    args::{parse_auth, CliArgs, MediaType},
+    args::{
+        parse_auth, CliArgs, CustomRoute, FooterLink, GroupBy, MediaType, Robots, UploadRedirect,
+    },
     auth::RequiredAuth,
     file_utils::sanitize_path,
+    i18n::Language,
     listing::{SortingMethod, SortingOrder},
-    renderer::ThemeSlug,
+    renderer::{self, ListingStyle, ThemeSlug},
 };
 
 /// Possible characters for random routes
@@ -24,12 +41,34 @@ const ROUTE_ALPHABET: [char; 16] = [
     '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'a', 'b', 'c', 'd', 'e', 'f',
 ];
 
+/// Routes registered by miniserve itself inside the route prefix
+const INTERNAL_ROUTES: &[&str] = &[
+    "/sitemap.xml",
+    "/upload",
+    "/upload/progress",
+    "/upload/tus",
+    "/api",
+    "/robots.txt",
+];
+
+/// Prefixes of the internal routes that contain a dynamic segment
+const INTERNAL_ROUTE_PREFIXES: &[&str] = &["/upload/tus/", "/__miniserve_internal/"];
+
 #[derive(Clone)]
 /// Configuration of the Miniserve application
 pub struct MiniserveConfig {
     /// Enable verbose mode
     pub verbose: bool,
 
+    /// Enable logging every completely served file
+    pub log_downloads: bool,
+
+    /// Suppress the startup output on stdout
+    pub quiet: bool,
+
+    /// Shut down after being idle for this long
+    pub shutdown_after_idle: Option<Duration>,
+
     /// Path to be served by miniserve
     pub path: std::path::PathBuf,
 
@@ -39,9 +78,18 @@ pub struct MiniserveConfig {
     /// IP address(es) on which miniserve will be available
     pub interfaces: Vec<IpAddr>,
 
+    /// Addresses whose URLs are shown at startup, all if empty
+    pub display_interfaces: Vec<IpAddr>,
+
     /// Enable HTTP basic authentication
     pub auth: Vec<RequiredAuth>,
 
+    /// Number of failed authentication attempts after which clients are locked out
+    pub auth_max_attempts: Option<u32>,
+
+    /// How long clients are locked out after too many failed authentication attempts
+    pub auth_lockout: Duration,
+
     /// If false, miniserve will serve the current working directory
     pub path_explicitly_chosen: bool,
 
@@ -60,18 +108,36 @@ pub struct MiniserveConfig {
     /// Route prefix; Either empty or prefixed with slash
     pub route_prefix: String,
 
+    /// If enabled, requests to `/` are redirected to the route prefix
+    pub redirect_root_to_prefix: bool,
+
+    /// Base URL used for generated links instead of the one of the request, if any
+    pub external_base_url: Option<Uri>,
+
     /// Randomly generated favicon route
     pub favicon_route: String,
 
     /// Randomly generated css route
     pub css_route: String,
 
+    /// Subresource Integrity hash of the served stylesheet, if enabled
+    pub css_integrity: Option<String>,
+
     /// Default color scheme
     pub default_color_scheme: ThemeSlug,
 
     /// Default dark mode color scheme
     pub default_color_scheme_dark: ThemeSlug,
 
+    /// Themes offered by the theme selector
+    pub themes: Vec<ThemeSlug>,
+
+    /// Content type and content of the favicons of themes, by theme
+    pub themed_favicons: HashMap<String, (mime::Mime, Vec<u8>)>,
+
+    /// Layout of the file listing
+    pub listing_style: ListingStyle,
+
     /// The name of a directory index file to serve, like "index.html"
     ///
     /// Normally, when miniserve serves a directory, it creates a listing for that directory.
@@ -92,12 +158,29 @@ pub struct MiniserveConfig {
     /// `/about` will try to find `about.html` and serve it.
     pub pretty_urls: bool,
 
+    /// Activate static site mode
+    ///
+    /// Paths that don't exist are answered with the site's `404.html`.
+    pub static_site: bool,
+
+    /// Enable looking up requested paths ignoring their case if they don't exist exactly
+    pub case_insensitive: bool,
+
+    /// Default language of the web UI
+    pub lang: Language,
+
+    /// If enabled, the language of the web UI is picked from the Accept-Language header
+    pub follow_accept_language: bool,
+
     /// Enable QR code display
     pub show_qrcode: bool,
 
     /// Enable creating directories
     pub mkdir_enabled: bool,
 
+    /// Fail when creating a directory that already exists
+    pub mkdir_error_on_exists: bool,
+
     /// Enable file upload
     pub file_upload: bool,
 
@@ -110,6 +193,81 @@ pub struct MiniserveConfig {
     /// Enable upload to override existing files
     pub overwrite_files: bool,
 
+    /// How long reading a directory may take before a partial listing is returned
+    pub io_timeout: Option<Duration>,
+
+    /// How long directory listings are cached
+    pub listing_cache_ttl: Option<Duration>,
+
+    /// Number of entries rendered per batch of a lazily loaded listing
+    pub lazy_listing: Option<usize>,
+
+    /// File to which uploads and created directories are logged
+    pub audit_log: Option<PathBuf>,
+
+    /// Maximum size of request bodies in bytes
+    pub max_request_body: Option<u64>,
+
+    /// Maximum number of uploads processed at the same time
+    pub max_concurrent_uploads: Option<u32>,
+
+    /// Maximum number of simultaneous connections per worker
+    pub max_connections: Option<usize>,
+
+    /// Secret used to sign the CSRF tokens of uploads, if they are required
+    pub upload_csrf_secret: Option<String>,
+
+    /// Hosts that uploads are accepted from, as told by the Origin or Referer header
+    pub upload_allowed_referers: Vec<String>,
+
+    /// Where to redirect after uploads instead of the page they were sent from
+    pub upload_redirect: Option<UploadRedirect>,
+
+    /// Enable requiring authentication for uploads while the rest of the site stays public
+    pub upload_requires_auth: bool,
+
+    /// Enable creating missing target directories of uploads
+    pub create_upload_dirs: bool,
+
+    /// Enable only allowing uploads to directories containing an upload marker file
+    pub sidecar_upload_control: bool,
+
+    /// Shell command that uploaded files are streamed into, and that has to accept them
+    pub upload_pipe: Option<String>,
+
+    /// Enable resumable uploads following the tus protocol
+    pub tus: bool,
+
+    /// Enable normalizing the names of uploaded files
+    pub slugify_uploads: bool,
+
+    /// Expected SHA-256 checksums of uploaded files by their name, if uploads are restricted to them
+    pub upload_hash_manifest: Option<HashMap<String, Vec<u8>>>,
+
+    /// Permissions of uploaded files
+    pub upload_file_mode: Option<u32>,
+
+    /// Permissions of directories created through uploads
+    pub upload_dir_mode: Option<u32>,
+
+    /// Maximum length in bytes of the names of uploaded files
+    pub max_filename_length: Option<usize>,
+
+    /// Enable storing uploads as blobs named after their checksum, linked to by the uploaded files
+    pub cas_upload: bool,
+
+    /// Enable extraction of uploaded archives
+    pub extract_uploads: bool,
+
+    /// Delete uploaded archives after extracting them
+    pub delete_extracted_archives: bool,
+
+    /// Maximum number of bytes an uploaded archive may decompress to
+    pub extract_max_total_size: Option<u64>,
+
+    /// Maximum ratio between the decompressed and the uploaded size of an archive
+    pub extract_max_ratio: Option<u64>,
+
     /// If false, creation of uncompressed tar archives is disabled
     pub tar_enabled: bool,
 
@@ -119,21 +277,123 @@ pub struct MiniserveConfig {
     /// If false, creation of zip archives is disabled
     pub zip_enabled: bool,
 
+    /// Enable downloading only the files of a directory as a zip
+    pub flat_zip_enabled: bool,
+
+    /// If enabled, tar archives contain a checksum manifest
+    pub archive_manifest: bool,
+
+    /// If enabled, empty directories are left out of generated archives
+    pub archive_skip_empty_dirs: bool,
+
+    /// If enabled, the served directory itself can't be downloaded as an archive
+    pub no_root_archive: bool,
+
+    /// Size in bytes above which zip archives are assembled on disk instead of in memory
+    pub archive_memory_limit: Option<u64>,
+
+    /// Directory in which generated archives are cached
+    pub archive_cache_dir: Option<PathBuf>,
+
+    /// If enabled, generated archives contain no top-level folder
+    pub archive_flat: bool,
+
     /// Enable  compress response
     pub compress_response: bool,
 
+    /// Enable compression of generated listings only
+    pub compress_listings: bool,
+
     /// If enabled, directories are listed first
     pub dirs_first: bool,
 
+    /// How entries are grouped in listings
+    pub group_by: Option<GroupBy>,
+
+    /// Names of entries listed before all others
+    pub pin_first: Vec<String>,
+
     /// Shown instead of host in page title and heading
     pub title: Option<String>,
 
+    /// Message shown in a banner atop every listing
+    pub banner: Option<String>,
+
     /// If specified, header will be added
     pub header: Vec<HeaderMap>,
 
+    /// Custom routes returning static responses
+    pub custom_routes: Vec<CustomRoute>,
+
+    /// Links shown in the footer of listings
+    pub footer_links: Vec<FooterLink>,
+
+    /// HTTP methods rejected with 405 Method Not Allowed
+    pub disabled_methods: Vec<Method>,
+
+    /// Host names requests are accepted for, all if empty
+    pub allowed_hosts: Vec<String>,
+
+    /// Header carrying the ID of every request and its response
+    pub request_id_header: Option<HeaderName>,
+
+    /// Leave query strings out of the access log
+    pub log_redact: bool,
+
+    /// Value of the Server header sent with all responses
+    pub server_header: Option<HeaderValue>,
+
+    /// If enabled, the Server header is stripped from all responses
+    pub no_server_header: bool,
+
+    /// If enabled, listings link to entries and parent directories with relative links
+    pub relative_links: bool,
+
+    /// Collapse chains of directories that only contain a single directory
+    pub collapse_single_dirs: bool,
+
     /// If specified, symlink destination will be shown
     pub show_symlink_info: bool,
 
+    /// If enabled, broken symlinks will be shown
+    pub show_broken_symlinks: bool,
+
+    /// Files last modified longer ago than this are hidden from listings
+    pub hide_files_older_than: Option<Duration>,
+
+    /// Only files whose names match one of these are listed and served, all if empty
+    pub filters: Vec<Regex>,
+
+    /// If enabled, files hidden because of their age are not served either
+    pub block_old_files: bool,
+
+    /// If enabled, file sizes are shown in SI instead of IEC units
+    pub si_units: bool,
+
+    /// If enabled, the content type of files will be shown
+    pub show_mimetype: bool,
+
+    /// If enabled, a link always downloading the file is shown next to every file
+    pub show_download_links: bool,
+
+    /// Names of the extended attributes of files shown in listings
+    pub show_xattrs: Vec<String>,
+
+    /// If enabled, the free space of the served filesystem is shown in the footer
+    pub show_disk_space: bool,
+
+    /// If enabled, audio and video files are served ready for streaming
+    pub media_streaming: bool,
+
+    /// If enabled, the content type of served files is detected from their contents
+    pub mime_sniff: bool,
+
+    /// If enabled, HTML files are served as plain text
+    pub serve_html_as_text: bool,
+
+    /// If enabled, files can be previewed in the listing
+    pub preview: bool,
+
     /// If enabled, version footer is hidden
     pub hide_version_footer: bool,
 
@@ -143,18 +403,75 @@ pub struct MiniserveConfig {
     /// If enabled, display a wget command to recursively download the current directory
     pub show_wget_footer: bool,
 
+    /// Checksums the served files are verified against at startup, by their relative path
+    pub verify_manifest: Option<HashMap<String, Vec<u8>>>,
+
+    /// If enabled, files not matching the verification manifest are only warned about
+    pub verify_manifest_warn_only: bool,
+
+    /// If enabled, serve a generated sitemap.xml
+    pub sitemap: bool,
+
+    /// Contents of the robots.txt to serve, if any
+    pub robots_txt: Option<String>,
+
+    /// If enabled, a single served file is linked from a landing page instead of served at the root
+    pub single_file_landing: bool,
+
+    /// If enabled, pages contain Open Graph meta tags for link previews
+    pub open_graph: bool,
+
+    /// If set, switch to this user after binding
+    #[cfg(unix)]
+    pub user: Option<nix::unistd::Uid>,
+
+    /// If set, switch to this group after binding
+    #[cfg(unix)]
+    pub group: Option<nix::unistd::Gid>,
+
+    /// If enabled, change the root directory to the served directory after binding
+    #[cfg(unix)]
+    pub chroot: bool,
+
+    /// If enabled, show titles and descriptions from `.meta.json` files
+    pub metadata_sidecar: bool,
+
+    /// If enabled, serve recursive directory trees as JSON
+    pub tree_api: bool,
+
+    /// If enabled, serve directory listings as JSON and advertise them in HTML listings
+    pub emit_json_index: bool,
+
     /// If enabled, render the readme from the current directory
     pub readme: bool,
 
+    /// Markdown template of the README shown in directories without one
+    pub auto_readme: Option<String>,
+
+    /// Name of the file rendered listings are written to in their directory
+    pub write_index: Option<String>,
+
+    /// If enabled, render the description file of the current directory above the listing
+    pub descriptions: bool,
+
+    /// If enabled, description HTML files are rendered instead of escaped
+    pub trust_description_html: bool,
+
     /// If enabled, indexing is disabled.
     pub disable_indexing: bool,
 
+    /// Maximum depth of listed directories below the served directory, if limited
+    pub max_depth: Option<usize>,
+
     /// If set, use provided rustls config for TLS
     #[cfg(feature = "tls")]
     pub tls_rustls_config: Option<rustls::ServerConfig>,
 
     #[cfg(not(feature = "tls"))]
     pub tls_rustls_config: Option<()>,
+
+    /// If set, plain HTTP requests on this port are redirected to HTTPS
+    pub redirect_http: Option<u16>,
 }
 
 impl MiniserveConfig {
@@ -186,6 +503,25 @@ impl MiniserveConfig {
             }
         }
 
+        if let Some(var) = args.auth_file_env {
+            let contents = std::env::var(&var)
+                .with_context(|| format!("Failed to read environment variable {var}"))?;
+
+            for line in contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+            {
+                auth.push(parse_auth(line)?);
+            }
+        }
+
+        if args.upload_requires_auth && auth.is_empty() {
+            return Err(anyhow!(
+                "--upload-requires-auth requires --auth or --auth-file"
+            ));
+        }
+
         // Generate some random routes for the favicon and css so that they are very unlikely to conflict with
         // real files.
         // If --random-route is enabled , in order to not leak the random generated route, we must not use it
@@ -206,6 +542,25 @@ impl MiniserveConfig {
         let default_color_scheme = args.color_scheme;
         let default_color_scheme_dark = args.color_scheme_dark;
 
+        let themes = if args.themes.is_empty() {
+            ThemeSlug::value_variants().to_vec()
+        } else {
+            args.themes
+        };
+        for color_scheme in [&default_color_scheme, &default_color_scheme_dark] {
+            if !themes.contains(color_scheme) {
+                return Err(anyhow!(
+                    "The color scheme {color_scheme} isn't among the themes given by --themes"
+                ));
+            }
+        }
+
+        let css_integrity = args.css_integrity.then(|| {
+            let stylesheet =
+                renderer::stylesheet(&default_color_scheme, &default_color_scheme_dark);
+            format!("sha384-{}", BASE64.encode(Sha384::digest(stylesheet)))
+        });
+
         let path_explicitly_chosen = args.path.is_some() || args.index.is_some();
 
         let port = match args.port {
@@ -214,32 +569,107 @@ impl MiniserveConfig {
         };
 
         #[cfg(feature = "tls")]
-        let tls_rustls_server_config =
-            if let (Some(tls_cert), Some(tls_key)) = (args.tls_cert, args.tls_key) {
-                let cert_file = &mut BufReader::new(
-                    File::open(&tls_cert)
-                        .context(format!("Couldn't access TLS certificate {tls_cert:?}"))?,
-                );
-                let key_file = &mut BufReader::new(
-                    File::open(&tls_key).context(format!("Couldn't access TLS key {tls_key:?}"))?,
-                );
-                let cert_chain = pemfile::certs(cert_file)
-                    .map(|cert| cert.expect("Invalid certificate in certificate chain"))
-                    .collect();
-                let private_key = pemfile::private_key(key_file)
-                    .context("Reading private key file")?
-                    .expect("No private key found");
-                let server_config = rustls::ServerConfig::builder()
-                    .with_no_client_auth()
-                    .with_single_cert(cert_chain, private_key)?;
-                Some(server_config)
+        let tls_rustls_server_config = if args.tls_cert.is_empty() {
+            None
+        } else {
+            if args.tls_cert.len() != args.tls_key.len() {
+                return Err(anyhow!(
+                    "Every TLS certificate needs a matching TLS key, got {} certificates and {} keys",
+                    args.tls_cert.len(),
+                    args.tls_key.len()
+                ));
+            }
+
+            let mut provider = rustls::crypto::ring::default_provider();
+            if !args.tls_cipher_suites.is_empty() {
+                provider.cipher_suites = select_cipher_suites(&args.tls_cipher_suites)?;
+            }
+            let provider = Arc::new(provider);
+            let protocol_versions: &[&rustls::SupportedProtocolVersion] = match args.tls_min_version
+            {
+                TlsVersion::Tls12 => &[&rustls::version::TLS13, &rustls::version::TLS12],
+                TlsVersion::Tls13 => &[&rustls::version::TLS13],
+            };
+            let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+                .with_protocol_versions(protocol_versions)
+                .context("The TLS cipher suites don't support the minimum TLS version")?
+                .with_no_client_auth();
+
+            let mut certs = args
+                .tls_cert
+                .iter()
+                .zip(&args.tls_key)
+                .map(|(tls_cert, tls_key)| read_tls_cert(tls_cert, tls_key))
+                .collect::<Result<Vec<_>>>()?;
+            let server_config = if certs.len() == 1 {
+                let (cert_chain, private_key) = certs.remove(0);
+                builder.with_single_cert(cert_chain, private_key)?
             } else {
-                None
+                let resolver = SniCertResolver::new(&provider, &args.tls_cert, certs)?;
+                builder.with_cert_resolver(Arc::new(resolver))
             };
+            Some(server_config)
+        };
 
         #[cfg(not(feature = "tls"))]
         let tls_rustls_server_config = None;
 
+        #[cfg(feature = "tls")]
+        let redirect_http = args.redirect_http;
+        #[cfg(not(feature = "tls"))]
+        let redirect_http = None;
+
+        if let Some(archive_cache_dir) = &args.archive_cache_dir {
+            std::fs::create_dir_all(archive_cache_dir).context(format!(
+                "Couldn't create archive cache directory {archive_cache_dir:?}"
+            ))?;
+        }
+        let archive_cache_dir = args.archive_cache_dir;
+
+        let upload_hash_manifest = args
+            .upload_hash_manifest
+            .as_deref()
+            .map(read_hash_manifest)
+            .transpose()?;
+        let verify_manifest = args
+            .verify_manifest
+            .as_deref()
+            .map(read_hash_manifest)
+            .transpose()?;
+
+        let themed_favicons = args
+            .themed_favicons
+            .iter()
+            .map(|(theme, path)| {
+                let favicon = std::fs::read(path)
+                    .with_context(|| format!("Couldn't read favicon {path:?}"))?;
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                Ok((theme.to_string(), (mime, favicon)))
+            })
+            .collect::<Result<_>>()?;
+
+        let robots_txt = match args.robots {
+            Some(Robots::Allow) => Some("User-agent: *\nDisallow:\n".to_owned()),
+            Some(Robots::Deny) => Some("User-agent: *\nDisallow: /\n".to_owned()),
+            Some(Robots::File(path)) => Some(
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Couldn't read robots.txt {path:?}"))?,
+            ),
+            None => None,
+        };
+
+        #[cfg(unix)]
+        let user = args.user.as_deref().map(lookup_user).transpose()?;
+        #[cfg(unix)]
+        let group = match args.group.as_deref() {
+            Some(group) => Some(lookup_group(group)?),
+            None => user.as_ref().map(|user| user.gid),
+        };
+        #[cfg(unix)]
+        if args.chroot && args.path.as_ref().is_some_and(|path| path.is_file()) {
+            return Err(anyhow!("--chroot requires serving a directory"));
+        }
+
         let uploadable_media_type = args.media_type_raw.or_else(|| {
             args.media_type.map(|types| {
                 types
@@ -254,6 +684,20 @@ impl MiniserveConfig {
             })
         });
 
+        // Custom routes live inside the route prefix, so make sure they don't clash with the
+        // internal routes.
+        if let Some(route) = args.custom_routes.iter().find(|route| {
+            INTERNAL_ROUTES.contains(&route.path.as_str())
+                || INTERNAL_ROUTE_PREFIXES
+                    .iter()
+                    .any(|prefix| route.path.starts_with(prefix))
+        }) {
+            return Err(anyhow!(
+                "Custom route {} conflicts with an internal route",
+                route.path
+            ));
+        }
+
         let allowed_upload_dir = args
             .allowed_upload_dir
             .as_ref()
@@ -271,43 +715,337 @@ impl MiniserveConfig {
 
         Ok(Self {
             verbose: args.verbose,
+            log_downloads: args.log_downloads,
+            quiet: args.quiet,
+            shutdown_after_idle: args.shutdown_after_idle.map(Duration::from_secs),
             path: args.path.unwrap_or_else(|| PathBuf::from(".")),
             port,
             interfaces,
+            display_interfaces: args.display_interfaces,
             auth,
+            auth_max_attempts: args.auth_max_attempts,
+            auth_lockout: args.auth_lockout,
             path_explicitly_chosen,
             no_symlinks: args.no_symlinks,
             show_hidden: args.hidden,
             default_sorting_method: args.default_sorting_method,
             default_sorting_order: args.default_sorting_order,
+            // A prefix of just `/` would redirect to itself
+            redirect_root_to_prefix: args.redirect_root_to_prefix && route_prefix.len() > 1,
             route_prefix,
+            external_base_url: args.external_base_url,
             favicon_route,
             css_route,
+            css_integrity,
             default_color_scheme,
             default_color_scheme_dark,
-            index: args.index,
+            themes,
+            themed_favicons,
+            listing_style: args.listing_style,
+            index: args
+                .index
+                .or_else(|| args.static_site.then(|| PathBuf::from("index.html"))),
             spa: args.spa,
-            pretty_urls: args.pretty_urls,
+            pretty_urls: args.pretty_urls || args.static_site,
+            static_site: args.static_site,
+            case_insensitive: args.case_insensitive,
             overwrite_files: args.overwrite_files,
+            io_timeout: args.io_timeout.map(Duration::from_millis),
+            listing_cache_ttl: args.listing_cache_ttl.map(Duration::from_secs),
+            lazy_listing: args.lazy_listing.map(|entries| entries as usize),
+            audit_log: args.audit_log,
+            max_request_body: args.max_request_body.map(|size| size.as_u64()),
+            max_concurrent_uploads: args.max_concurrent_uploads,
+            max_connections: args.max_connections.map(|max| max as usize),
+            upload_csrf_secret: args.upload_csrf.then(|| nanoid::nanoid!(32)),
+            upload_allowed_referers: args.upload_allowed_referers,
+            upload_redirect: args.upload_redirect,
+            upload_requires_auth: args.upload_requires_auth,
+            create_upload_dirs: args.create_upload_dirs,
+            sidecar_upload_control: args.sidecar_upload_control,
+            upload_pipe: args.upload_pipe,
+            tus: args.tus,
+            slugify_uploads: args.slugify_uploads,
+            upload_hash_manifest,
+            upload_file_mode: args.upload_file_mode,
+            upload_dir_mode: args.upload_dir_mode,
+            max_filename_length: args.max_filename_length.map(|len| len as usize),
+            cas_upload: args.cas_upload,
+            extract_uploads: args.extract_uploads,
+            delete_extracted_archives: args.delete_extracted_archives,
+            extract_max_total_size: args.extract_max_total_size.map(|size| size.as_u64()),
+            extract_max_ratio: args.extract_max_ratio,
+            lang: args.lang,
+            follow_accept_language: args.follow_accept_language,
             show_qrcode: args.qrcode,
             mkdir_enabled: args.mkdir_enabled,
+            mkdir_error_on_exists: args.mkdir_error_on_exists,
             file_upload: args.allowed_upload_dir.is_some(),
             allowed_upload_dir,
             uploadable_media_type,
             tar_enabled: args.enable_tar,
             tar_gz_enabled: args.enable_tar_gz,
             zip_enabled: args.enable_zip,
+            flat_zip_enabled: args.enable_flat_zip,
+            archive_manifest: args.archive_manifest,
+            archive_skip_empty_dirs: args.archive_skip_empty_dirs,
+            no_root_archive: args.no_root_archive,
+            archive_memory_limit: args.archive_memory_limit.map(|size| size.as_u64()),
+            archive_cache_dir,
+            archive_flat: args.archive_flat,
             dirs_first: args.dirs_first,
+            group_by: args.group_by,
+            pin_first: args.pin_first,
             title: args.title,
+            banner: args.banner,
             header: args.header,
+            custom_routes: args.custom_routes,
+            footer_links: args.footer_links,
+            disabled_methods: args.disabled_methods,
+            allowed_hosts: args
+                .allowed_hosts
+                .iter()
+                .map(|host| normalize_host(host))
+                .collect(),
+            request_id_header: args.request_id_header,
+            log_redact: args.log_redact,
+            server_header: args.server_header,
+            no_server_header: args.no_server_header,
+            relative_links: args.relative_links,
+            collapse_single_dirs: args.collapse_single_dirs,
             show_symlink_info: args.show_symlink_info,
+            show_broken_symlinks: args.show_broken_symlinks,
+            show_mimetype: args.show_mimetype,
+            show_download_links: args.show_download_links,
+            show_xattrs: args.show_xattrs,
+            show_disk_space: args.show_disk_space,
+            media_streaming: args.media_streaming,
+            si_units: args.si_units,
+            hide_files_older_than: args.hide_files_older_than,
+            filters: args.filters,
+            block_old_files: args.block_old_files,
+            mime_sniff: args.mime_sniff,
+            serve_html_as_text: args.serve_html_as_text,
+            preview: args.preview,
             hide_version_footer: args.hide_version_footer,
             hide_theme_selector: args.hide_theme_selector,
             show_wget_footer: args.show_wget_footer,
+            verify_manifest,
+            verify_manifest_warn_only: args.verify_manifest_warn_only,
+            sitemap: args.sitemap,
+            robots_txt,
+            single_file_landing: args.single_file_landing,
+            open_graph: args.open_graph,
+            #[cfg(unix)]
+            user: user.map(|user| user.uid),
+            #[cfg(unix)]
+            group,
+            #[cfg(unix)]
+            chroot: args.chroot,
+            metadata_sidecar: args.metadata_sidecar,
+            tree_api: args.tree_api,
+            emit_json_index: args.emit_json_index,
             readme: args.readme,
+            auto_readme: args.auto_readme,
+            write_index: args.write_index,
+            descriptions: args.descriptions,
+            trust_description_html: args.trust_description_html,
             disable_indexing: args.disable_indexing,
+            max_depth: args.max_depth,
             tls_rustls_config: tls_rustls_server_config,
+            redirect_http,
             compress_response: args.compress_response,
+            compress_listings: args.compress_listings,
+        })
+    }
+}
+
+/// Reads a manifest in the format of `sha256sum` into a map of file names to checksums
+fn read_hash_manifest(path: &Path) -> Result<HashMap<String, Vec<u8>>> {
+    let manifest = File::open(path).context(format!("Couldn't open hash manifest {path:?}"))?;
+
+    BufReader::new(manifest)
+        .lines()
+        .map(|line| line.context(format!("Couldn't read hash manifest {path:?}")))
+        .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        .map(|line| {
+            let line = line?;
+            let (hash, file_name) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| anyhow!("Invalid line in hash manifest: {line:?}"))?;
+            let hash = hex::decode(hash)
+                .ok()
+                .filter(|hash| hash.len() == Sha256::output_size())
+                .ok_or_else(|| anyhow!("Invalid SHA-256 checksum in hash manifest: {hash:?}"))?;
+
+            // `sha256sum` marks files read in binary mode with a leading asterisk
+            let file_name = file_name.trim_start();
+            let file_name = file_name.strip_prefix('*').unwrap_or(file_name);
+            let file_name = file_name.strip_prefix("./").unwrap_or(file_name);
+            Ok((file_name.to_string(), hash))
+        })
+        .collect()
+}
+
+/// Strips the port and IPv6 brackets from a host and lowercases it
+pub fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let host = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+        None if host.matches(':').count() == 1 => host.split(':').next().unwrap_or_default(),
+        None => host,
+    };
+    host.to_lowercase()
+}
+
+/// Looks up a user by name or uid
+#[cfg(unix)]
+fn lookup_user(user: &str) -> Result<nix::unistd::User> {
+    let found = match user.parse() {
+        Ok(uid) => nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid)),
+        Err(_) => nix::unistd::User::from_name(user),
+    };
+    found
+        .context(format!("Couldn't look up user {user:?}"))?
+        .ok_or_else(|| anyhow!("User {user:?} doesn't exist"))
+}
+
+/// Looks up a group by name or gid
+#[cfg(unix)]
+fn lookup_group(group: &str) -> Result<nix::unistd::Gid> {
+    if let Ok(gid) = group.parse() {
+        return Ok(nix::unistd::Gid::from_raw(gid));
+    }
+    nix::unistd::Group::from_name(group)
+        .context(format!("Couldn't look up group {group:?}"))?
+        .map(|group| group.gid)
+        .ok_or_else(|| anyhow!("Group {group:?} doesn't exist"))
+}
+
+/// Reads a TLS certificate chain and its private key
+#[cfg(feature = "tls")]
+fn read_tls_cert(
+    tls_cert: &PathBuf,
+    tls_key: &PathBuf,
+) -> Result<(
+    Vec<rustls::pki_types::CertificateDer<'static>>,
+    rustls::pki_types::PrivateKeyDer<'static>,
+)> {
+    let cert_file = &mut BufReader::new(
+        File::open(tls_cert).context(format!("Couldn't access TLS certificate {tls_cert:?}"))?,
+    );
+    let key_file = &mut BufReader::new(
+        File::open(tls_key).context(format!("Couldn't access TLS key {tls_key:?}"))?,
+    );
+    let cert_chain = pemfile::certs(cert_file)
+        .map(|cert| cert.expect("Invalid certificate in certificate chain"))
+        .collect();
+    let private_key = pemfile::private_key(key_file)
+        .context("Reading private key file")?
+        .expect("No private key found");
+    Ok((cert_chain, private_key))
+}
+
+/// Picks the TLS certificate by the server name the client asks for (SNI)
+///
+/// Falls back to the first certificate if the client doesn't send a server name or none of the
+/// certificates matches it.
+#[cfg(feature = "tls")]
+#[derive(Debug)]
+struct SniCertResolver {
+    default: Arc<rustls::sign::CertifiedKey>,
+    by_name: rustls::server::ResolvesServerCertUsingSni,
+}
+
+#[cfg(feature = "tls")]
+impl SniCertResolver {
+    /// Registers every certificate for the DNS names in its subject alternative names
+    fn new(
+        provider: &rustls::crypto::CryptoProvider,
+        paths: &[PathBuf],
+        certs: Vec<(
+            Vec<rustls::pki_types::CertificateDer<'static>>,
+            rustls::pki_types::PrivateKeyDer<'static>,
+        )>,
+    ) -> Result<Self> {
+        let mut default = None;
+        let mut by_name = rustls::server::ResolvesServerCertUsingSni::new();
+        for (path, (cert_chain, private_key)) in paths.iter().zip(certs) {
+            let signing_key = provider
+                .key_provider
+                .load_private_key(private_key)
+                .context(format!("Unsupported TLS key for certificate {path:?}"))?;
+            let certified_key = rustls::sign::CertifiedKey::new(cert_chain, signing_key);
+            certified_key
+                .keys_match()
+                .context(format!("TLS key doesn't match certificate {path:?}"))?;
+
+            let end_entity_cert = certified_key
+                .end_entity_cert()
+                .context(format!("No certificate found in {path:?}"))?;
+            let names = webpki::EndEntityCert::try_from(end_entity_cert)
+                .map_err(|e| anyhow!("Invalid TLS certificate {path:?}: {e}"))?
+                .valid_dns_names()
+                // Wildcard names can't be looked up by the exact server name
+                .filter(|name| !name.starts_with('*'))
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            if names.is_empty() && default.is_some() {
+                return Err(anyhow!(
+                    "TLS certificate {path:?} doesn't contain any DNS names to select it by"
+                ));
+            }
+            for name in names {
+                by_name
+                    .add(&name, certified_key.clone())
+                    .context(format!("Can't use TLS certificate {path:?} for {name:?}"))?;
+            }
+
+            default.get_or_insert_with(|| Arc::new(certified_key));
+        }
+
+        Ok(Self {
+            default: default.context("No TLS certificate given")?,
+            by_name,
         })
     }
 }
+
+#[cfg(feature = "tls")]
+impl rustls::server::ResolvesServerCert for SniCertResolver {
+    fn resolve(
+        &self,
+        client_hello: rustls::server::ClientHello<'_>,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        self.by_name
+            .resolve(client_hello)
+            .or_else(|| Some(self.default.clone()))
+    }
+}
+
+/// Looks up the cipher suites supported by rustls with the given names
+#[cfg(feature = "tls")]
+fn select_cipher_suites(names: &[String]) -> Result<Vec<rustls::SupportedCipherSuite>> {
+    let supported = rustls::crypto::ring::ALL_CIPHER_SUITES;
+    let suite_name = |suite: &rustls::SupportedCipherSuite| format!("{:?}", suite.suite());
+
+    names
+        .iter()
+        .map(|name| {
+            supported
+                .iter()
+                .find(|suite| suite_name(suite).eq_ignore_ascii_case(name.trim()))
+                .copied()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Unsupported TLS cipher suite {name:?}, supported are: {}",
+                        supported
+                            .iter()
+                            .map(suite_name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })
+        })
+        .collect()
+}
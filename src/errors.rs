@@ -8,7 +8,7 @@ use futures::prelude::*;
 use std::str::FromStr;
 use thiserror::Error;
 
-use crate::{renderer::render_error, MiniserveConfig};
+use crate::{i18n::Language, renderer::render_error, MiniserveConfig};
 
 #[derive(Debug, Error)]
 pub enum StartupError {
@@ -21,6 +21,10 @@ pub enum StartupError {
 Please set an explicit serve path like: `miniserve /my/path`")]
     NoExplicitPathAndNoTerminal,
 
+    /// In case served files don't match the manifest given by --verify-manifest
+    #[error("Refusing to start as {0} file(s) don't match the verification manifest")]
+    ManifestMismatch(usize),
+
     /// In case miniserve was invoked with --no-symlinks but the serve path is a symlink
     #[error("The -P|--no-symlinks option was provided but the serve path '{0}' is a symlink")]
     NoSymlinksOptionWithSymlinkServePath(String),
@@ -40,10 +44,69 @@ pub enum RuntimeError {
     #[error("File already exists, and the overwrite_files option has not been set")]
     DuplicateFileError,
 
+    /// Might occur when the name of an uploaded file is longer than allowed
+    #[error("File names must not be longer than {0} bytes")]
+    FileNameTooLongError(usize),
+
     /// Upload not allowed
     #[error("Upload not allowed to this directory")]
     UploadForbiddenError,
 
+    /// Might occur when an upload lacks a valid CSRF token
+    #[error("Missing or invalid CSRF token")]
+    CsrfTokenError,
+
+    /// Might occur when an upload comes from a page on a host that isn't allowed
+    #[error("Uploads from {0} are not allowed")]
+    RefererNotAllowedError(String),
+
+    /// Might occur when uploads are restricted to the files of the upload hash manifest
+    #[error("{0} is not listed in the upload hash manifest")]
+    UploadNotInManifestError(String),
+
+    /// Might occur when an uploaded file doesn't match its checksum in the upload hash manifest
+    #[error("Checksum of {0} doesn't match the upload hash manifest")]
+    UploadHashMismatchError(String),
+
+    /// Might occur when the command given by --upload-pipe rejects an uploaded file
+    #[error("{0} was rejected by the upload pipe: {1}")]
+    UploadPipeRejectedError(String, String),
+
+    /// Might occur when the request body is larger than allowed
+    #[error("Request body exceeds the limit of {0} bytes")]
+    PayloadTooLargeError(u64),
+
+    /// Might occur when the request is for a host that isn't allowed
+    #[error("Host {0} is not allowed")]
+    HostNotAllowedError(String),
+
+    /// Might occur when the request method has been disabled
+    #[error("Method {0} is not allowed")]
+    MethodNotAllowedError(String),
+
+    /// Might occur when listing a directory nested deeper than allowed
+    #[error("Listing directories nested deeper than {0} levels is not allowed")]
+    MaxDepthError(usize),
+
+    /// Might occur when the maximum number of concurrent uploads is reached
+    #[error("Too many uploads in progress, please try again later")]
+    TooManyUploadsError,
+
+    /// Might occur when a tus client uses an unsupported version of the protocol
+    #[error(
+        "Unsupported tus version, only {} is supported",
+        crate::tus::TUS_VERSION
+    )]
+    TusVersionError,
+
+    /// Might occur when a tus client appends to an upload at the wrong offset
+    #[error("Upload offset doesn't match the current offset {0}")]
+    TusOffsetMismatchError(u64),
+
+    /// Might occur when a tus client sends data with the wrong content type
+    #[error("Upload data must be sent as {}", crate::tus::TUS_CONTENT_TYPE)]
+    TusContentTypeError,
+
     /// Any error related to an invalid path (failed to retrieve entry name, unexpected entry type, etc)
     #[error("Invalid path\ncaused by: {0}")]
     InvalidPathError(String),
@@ -64,10 +127,22 @@ pub enum RuntimeError {
     #[error("{0}")]
     ArchiveCreationDetailError(String),
 
+    /// Might occur when an uploaded archive can not be extracted
+    #[error("Failed to extract uploaded archive\ncaused by: {0}")]
+    ArchiveExtractionError(String),
+
+    /// Might occur when an uploaded archive decompresses to more data than allowed
+    #[error("Uploaded archive is too large when extracted\ncaused by: {0}")]
+    ArchiveExtractionLimitError(String),
+
     /// Might occur when the HTTP credentials are not correct
     #[error("Invalid credentials for HTTP authentication")]
     InvalidHttpCredentials,
 
+    /// Might occur when a client failed to authenticate too often
+    #[error("Too many failed authentication attempts, please try again later")]
+    TooManyAuthAttemptsError,
+
     /// Might occur when an HTTP request is invalid
     #[error("Invalid HTTP request\ncaused by: {0}")]
     InvalidHttpRequestError(String),
@@ -85,13 +160,30 @@ impl ResponseError for RuntimeError {
             E::IoError(_, _) => S::INTERNAL_SERVER_ERROR,
             E::MultipartError(_) => S::BAD_REQUEST,
             E::DuplicateFileError => S::CONFLICT,
+            E::FileNameTooLongError(_) => S::BAD_REQUEST,
             E::UploadForbiddenError => S::FORBIDDEN,
+            E::CsrfTokenError => S::FORBIDDEN,
+            E::RefererNotAllowedError(_) => S::FORBIDDEN,
+            E::UploadNotInManifestError(_) => S::FORBIDDEN,
+            E::UploadHashMismatchError(_) => S::BAD_REQUEST,
+            E::UploadPipeRejectedError(_, _) => S::UNPROCESSABLE_ENTITY,
+            E::TooManyUploadsError => S::SERVICE_UNAVAILABLE,
+            E::MaxDepthError(_) => S::FORBIDDEN,
+            E::PayloadTooLargeError(_) => S::PAYLOAD_TOO_LARGE,
+            E::MethodNotAllowedError(_) => S::METHOD_NOT_ALLOWED,
+            E::HostNotAllowedError(_) => S::MISDIRECTED_REQUEST,
+            E::TusVersionError => S::PRECONDITION_FAILED,
+            E::TusOffsetMismatchError(_) => S::CONFLICT,
+            E::TusContentTypeError => S::UNSUPPORTED_MEDIA_TYPE,
             E::InvalidPathError(_) => S::BAD_REQUEST,
             E::InsufficientPermissionsError(_) => S::FORBIDDEN,
             E::ParseError(_, _) => S::BAD_REQUEST,
             E::ArchiveCreationError(_, err) => err.status_code(),
             E::ArchiveCreationDetailError(_) => S::INTERNAL_SERVER_ERROR,
+            E::ArchiveExtractionError(_) => S::BAD_REQUEST,
+            E::ArchiveExtractionLimitError(_) => S::PAYLOAD_TOO_LARGE,
             E::InvalidHttpCredentials => S::UNAUTHORIZED,
+            E::TooManyAuthAttemptsError => S::TOO_MANY_REQUESTS,
             E::InvalidHttpRequestError(_) => S::BAD_REQUEST,
             E::RouteNotFoundError(_) => S::NOT_FOUND,
         }
@@ -107,6 +199,9 @@ impl ResponseError for RuntimeError {
                 header::HeaderValue::from_static("Basic realm=\"miniserve\""),
             ));
         }
+        if let Self::TusVersionError = self {
+            resp.append_header((crate::tus::TUS_VERSION_HEADER, crate::tus::TUS_VERSION));
+        }
 
         resp.content_type(mime::TEXT_PLAIN_UTF_8)
             .body(self.to_string())
@@ -170,7 +265,9 @@ fn map_error_page(req: &HttpRequest, head: &mut ResponseHead, body: BoxBody) ->
         mime::TEXT_HTML_UTF_8.essence_str().try_into().unwrap(),
     );
 
-    BoxBody::new(render_error(error_msg, head.status, conf, return_address).into_string())
+    let lang = Language::for_request(req, conf);
+
+    BoxBody::new(render_error(error_msg, head.status, conf, return_address, lang).into_string())
 }
 
 pub fn log_error_chain(description: String) {
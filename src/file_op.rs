@@ -1,34 +1,241 @@
 //! Handlers for file upload and removal
 
+use std::convert::Infallible;
 use std::io::ErrorKind;
 use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
 
-use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use actix_multipart::MultipartError;
+use actix_web::{
+    error::PayloadError,
+    http::{header, Uri},
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
 use futures::TryFutureExt;
 use futures::TryStreamExt;
-use serde::Deserialize;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{broadcast, Semaphore, SemaphorePermit};
 
 use crate::{
-    config::MiniserveConfig, errors::RuntimeError, file_utils::contains_symlink,
-    file_utils::sanitize_path,
+    archive::{ArchiveMethod, ExtractionLimits},
+    args::{parse_upload_redirect, UploadRedirect},
+    audit::{AuditAction, AuditLog},
+    auth::{verify_csrf_token, CurrentUser},
+    config::MiniserveConfig,
+    errors::RuntimeError,
+    file_utils::{contains_symlink, sanitize_path, slugify_file_name},
 };
 
+/// Header in which non-browser clients send the CSRF token of uploads
+const CSRF_TOKEN_HEADER: &str = "X-Csrf-Token";
+
+/// Name of the form field in which the upload forms send the CSRF token, as their first field
+const CSRF_TOKEN_FIELD: &str = "csrf_token";
+
+/// CSRF tokens are hex encoded SHA-256 hashes, so longer form fields can't be valid tokens
+const CSRF_TOKEN_MAX_LEN: usize = 64;
+
+/// Directory in the served directory that uploads are stored in with --cas-upload
+const CAS_BLOB_DIR: &str = ".blobs";
+
+/// Header in which clients choose where to be redirected after an upload
+const UPLOAD_REDIRECT_HEADER: &str = "X-Upload-Redirect";
+
+/// Name of the marker file that allows uploads to its directory with --sidecar-upload-control
+pub const UPLOAD_MARKER_FILE: &str = ".miniserve-upload";
+
+/// How long an upload waits for one of the running uploads to finish
+const UPLOAD_PERMIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Limits the number of uploads processed at the same time, if configured
+pub struct UploadLimiter(Option<Semaphore>);
+
+impl UploadLimiter {
+    pub fn new(max_concurrent_uploads: Option<u32>) -> Self {
+        Self(max_concurrent_uploads.map(|max| Semaphore::new(max as usize)))
+    }
+}
+
+/// Minimum time between two progress events of the same upload
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress of a single uploaded file, as sent to the subscribers of the progress events
+#[derive(Clone, Debug, Serialize)]
+pub struct UploadProgressEvent {
+    /// Identifier chosen by the client performing the upload
+    pub upload_id: String,
+
+    /// Name of the uploaded file
+    pub file_name: String,
+
+    /// Number of bytes written so far
+    pub written: u64,
+
+    /// Whether the file has been written completely
+    pub done: bool,
+}
+
+/// Broadcasts the progress of uploads to all subscribers
+pub struct UploadProgress(broadcast::Sender<UploadProgressEvent>);
+
+impl Default for UploadProgress {
+    fn default() -> Self {
+        Self(broadcast::channel(64).0)
+    }
+}
+
+/// Reports the progress of one uploaded file
+struct ProgressReporter {
+    sender: broadcast::Sender<UploadProgressEvent>,
+    upload_id: String,
+    file_name: String,
+    last_report: Option<Instant>,
+}
+
+impl ProgressReporter {
+    /// Sends a progress event, unless the previous one was sent only just now
+    fn report(&mut self, written: u64, done: bool) {
+        if !done
+            && self
+                .last_report
+                .is_some_and(|last| last.elapsed() < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_report = Some(Instant::now());
+
+        // Sending only fails if nobody is subscribed, which is fine
+        let _ = self.sender.send(UploadProgressEvent {
+            upload_id: self.upload_id.clone(),
+            file_name: self.file_name.clone(),
+            written,
+            done,
+        });
+    }
+}
+
+/// Query parameters of the upload progress events
+#[derive(Deserialize)]
+pub struct UploadProgressQueryParameters {
+    /// Only the events of the upload with this identifier are sent
+    upload_id: String,
+}
+
+/// Handles subscriptions to the progress events of an upload, which are sent as server-sent
+/// events
+///
+/// Subscribers have to be allowed to upload themselves.
+pub async fn upload_progress(
+    req: HttpRequest,
+    query: web::Query<UploadProgressQueryParameters>,
+    progress: web::Data<UploadProgress>,
+) -> Result<HttpResponse, RuntimeError> {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    check_upload_allowed(&req, conf)?;
+    check_csrf_token(&req, None)?;
+
+    let receiver = progress.0.subscribe();
+    let upload_id = query.into_inner().upload_id;
+    let events = futures::stream::unfold(receiver, move |mut receiver| {
+        let upload_id = upload_id.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if event.upload_id != upload_id => continue,
+                    Ok(event) => {
+                        let data = serde_json::to_string(&event).unwrap_or_default();
+                        let message = web::Bytes::from(format!("data: {data}\n\n"));
+                        return Some((Ok::<_, Infallible>(message), receiver));
+                    }
+                    // Slow subscribers miss some events, but keep receiving the newer ones
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(header::CacheControl(vec![header::CacheDirective::NoCache]))
+        .streaming(events))
+}
+
+/// Sets the permissions of `path` to `mode`, if given.
+///
+/// This is a no-op on non-unix platforms.
+pub async fn set_mode(path: &Path, mode: Option<u32>) -> Result<(), RuntimeError> {
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .await
+            .map_err(|e| {
+                RuntimeError::IoError(
+                    format!("Failed to set permissions of {}", path.display()),
+                    e,
+                )
+            })?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+
+    Ok(())
+}
+
 /// Saves file data from a multipart form field (`field`) to `file_path`, optionally overwriting
-/// existing file.
+/// existing file and setting its permissions to `file_mode`.
+///
+/// If a `progress` reporter is given, the number of written bytes is reported periodically.
+///
+/// If an `expected_hash` is given, the file is written to a temporary file first and only moved
+/// to `file_path` if its SHA-256 checksum matches, so that a rejected upload doesn't replace an
+/// existing file. The same goes for an `upload_pipe`, a shell command the data is streamed into
+/// which has to exit successfully for the file to be kept.
+///
+/// If a `blob_dir` is given, the content is stored in a blob named after its SHA-256 checksum in
+/// that directory, and `file_path` is created as a hard link to it. Identical uploads share the
+/// same blob.
 ///
 /// Returns total bytes written to file.
+#[allow(clippy::too_many_arguments)]
 async fn save_file(
     field: actix_multipart::Field,
     file_path: PathBuf,
     overwrite_files: bool,
+    file_mode: Option<u32>,
+    progress: Option<ProgressReporter>,
+    expected_hash: Option<&[u8]>,
+    upload_pipe: Option<&str>,
+    blob_dir: Option<&Path>,
+    max_request_body: Option<u64>,
 ) -> Result<u64, RuntimeError> {
     if !overwrite_files && file_path.exists() {
         return Err(RuntimeError::DuplicateFileError);
     }
 
-    let file = match File::create(&file_path).await {
+    let write_path = if expected_hash.is_some() || upload_pipe.is_some() || blob_dir.is_some() {
+        unverified_upload_path(&file_path)
+    } else {
+        file_path.clone()
+    };
+    // Without overwriting, the file is claimed atomically so that concurrent uploads of the same
+    // file can't both pass the check above and write to it at the same time
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if overwrite_files {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    let file = match options.open(&write_path).await {
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => Err(RuntimeError::DuplicateFileError),
         Err(err) if err.kind() == ErrorKind::PermissionDenied => Err(
             RuntimeError::InsufficientPermissionsError(file_path.display().to_string()),
         ),
@@ -39,30 +246,388 @@ async fn save_file(
         Ok(v) => Ok(v),
     }?;
 
-    let (_, written_len) = field
-        .map_err(|x| RuntimeError::MultipartError(x.to_string()))
-        .try_fold((file, 0u64), |(mut file, written_len), bytes| async move {
-            file.write_all(bytes.as_ref())
-                .map_err(|e| RuntimeError::IoError("Failed to write to file".to_string(), e))
-                .await?;
-            Ok((file, written_len + bytes.len() as u64))
-        })
-        .await?;
+    let mut pipe = match upload_pipe.map(UploadPipe::spawn).transpose() {
+        Ok(pipe) => pipe,
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&write_path).await;
+            return Err(err);
+        }
+    };
+    let stdin = pipe.as_mut().and_then(|pipe| pipe.child.stdin.take());
+
+    let hasher = (expected_hash.is_some() || blob_dir.is_some()).then(Sha256::new);
+    let written = field
+        .map_err(|x| multipart_error(x, max_request_body))
+        .try_fold(
+            (file, 0u64, progress, hasher, stdin),
+            |(mut file, written_len, mut progress, mut hasher, mut stdin), bytes| async move {
+                file.write_all(bytes.as_ref())
+                    .map_err(|e| RuntimeError::IoError("Failed to write to file".to_string(), e))
+                    .await?;
+                let written_len = written_len + bytes.len() as u64;
+                if let Some(progress) = &mut progress {
+                    progress.report(written_len, false);
+                }
+                if let Some(hasher) = &mut hasher {
+                    hasher.update(&bytes);
+                }
+                // The command may stop reading early, its exit status decides about the upload
+                if let Some(pipe) = &mut stdin {
+                    if pipe.write_all(bytes.as_ref()).await.is_err() {
+                        stdin = None;
+                    }
+                }
+                Ok((file, written_len, progress, hasher, stdin))
+            },
+        )
+        .await;
+
+    let (_, written_len, mut progress, hasher, _) = match written {
+        Ok(written) => written,
+        Err(err) if write_path != file_path => {
+            let _ = tokio::fs::remove_file(&write_path).await;
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+    };
+
+    if let Some(pipe) = pipe {
+        if let Err(err) = pipe.finish(&file_path).await {
+            let _ = tokio::fs::remove_file(&write_path).await;
+            return Err(err);
+        }
+    }
+
+    if write_path != file_path {
+        let hash = hasher.map(|hasher| hasher.finalize());
+        let hash_matches = match (expected_hash, &hash) {
+            (Some(expected_hash), Some(hash)) => hash.as_slice() == expected_hash,
+            _ => true,
+        };
+        match (blob_dir, hash) {
+            (Some(blob_dir), Some(hash)) if hash_matches => {
+                link_upload_blob(&write_path, &file_path, blob_dir, &hash, overwrite_files).await?
+            }
+            _ => {
+                move_verified_upload(&write_path, &file_path, hash_matches, overwrite_files).await?
+            }
+        }
+    }
+
+    if let Some(progress) = &mut progress {
+        progress.report(written_len, true);
+    }
+
+    set_mode(&file_path, file_mode).await?;
 
     Ok(written_len)
 }
 
+/// How much of the standard error of the --upload-pipe command is returned to the client
+const UPLOAD_PIPE_STDERR_LIMIT: usize = 1024;
+
+/// Shell command given by --upload-pipe that an upload is streamed into
+struct UploadPipe {
+    child: tokio::process::Child,
+
+    /// Collects the standard error of the command while the upload is streamed, so that the
+    /// command can't block on writing it
+    stderr: Option<actix_web::rt::task::JoinHandle<Vec<u8>>>,
+}
+
+impl UploadPipe {
+    fn spawn(command: &str) -> Result<Self, RuntimeError> {
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let mut child = tokio::process::Command::new(shell)
+            .arg(flag)
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| RuntimeError::IoError(format!("Failed to run {command:?}"), e))?;
+        // The whole output is read so the command doesn't block, but only its start is kept
+        let stderr = child.stderr.take().map(|mut stderr| {
+            actix_web::rt::spawn(async move {
+                let mut output = Vec::new();
+                let mut buffer = [0; 4096];
+                while let Ok(read @ 1..) = stderr.read(&mut buffer).await {
+                    let kept = read.min(UPLOAD_PIPE_STDERR_LIMIT.saturating_sub(output.len()));
+                    output.extend_from_slice(&buffer[..kept]);
+                }
+                output
+            })
+        });
+
+        Ok(Self { child, stderr })
+    }
+
+    /// Waits for the command to exit, failing if it rejected the upload of `file_path`
+    ///
+    /// Only the file name is passed on to the client, so that the error doesn't reveal where the
+    /// file is stored.
+    async fn finish(mut self, file_path: &Path) -> Result<(), RuntimeError> {
+        // Closes the standard input in case it's still open
+        drop(self.child.stdin.take());
+        let status = self.child.wait().await.map_err(|e| {
+            RuntimeError::IoError("Failed to wait for the upload pipe".to_string(), e)
+        })?;
+        if status.success() {
+            return Ok(());
+        }
+
+        let stderr = match self.stderr {
+            Some(stderr) => stderr.await.unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+        log::warn!(
+            "The upload pipe rejected {} ({status}): {stderr}",
+            file_path.display()
+        );
+        Err(RuntimeError::UploadPipeRejectedError(
+            file_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            if stderr.is_empty() {
+                status.to_string()
+            } else {
+                stderr
+            },
+        ))
+    }
+}
+
+/// Streams the complete upload at `write_path` into the command given by --upload-pipe,
+/// deleting it if the command rejects it
+///
+/// This is used for uploads that aren't streamed into the command while they are received.
+pub async fn pipe_written_upload(
+    command: &str,
+    write_path: &Path,
+    file_path: &Path,
+) -> Result<(), RuntimeError> {
+    let result = async {
+        let mut pipe = UploadPipe::spawn(command)?;
+        if let Some(mut stdin) = pipe.child.stdin.take() {
+            let mut file = tokio::fs::File::open(write_path).await.map_err(|e| {
+                RuntimeError::IoError(format!("Failed to read {}", write_path.display()), e)
+            })?;
+            // The command may stop reading early, its exit status decides about the upload
+            let _ = tokio::io::copy(&mut file, &mut stdin).await;
+        }
+        pipe.finish(file_path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(write_path).await;
+    }
+    result
+}
+
+/// Moves an upload written to the temporary `write_path` to `file_path`, or deletes it if its
+/// checksum doesn't match
+pub async fn move_verified_upload(
+    write_path: &Path,
+    file_path: &Path,
+    hash_matches: bool,
+    overwrite_files: bool,
+) -> Result<(), RuntimeError> {
+    if !hash_matches {
+        tokio::fs::remove_file(write_path).await.map_err(|e| {
+            RuntimeError::IoError(format!("Failed to delete {}", write_path.display()), e)
+        })?;
+        return Err(RuntimeError::UploadHashMismatchError(
+            file_path.display().to_string(),
+        ));
+    }
+
+    // A rename silently replaces a file that another upload created in the meantime, whereas
+    // linking the upload into place fails
+    let moved = if overwrite_files {
+        tokio::fs::rename(write_path, file_path).await
+    } else {
+        match tokio::fs::hard_link(write_path, file_path).await {
+            Ok(()) => tokio::fs::remove_file(write_path).await,
+            Err(e) => Err(e),
+        }
+    };
+    match moved {
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            let _ = tokio::fs::remove_file(write_path).await;
+            Err(RuntimeError::DuplicateFileError)
+        }
+        Err(e) => Err(RuntimeError::IoError(
+            format!("Failed to create {}", file_path.display()),
+            e,
+        )),
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Moves an upload written to `write_path` into the blob named after its `hash` in `blob_dir`,
+/// unless an identical blob exists already, and creates `file_path` as a hard link to the blob
+async fn link_upload_blob(
+    write_path: &Path,
+    file_path: &Path,
+    blob_dir: &Path,
+    hash: &[u8],
+    overwrite_files: bool,
+) -> Result<(), RuntimeError> {
+    let blob = blob_dir.join(hex::encode(hash));
+    let stored = async {
+        tokio::fs::create_dir_all(blob_dir).await?;
+        if tokio::fs::try_exists(&blob).await? {
+            tokio::fs::remove_file(write_path).await?;
+        } else {
+            move_file(write_path, &blob).await?;
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    if let Err(e) = stored.await {
+        let _ = tokio::fs::remove_file(write_path).await;
+        return Err(RuntimeError::IoError(
+            format!("Failed to store the blob of {}", file_path.display()),
+            e,
+        ));
+    }
+
+    // Overwriting replaces the link, leaving the blob of the previous file alone
+    if overwrite_files {
+        match tokio::fs::remove_file(file_path).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                return Err(RuntimeError::IoError(
+                    format!("Failed to replace {}", file_path.display()),
+                    e,
+                ))
+            }
+            _ => {}
+        }
+    }
+    match tokio::fs::hard_link(&blob, file_path).await {
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(RuntimeError::DuplicateFileError),
+        // Files on another filesystem than the blobs can't link to them, so they get a copy
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_file(&blob, file_path).await.map_err(|e| {
+                RuntimeError::IoError(format!("Failed to create {}", file_path.display()), e)
+            })
+        }
+        Err(e) => Err(RuntimeError::IoError(
+            format!("Failed to create {}", file_path.display()),
+            e,
+        )),
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Moves the file at `from` to `to`, copying it if they are on different filesystems
+async fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match tokio::fs::rename(from, to).await {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_file(from, to).await?;
+            tokio::fs::remove_file(from).await
+        }
+        result => result,
+    }
+}
+
+/// Copies the file at `from` to `to`, which only appears once it has been written completely
+async fn copy_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    let partial = to.with_extension(format!("{}.partial", nanoid::nanoid!(8)));
+    let copied = async {
+        tokio::fs::copy(from, &partial).await?;
+        tokio::fs::rename(&partial, to).await
+    };
+    let result = copied.await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&partial).await;
+    }
+    result
+}
+
+/// Returns the hidden path in the same directory that an upload is written to until it's verified
+fn unverified_upload_path(file_path: &Path) -> PathBuf {
+    let file_name = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    file_path.with_file_name(format!(".{file_name}{UNVERIFIED_UPLOAD_SUFFIX}"))
+}
+
+/// Appended to the names of uploads while they're verified, after a leading `.`
+const UNVERIFIED_UPLOAD_SUFFIX: &str = ".unverified";
+
+/// Extracts the uploaded archive at `archive_path` into `target_dir`, optionally deleting it
+/// afterwards.
+///
+/// The archive is removed if it is rejected, so that no partial upload remains.
+#[allow(clippy::too_many_arguments)]
+async fn extract_upload(
+    method: ArchiveMethod,
+    archive_path: PathBuf,
+    target_dir: PathBuf,
+    overwrite_files: bool,
+    allow_hidden_paths: bool,
+    allow_symlinks: bool,
+    delete_archive: bool,
+    limits: ExtractionLimits,
+) -> Result<(), RuntimeError> {
+    let extracted_archive = archive_path.clone();
+    let result = web::block(move || {
+        method.extract_archive(
+            &extracted_archive,
+            &target_dir,
+            allow_hidden_paths,
+            allow_symlinks,
+            overwrite_files,
+            limits,
+        )
+    })
+    .await
+    .map_err(|e| RuntimeError::ArchiveExtractionError(e.to_string()))
+    .and_then(|result| result);
+
+    if result.is_err() || delete_archive {
+        tokio::fs::remove_file(&archive_path).await.map_err(|e| {
+            RuntimeError::IoError(format!("Failed to delete {}", archive_path.display()), e)
+        })?;
+    }
+
+    result
+}
+
 /// Handles a single field in a multipart form
 async fn handle_multipart(
     mut field: actix_multipart::Field,
     path: PathBuf,
-    overwrite_files: bool,
-    allow_mkdir: bool,
-    allow_hidden_paths: bool,
-    allow_symlinks: bool,
+    conf: &MiniserveConfig,
+    progress: Option<(broadcast::Sender<UploadProgressEvent>, String)>,
+    req: &HttpRequest,
+    audit_log: &AuditLog,
 ) -> Result<u64, RuntimeError> {
+    let allow_hidden_paths = conf.show_hidden;
+    let allow_symlinks = !conf.no_symlinks;
     let field_name = field.name().expect("No name field found").to_string();
 
+    // Forms send the CSRF token even if the client also sent it in a header
+    if field_name == CSRF_TOKEN_FIELD {
+        while field
+            .try_next()
+            .await
+            .map_err(|e| multipart_error(e, conf.max_request_body))?
+            .is_some()
+        {}
+        return Ok(0);
+    }
+
     match tokio::fs::metadata(&path).await {
         Err(_) => Err(RuntimeError::InsufficientPermissionsError(
             path.display().to_string(),
@@ -75,7 +640,7 @@ async fn handle_multipart(
     }?;
 
     if field_name == "mkdir" {
-        if !allow_mkdir {
+        if !conf.mkdir_enabled {
             return Err(RuntimeError::InsufficientPermissionsError(
                 path.display().to_string(),
             ));
@@ -131,16 +696,14 @@ async fn handle_multipart(
             }
         }
 
-        return match tokio::fs::create_dir_all(&absolute_path).await {
-            Err(err) if err.kind() == ErrorKind::PermissionDenied => Err(
-                RuntimeError::InsufficientPermissionsError(path.display().to_string()),
-            ),
-            Err(err) => Err(RuntimeError::IoError(
-                format!("Failed to create {}", user_given_path.display()),
-                err,
-            )),
-            Ok(_) => Ok(0),
-        };
+        if conf.mkdir_error_on_exists && tokio::fs::metadata(&absolute_path).await.is_ok() {
+            return Err(RuntimeError::DuplicateFileError);
+        }
+
+        create_dirs(&absolute_path, conf).await?;
+        audit_log.record(req, AuditAction::Mkdir, &absolute_path, None);
+
+        return Ok(0);
     }
 
     let filename = field
@@ -154,40 +717,298 @@ async fn handle_multipart(
             )
         })?;
 
-    let filename_path = sanitize_path(Path::new(&filename), allow_hidden_paths)
+    // Uploads that have to be verified are written under a longer name first, see save_file
+    let temporary_suffix_len =
+        if conf.upload_hash_manifest.is_some() || conf.upload_pipe.is_some() || conf.cas_upload {
+            1 + UNVERIFIED_UPLOAD_SUFFIX.len()
+        } else {
+            0
+        };
+    let filename_path = upload_file_name(filename, conf, temporary_suffix_len)?;
+    ensure_no_symlinks(&path, conf)?;
+    let expected_hash = expected_upload_hash(&filename_path, conf)?;
+
+    let file_path = path.join(&filename_path);
+    let progress = progress.map(|(sender, upload_id)| ProgressReporter {
+        sender,
+        upload_id,
+        file_name: filename.to_string(),
+        last_report: None,
+    });
+    let written_len = save_file(
+        field,
+        file_path.clone(),
+        conf.overwrite_files,
+        conf.upload_file_mode,
+        progress,
+        expected_hash,
+        conf.upload_pipe.as_deref(),
+        conf.cas_upload
+            .then(|| conf.path.join(CAS_BLOB_DIR))
+            .as_deref(),
+        conf.max_request_body,
+    )
+    .await?;
+
+    finish_upload(&file_path, path, written_len, conf, req, audit_log).await?;
+
+    Ok(written_len)
+}
+
+/// Creates the directory `dir` along with its missing parents, setting their permissions to the
+/// configured mode
+async fn create_dirs(dir: &Path, conf: &MiniserveConfig) -> Result<(), RuntimeError> {
+    let created_dirs = dir
+        .ancestors()
+        .take_while(|dir| !dir.exists())
+        .map(Path::to_path_buf)
+        .collect::<Vec<_>>();
+
+    match tokio::fs::create_dir_all(dir).await {
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => Err(
+            RuntimeError::InsufficientPermissionsError(dir.display().to_string()),
+        ),
+        Err(err) => Err(RuntimeError::IoError(
+            format!("Failed to create {}", dir.display()),
+            err,
+        )),
+        Ok(_) => Ok(()),
+    }?;
+
+    for dir in created_dirs {
+        set_mode(&dir, conf.upload_dir_mode).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns the sanitized path an uploaded file with the name given by the client is stored
+/// under, relative to the upload directory
+///
+/// While it's written, the file may have a temporary name that is `temporary_suffix_len` bytes
+/// longer, which has to fit into the maximum file name length as well.
+pub fn upload_file_name(
+    file_name: &str,
+    conf: &MiniserveConfig,
+    temporary_suffix_len: usize,
+) -> Result<PathBuf, RuntimeError> {
+    let file_name_path = sanitize_path(Path::new(file_name), conf.show_hidden)
         .ok_or_else(|| RuntimeError::InvalidPathError("Invalid file name to upload".to_string()))?;
+    let file_name_path = match file_name_path.file_name() {
+        Some(name) if conf.slugify_uploads => {
+            file_name_path.with_file_name(slugify_file_name(&name.to_string_lossy()))
+        }
+        _ => file_name_path,
+    };
 
-    // Ensure there are no illegal symlinks in the file upload path
-    if !allow_symlinks {
-        match contains_symlink(&path) {
-            Err(err) => Err(RuntimeError::InsufficientPermissionsError(err.to_string()))?,
-            Ok(true) => Err(RuntimeError::InsufficientPermissionsError(format!(
-                "{path:?} traverses through a symlink"
-            )))?,
-            Ok(false) => (),
+    if let Some(max_len) = conf.max_filename_length {
+        let mut components = file_name_path.components().peekable();
+        while let Some(component) = components.next() {
+            // Only the file itself is written under a longer name
+            let max_component_len = match components.peek() {
+                Some(_) => max_len,
+                None => max_len.saturating_sub(temporary_suffix_len),
+            };
+            if component.as_os_str().as_encoded_bytes().len() > max_component_len {
+                return Err(RuntimeError::FileNameTooLongError(max_component_len));
+            }
         }
     }
 
-    save_file(field, path.join(filename_path), overwrite_files).await
+    Ok(file_name_path)
+}
+
+/// Ensures there are no illegal symlinks in the upload directory `path`
+pub fn ensure_no_symlinks(path: &Path, conf: &MiniserveConfig) -> Result<(), RuntimeError> {
+    if !conf.no_symlinks {
+        return Ok(());
+    }
+    match contains_symlink(path) {
+        Err(err) => Err(RuntimeError::InsufficientPermissionsError(err.to_string())),
+        Ok(true) => Err(RuntimeError::InsufficientPermissionsError(format!(
+            "{path:?} traverses through a symlink"
+        ))),
+        Ok(false) => Ok(()),
+    }
+}
+
+/// Returns the checksum an uploaded file has to match, if uploads are restricted to the files of
+/// the upload hash manifest
+pub fn expected_upload_hash<'a>(
+    file_name_path: &Path,
+    conf: &'a MiniserveConfig,
+) -> Result<Option<&'a [u8]>, RuntimeError> {
+    match &conf.upload_hash_manifest {
+        Some(manifest) => {
+            let manifest_name = file_name_path.display().to_string().replace('\\', "/");
+            let hash = manifest
+                .get(&manifest_name)
+                .ok_or(RuntimeError::UploadNotInManifestError(manifest_name))?;
+            Ok(Some(hash.as_slice()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Extracts the saved upload at `file_path` into `target_dir` if it's an archive and uploads
+/// are extracted, and records the upload in the audit log
+pub async fn finish_upload(
+    file_path: &Path,
+    target_dir: PathBuf,
+    written_len: u64,
+    conf: &MiniserveConfig,
+    req: &HttpRequest,
+    audit_log: &AuditLog,
+) -> Result<(), RuntimeError> {
+    if conf.extract_uploads {
+        if let Some(method) = ArchiveMethod::from_file_name(file_path) {
+            extract_upload(
+                method,
+                file_path.to_path_buf(),
+                target_dir,
+                conf.overwrite_files,
+                conf.show_hidden,
+                !conf.no_symlinks,
+                conf.delete_extracted_archives,
+                ExtractionLimits {
+                    max_total_size: conf.extract_max_total_size,
+                    max_ratio: conf.extract_max_ratio,
+                },
+            )
+            .await?;
+        }
+    }
+
+    audit_log.record(req, AuditAction::Upload, file_path, Some(written_len));
+
+    Ok(())
 }
 
 /// Query parameters used by upload and rm APIs
 #[derive(Deserialize, Default)]
 pub struct FileOpQueryParameters {
     path: PathBuf,
+
+    /// Identifies the upload in the progress events, which are only sent if this is set
+    upload_id: Option<String>,
 }
 
-/// Handle incoming request to upload a file or create a directory.
-/// Target file path is expected as path parameter in URI and is interpreted as relative from
-/// server root directory. Any path which will go outside of this directory is considered
-/// invalid.
-/// This method returns future.
-pub async fn upload_file(
-    req: HttpRequest,
-    query: web::Query<FileOpQueryParameters>,
-    payload: web::Payload,
-) -> Result<HttpResponse, RuntimeError> {
+/// Checks whether the client may upload at all, regardless of the target directory
+fn check_upload_allowed(req: &HttpRequest, conf: &MiniserveConfig) -> Result<(), RuntimeError> {
+    if conf.upload_requires_auth && req.extensions().get::<CurrentUser>().is_none() {
+        return Err(RuntimeError::InvalidHttpCredentials);
+    }
+
+    if !conf.upload_allowed_referers.is_empty() {
+        check_referer(req, &conf.upload_allowed_referers)?;
+    }
+
+    Ok(())
+}
+
+/// Checks whether the request comes from a page on one of the `allowed` hosts, as told by the
+/// Origin header or, if there's none, the Referer header
+fn check_referer(req: &HttpRequest, allowed: &[String]) -> Result<(), RuntimeError> {
+    let referer = req
+        .headers()
+        .get(header::ORIGIN)
+        .or_else(|| req.headers().get(header::REFERER))
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+
+    let is_allowed = referer
+        .parse::<Uri>()
+        .ok()
+        .and_then(|uri| uri.authority().cloned())
+        .is_some_and(|authority| {
+            allowed.iter().any(|host| {
+                authority.as_str().eq_ignore_ascii_case(host)
+                    || authority.host().eq_ignore_ascii_case(host)
+            })
+        });
+
+    if is_allowed {
+        Ok(())
+    } else {
+        Err(RuntimeError::RefererNotAllowedError(match referer {
+            "" => "unknown pages".to_string(),
+            referer => referer.to_string(),
+        }))
+    }
+}
+
+/// Converts an error of a multipart request, telling apart bodies larger than
+/// `max_request_body`
+fn multipart_error(err: MultipartError, max_request_body: Option<u64>) -> RuntimeError {
+    match (err, max_request_body) {
+        (MultipartError::Payload(PayloadError::Overflow), Some(limit)) => {
+            RuntimeError::PayloadTooLargeError(limit)
+        }
+        (err, _) => RuntimeError::MultipartError(err.to_string()),
+    }
+}
+
+/// Checks the CSRF token of an upload if --upload-csrf is set.
+///
+/// The token is taken from the X-Csrf-Token header, or else from `form_token`.
+pub fn check_csrf_token(req: &HttpRequest, form_token: Option<&str>) -> Result<(), RuntimeError> {
     let conf = req.app_data::<MiniserveConfig>().unwrap();
+    let Some(secret) = &conf.upload_csrf_secret else {
+        return Ok(());
+    };
+
+    let token = req
+        .headers()
+        .get(CSRF_TOKEN_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .or(form_token)
+        .unwrap_or_default();
+    if verify_csrf_token(secret, req.extensions().get::<CurrentUser>(), token) {
+        Ok(())
+    } else {
+        Err(RuntimeError::CsrfTokenError)
+    }
+}
+
+/// Reads the CSRF token from the first field of a multipart request, which is where the upload
+/// forms put it
+async fn read_csrf_token_field(
+    multipart: &mut (impl futures::Stream<Item = Result<actix_multipart::Field, RuntimeError>> + Unpin),
+) -> Result<Option<String>, RuntimeError> {
+    let Some(mut field) = multipart.try_next().await? else {
+        return Ok(None);
+    };
+    if field.name() != Some(CSRF_TOKEN_FIELD) {
+        return Ok(None);
+    }
+
+    let mut token = Vec::new();
+    while let Some(chunk) = field
+        .try_next()
+        .await
+        .map_err(|e| RuntimeError::MultipartError(e.to_string()))?
+    {
+        token.extend_from_slice(&chunk);
+        if token.len() > CSRF_TOKEN_MAX_LEN {
+            return Ok(None);
+        }
+    }
+
+    Ok(String::from_utf8(token).ok())
+}
+
+/// Checks whether the client may upload to the directory given by the `path` query parameter
+/// and returns it.
+///
+/// The target directory isn't canonicalized so that it can still be checked for symlinks. It may
+/// not exist yet if `--create-upload-dirs` is enabled, see [`create_upload_target_dir`].
+pub async fn upload_target_dir(
+    req: &HttpRequest,
+    query: &FileOpQueryParameters,
+) -> Result<PathBuf, RuntimeError> {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    check_upload_allowed(req, conf)?;
+
     let upload_path = sanitize_path(&query.path, conf.show_hidden).ok_or_else(|| {
         RuntimeError::InvalidPathError("Invalid value for 'path' parameter".to_string())
     })?;
@@ -206,38 +1027,142 @@ pub async fn upload_file(
         return Err(RuntimeError::UploadForbiddenError);
     }
 
-    // Disallow the target path to go outside of the served directory
-    // The target directory shouldn't be canonicalized when it gets passed to
-    // handle_multipart so that it can check for symlinks if needed
+    // Directories have to opt into uploads as well if enabled
     let non_canonicalized_target_dir = app_root_dir.join(upload_path);
-    match non_canonicalized_target_dir.canonicalize() {
-        Ok(path) if !conf.no_symlinks => Ok(path),
-        Ok(path) if path.starts_with(&app_root_dir) => Ok(path),
+    if conf.sidecar_upload_control
+        && !non_canonicalized_target_dir
+            .join(UPLOAD_MARKER_FILE)
+            .is_file()
+    {
+        return Err(RuntimeError::UploadForbiddenError);
+    }
+
+    // Missing directories are only created once the upload is authorized. The path is
+    // sanitized, so it can only leave the served directory through a symlink.
+    if conf.create_upload_dirs && !non_canonicalized_target_dir.exists() {
+        ensure_no_symlinks(&non_canonicalized_target_dir, conf)?;
+        return Ok(non_canonicalized_target_dir);
+    }
+
+    ensure_inside_served_dir(&non_canonicalized_target_dir, &app_root_dir, conf)?;
+
+    Ok(non_canonicalized_target_dir)
+}
+
+/// Creates the upload target directory returned by [`upload_target_dir`] if it's missing.
+///
+/// Must only be called once the upload passed the CSRF check.
+pub async fn create_upload_target_dir(
+    dir: &Path,
+    conf: &MiniserveConfig,
+) -> Result<(), RuntimeError> {
+    if !conf.create_upload_dirs || dir.exists() {
+        return Ok(());
+    }
+
+    create_dirs(dir, conf).await?;
+    let app_root_dir = conf.path.canonicalize().map_err(|e| {
+        RuntimeError::IoError("Failed to resolve path served by miniserve".to_string(), e)
+    })?;
+    ensure_inside_served_dir(dir, &app_root_dir, conf)
+}
+
+/// Disallows the upload target directory to go outside of the served directory
+fn ensure_inside_served_dir(
+    dir: &Path,
+    app_root_dir: &Path,
+    conf: &MiniserveConfig,
+) -> Result<(), RuntimeError> {
+    match dir.canonicalize() {
+        Ok(_) if !conf.no_symlinks => Ok(()),
+        Ok(path) if path.starts_with(app_root_dir) => Ok(()),
         _ => Err(RuntimeError::InvalidHttpRequestError(
             "Invalid value for 'path' parameter".to_string(),
         )),
-    }?;
+    }
+}
+
+/// Waits for one of the running uploads to finish if the maximum number of concurrent uploads
+/// is reached. The returned permit has to be held until the upload is done.
+pub async fn acquire_upload_permit(
+    limiter: &UploadLimiter,
+) -> Result<Option<SemaphorePermit<'_>>, RuntimeError> {
+    match &limiter.0 {
+        Some(semaphore) => Ok(Some(
+            tokio::time::timeout(UPLOAD_PERMIT_TIMEOUT, semaphore.acquire())
+                .await
+                .map_err(|_| RuntimeError::TooManyUploadsError)?
+                .map_err(|_| RuntimeError::TooManyUploadsError)?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Handle incoming request to upload a file or create a directory.
+/// Target file path is expected as path parameter in URI and is interpreted as relative from
+/// server root directory. Any path which will go outside of this directory is considered
+/// invalid.
+/// This method returns future.
+pub async fn upload_file(
+    req: HttpRequest,
+    query: web::Query<FileOpQueryParameters>,
+    payload: web::Payload,
+    limiter: web::Data<UploadLimiter>,
+    progress: web::Data<UploadProgress>,
+    audit_log: web::Data<AuditLog>,
+) -> Result<HttpResponse, RuntimeError> {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    let non_canonicalized_target_dir = upload_target_dir(&req, &query).await?;
+
+    let mut multipart = std::pin::pin!(actix_multipart::Multipart::new(req.headers(), payload)
+        .map_err(|x| multipart_error(x, conf.max_request_body)));
+
+    // Browsers can't add headers when submitting a form, so the forms send the token first
+    let form_token =
+        if conf.upload_csrf_secret.is_some() && !req.headers().contains_key(CSRF_TOKEN_HEADER) {
+            read_csrf_token_field(&mut multipart).await?
+        } else {
+            None
+        };
+    check_csrf_token(&req, form_token.as_deref())?;
 
-    actix_multipart::Multipart::new(req.headers(), payload)
-        .map_err(|x| RuntimeError::MultipartError(x.to_string()))
+    // Held until the upload is done
+    let _permit = acquire_upload_permit(&limiter).await?;
+    create_upload_target_dir(&non_canonicalized_target_dir, conf).await?;
+
+    multipart
         .and_then(|field| {
             handle_multipart(
                 field,
                 non_canonicalized_target_dir.clone(),
-                conf.overwrite_files,
-                conf.mkdir_enabled,
-                conf.show_hidden,
-                !conf.no_symlinks,
+                conf,
+                query
+                    .upload_id
+                    .clone()
+                    .map(|upload_id| (progress.0.clone(), upload_id)),
+                &req,
+                &audit_log,
             )
         })
         .try_collect::<Vec<u64>>()
         .await?;
 
-    let return_path = req
+    let redirect = req
         .headers()
-        .get(header::REFERER)
+        .get(UPLOAD_REDIRECT_HEADER)
         .and_then(|h| h.to_str().ok())
-        .unwrap_or("/");
+        .and_then(|h| parse_upload_redirect(h).ok())
+        .or_else(|| conf.upload_redirect.clone());
+    let return_path = match redirect {
+        Some(UploadRedirect::None) => return Ok(HttpResponse::Ok().finish()),
+        Some(UploadRedirect::Location(location)) => location,
+        None => req
+            .headers()
+            .get(header::REFERER)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("/")
+            .to_string(),
+    };
 
     Ok(HttpResponse::SeeOther()
         .append_header((header::LOCATION, return_path))
@@ -1,8 +1,15 @@
 use std::{
-    io,
+    collections::HashMap,
+    fs::{File, Metadata},
+    io::{self, Read},
     path::{Component, Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
 };
 
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
 /// Guarantee that the path is relative and cannot traverse back to parent directories
 /// and optionally prevent traversing hidden directories.
 ///
@@ -32,6 +39,237 @@ pub fn sanitize_path(path: impl AsRef<Path>, traverse_hidden: bool) -> Option<Pa
     Some(buf)
 }
 
+/// Turn a file name into a filesystem-safe slug.
+///
+/// The name is lowercased and every run of characters other than ASCII letters and digits is
+/// replaced by a single `-`. The extension is kept, and a leading `.` of hidden files as well.
+///
+/// See the unit tests tests::test_slugify_file_name for examples
+pub fn slugify_file_name(name: &str) -> String {
+    fn slugify(part: &str) -> String {
+        part.to_lowercase()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    let (prefix, name) = match name.strip_prefix('.') {
+        Some(name) => (".", name),
+        None => ("", name),
+    };
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) => (stem, slugify(extension)),
+        None => (name, String::new()),
+    };
+
+    let mut slug = slugify(stem);
+    if slug.is_empty() {
+        slug.push_str("file");
+    }
+    if !extension.is_empty() {
+        slug = format!("{slug}.{extension}");
+    }
+
+    format!("{prefix}{slug}")
+}
+
+/// Turn a glob matching file names, like `*.pdf`, into a regular expression.
+///
+/// `*` matches any number of characters and `?` a single one, everything else matches itself.
+pub fn glob_to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern)
+}
+
+/// Checks whether a file name matches one of the `filters`, which is always the case without any
+/// filters
+pub fn matches_filters(file_name: &str, filters: &[Regex]) -> bool {
+    filters.is_empty() || filters.iter().any(|filter| filter.is_match(file_name))
+}
+
+/// Checks whether a file was last modified longer ago than `max_age`
+pub fn is_older_than(metadata: &Metadata, max_age: Duration) -> bool {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > max_age)
+}
+
+/// Returns the available and total space in bytes of the filesystem `path` resides on
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let stat = nix::sys::statvfs::statvfs(path).ok()?;
+    let fragment_size = stat.fragment_size() as u64;
+    Some((
+        stat.blocks_available() as u64 * fragment_size,
+        stat.blocks() as u64 * fragment_size,
+    ))
+}
+
+/// Returns the available and total space in bytes of the filesystem `path` resides on
+#[cfg(not(unix))]
+pub fn disk_space(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// Returns the values of the extended attributes `names` of `path` that are set and valid UTF-8
+#[cfg(unix)]
+pub fn xattrs(path: &Path, names: &[String]) -> Vec<(String, String)> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let value = xattr::get(path, name).ok()??;
+            Some((name.clone(), String::from_utf8(value).ok()?))
+        })
+        .collect()
+}
+
+/// Returns the values of the extended attributes `names` of `path` that are set and valid UTF-8
+#[cfg(not(unix))]
+pub fn xattrs(_path: &Path, _names: &[String]) -> Vec<(String, String)> {
+    Vec::new()
+}
+
+/// Returns the content type browsers expect for common audio and video files
+pub fn media_content_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let content_type = match ext.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "flac" => "audio/flac",
+        "wav" => "audio/wav",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// How many bytes at the start of a file are read to detect its content type
+const MIME_SNIFF_LIMIT: u64 = 8 * 1024;
+
+/// How many detected content types are cached at most
+const MIME_SNIFF_CACHE_CAPACITY: usize = 1024;
+
+/// Content type detected from a file's contents at its given modification date
+type SniffedMimeType = (Option<SystemTime>, Option<&'static str>);
+
+/// Content types detected from file contents
+#[derive(Default)]
+pub struct MimeSniffCache(Mutex<HashMap<PathBuf, SniffedMimeType>>);
+
+impl MimeSniffCache {
+    /// Detects the content type of `path` from its first bytes, if it's a known binary format
+    pub fn sniff(&self, path: &Path) -> Option<&'static str> {
+        let modified = path.metadata().and_then(|m| m.modified()).ok();
+        if let Some((cached_modified, mime_type)) = self.0.lock().unwrap().get(path) {
+            if *cached_modified == modified {
+                return *mime_type;
+            }
+        }
+
+        let mut buf = Vec::new();
+        File::open(path)
+            .and_then(|file| file.take(MIME_SNIFF_LIMIT).read_to_end(&mut buf))
+            .ok()?;
+        let mime_type = infer::get(&buf).map(|kind| kind.mime_type());
+
+        let mut cache = self.0.lock().unwrap();
+        if cache.len() >= MIME_SNIFF_CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(path.to_path_buf(), (modified, mime_type));
+        mime_type
+    }
+}
+
+/// Resolves the relative `path` inside of `base` ignoring the case of its components.
+///
+/// If several entries of a directory match a component, the first one in lexicographic order is
+/// picked so that the result doesn't depend on the order in which the filesystem lists them.
+/// Returns `None` if no entry matches, or if a matching entry is a symlink and `allow_symlinks`
+/// isn't set.
+pub fn resolve_case_insensitive(base: &Path, path: &Path, allow_symlinks: bool) -> Option<PathBuf> {
+    let mut resolved = base.to_path_buf();
+
+    for component in path.components() {
+        let wanted = component.as_os_str().to_string_lossy().to_lowercase();
+        let mut candidates = std::fs::read_dir(&resolved)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name())
+            .filter(|name| name.to_string_lossy().to_lowercase() == wanted)
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        let name = candidates.first()?;
+        if candidates.len() > 1 {
+            log::warn!(
+                "{} matches several entries of {} when ignoring case, picking {}",
+                wanted,
+                resolved.display(),
+                name.to_string_lossy()
+            );
+        }
+
+        resolved.push(name);
+        if !allow_symlinks && resolved.is_symlink() {
+            return None;
+        }
+    }
+
+    Some(resolved)
+}
+
+/// Minimum time between two progress messages while verifying files against a manifest
+const VERIFY_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Verifies the files in `root` against a manifest of SHA-256 checksums by their relative path.
+///
+/// Returns the paths of the files that are missing or don't match their checksum. This reads
+/// every listed file, so it logs its progress from time to time.
+pub fn verify_manifest(root: &Path, manifest: &HashMap<String, Vec<u8>>) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let mut last_report = Instant::now();
+
+    for (verified, (file_name, expected_hash)) in manifest.iter().enumerate() {
+        if last_report.elapsed() > VERIFY_PROGRESS_INTERVAL {
+            log::info!("Verified {verified} of {} files", manifest.len());
+            last_report = Instant::now();
+        }
+
+        let hash = sanitize_path(file_name, true)
+            .and_then(|path| File::open(root.join(path)).ok())
+            .and_then(|mut file| {
+                let mut hasher = Sha256::new();
+                io::copy(&mut file, &mut hasher).ok()?;
+                Some(hasher.finalize())
+            });
+        if hash.is_none_or(|hash| hash.as_slice() != expected_hash) {
+            mismatches.push(file_name.clone());
+        }
+    }
+
+    mismatches.sort();
+    mismatches
+}
+
 /// Checks if any segment of the path is a symlink.
 ///
 /// This function fails if [`std::fs::symlink_metadata`] fails, which usually
@@ -81,4 +319,27 @@ mod tests {
     fn test_sanitize_path_no_hidden_files(#[case] input: &str) {
         assert_eq!(sanitize_path(Path::new(input), false), None);
     }
+
+    #[rstest]
+    #[case("My File (1).TXT", "my-file-1.txt")]
+    #[case("already-a-slug.txt", "already-a-slug.txt")]
+    #[case("Ünïcödé näme.tar.gz", "n-c-d-n-me-tar.gz")]
+    #[case("  spaces   everywhere  ", "spaces-everywhere")]
+    #[case(".Hidden File", ".hidden-file")]
+    #[case("(((.md", "file.md")]
+    fn test_slugify_file_name(#[case] input: &str, #[case] output: &str) {
+        assert_eq!(slugify_file_name(input), output);
+    }
+
+    #[rstest]
+    #[case("*.pdf", "report.pdf", true)]
+    #[case("*.pdf", "report.pdf.txt", false)]
+    #[case("*.pdf", "reportpdf", false)]
+    #[case("report-?.txt", "report-1.txt", true)]
+    #[case("report-?.txt", "report-10.txt", false)]
+    #[case("[a].txt", "[a].txt", true)]
+    #[case("[a].txt", "a.txt", false)]
+    fn test_glob_to_regex(#[case] glob: &str, #[case] file_name: &str, #[case] matches: bool) {
+        assert_eq!(glob_to_regex(glob).unwrap().is_match(file_name), matches);
+    }
 }
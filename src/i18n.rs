@@ -0,0 +1,191 @@
+//! Translations of the strings shown in the web UI
+
+use actix_web::{http::header, HttpRequest};
+use clap::ValueEnum;
+use strum::{Display, EnumIter, IntoEnumIterator};
+
+use crate::config::MiniserveConfig;
+
+/// Available UI languages
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Display, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum Language {
+    /// English
+    #[default]
+    En,
+
+    /// German
+    De,
+
+    /// French
+    Fr,
+}
+
+/// All the strings of the web UI for one language
+pub struct Translations {
+    pub drop_to_upload: &'static str,
+    pub create_directory_title: &'static str,
+    pub qr_code: &'static str,
+    pub change_theme: &'static str,
+    pub upload_prompt: &'static str,
+    pub upload_file: &'static str,
+    pub mkdir_prompt: &'static str,
+    pub directory_name: &'static str,
+    pub create_directory: &'static str,
+    pub log_in_to_upload: &'static str,
+    pub name: &'static str,
+    pub title: &'static str,
+    pub size: &'static str,
+    pub last_modification: &'static str,
+    pub parent_directory: &'static str,
+    pub download: &'static str,
+    pub download_files: &'static str,
+    pub back_to_listing: &'static str,
+    pub partial_listing: &'static str,
+    pub listing_description: &'static str,
+    pub dismiss: &'static str,
+    pub disk_space: &'static str,
+}
+
+const EN: Translations = Translations {
+    drop_to_upload: "Drop your file here to upload it",
+    create_directory_title: "Create a new directory",
+    qr_code: "QR code",
+    change_theme: "Change theme...",
+    upload_prompt: "Select a file to upload or drag it anywhere into the window",
+    upload_file: "Upload file",
+    mkdir_prompt: "Specify a directory name to create",
+    directory_name: "Directory name",
+    create_directory: "Create directory",
+    log_in_to_upload: "Log in to upload files",
+    name: "Name",
+    title: "Title",
+    size: "Size",
+    last_modification: "Last modification",
+    parent_directory: "Parent directory",
+    download: "Download",
+    download_files: "Download all files (flat .zip)",
+    back_to_listing: "Go back to file listing",
+    partial_listing: "Reading this directory took too long, so some entries may be missing",
+    listing_description: "Browse and download the files in this directory",
+    dismiss: "Dismiss",
+    disk_space: "{free} free of {total}",
+};
+
+const DE: Translations = Translations {
+    drop_to_upload: "Datei hier ablegen, um sie hochzuladen",
+    create_directory_title: "Neues Verzeichnis erstellen",
+    qr_code: "QR-Code",
+    change_theme: "Design ändern...",
+    upload_prompt: "Datei zum Hochladen auswählen oder irgendwo ins Fenster ziehen",
+    upload_file: "Datei hochladen",
+    mkdir_prompt: "Name des zu erstellenden Verzeichnisses angeben",
+    directory_name: "Verzeichnisname",
+    create_directory: "Verzeichnis erstellen",
+    log_in_to_upload: "Anmelden, um Dateien hochzuladen",
+    name: "Name",
+    title: "Titel",
+    size: "Größe",
+    last_modification: "Letzte Änderung",
+    parent_directory: "Übergeordnetes Verzeichnis",
+    download: "Herunterladen",
+    download_files: "Alle Dateien herunterladen (flaches .zip)",
+    back_to_listing: "Zurück zur Dateiliste",
+    partial_listing: "Das Lesen dieses Verzeichnisses hat zu lange gedauert, daher fehlen möglicherweise Einträge",
+    listing_description: "Dateien in diesem Verzeichnis ansehen und herunterladen",
+    dismiss: "Ausblenden",
+    disk_space: "{free} von {total} frei",
+};
+
+const FR: Translations = Translations {
+    drop_to_upload: "Déposez votre fichier ici pour le téléverser",
+    create_directory_title: "Créer un nouveau dossier",
+    qr_code: "Code QR",
+    change_theme: "Changer de thème...",
+    upload_prompt:
+        "Sélectionnez un fichier à téléverser ou glissez-le n'importe où dans la fenêtre",
+    upload_file: "Téléverser le fichier",
+    mkdir_prompt: "Indiquez le nom du dossier à créer",
+    directory_name: "Nom du dossier",
+    create_directory: "Créer le dossier",
+    log_in_to_upload: "Se connecter pour téléverser des fichiers",
+    name: "Nom",
+    title: "Titre",
+    size: "Taille",
+    last_modification: "Dernière modification",
+    parent_directory: "Dossier parent",
+    download: "Télécharger",
+    download_files: "Télécharger tous les fichiers (.zip plat)",
+    back_to_listing: "Retour à la liste des fichiers",
+    partial_listing:
+        "La lecture de ce dossier a pris trop de temps, certaines entrées peuvent manquer",
+    listing_description: "Parcourir et télécharger les fichiers de ce dossier",
+    dismiss: "Masquer",
+    disk_space: "{free} libres sur {total}",
+};
+
+impl Language {
+    /// Returns the UI strings of this language
+    pub fn translations(self) -> &'static Translations {
+        match self {
+            Self::En => &EN,
+            Self::De => &DE,
+            Self::Fr => &FR,
+        }
+    }
+
+    /// Picks the preferred supported language from an `Accept-Language` header value
+    pub fn from_accept_language(accept_language: &str) -> Option<Self> {
+        let mut preferences = accept_language
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((tag, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect::<Vec<_>>();
+
+        // Stable sort, so that tags with the same quality keep their order
+        preferences.sort_by(|(_, q1), (_, q2)| q2.total_cmp(q1));
+
+        preferences.into_iter().find_map(|(tag, _)| {
+            let primary = tag.split('-').next()?.to_lowercase();
+            Self::iter().find(|lang| lang.to_string() == primary)
+        })
+    }
+
+    /// Determines the language to use for a request
+    ///
+    /// The `Accept-Language` header is only honored when enabled in the config.
+    pub fn for_request(req: &HttpRequest, conf: &MiniserveConfig) -> Self {
+        conf.follow_accept_language
+            .then(|| req.headers().get(header::ACCEPT_LANGUAGE))
+            .flatten()
+            .and_then(|h| h.to_str().ok())
+            .and_then(Self::from_accept_language)
+            .unwrap_or(conf.lang)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("de", Some(Language::De))]
+    #[case("de-DE,de;q=0.9,en;q=0.8", Some(Language::De))]
+    #[case("en;q=0.5, fr-CH", Some(Language::Fr))]
+    #[case("es, de;q=0.1", Some(Language::De))]
+    #[case("es, it", None)]
+    #[case("de;q=0", None)]
+    fn test_from_accept_language(#[case] input: &str, #[case] expected: Option<Language>) {
+        assert_eq!(Language::from_accept_language(input), expected);
+    }
+}
@@ -1,29 +1,46 @@
 #![allow(clippy::format_push_string)]
-use std::io;
-use std::path::{Component, Path};
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 use actix_web::{
-    dev::ServiceResponse, http::Uri, web::Query, HttpMessage, HttpRequest, HttpResponse,
+    dev::ServiceResponse,
+    http::{
+        header::{
+            self, ContentDisposition, ContentEncoding, DispositionParam, DispositionType,
+            HeaderValue,
+        },
+        Method, Uri,
+    },
+    web::{self, Query},
+    HttpMessage, HttpRequest, HttpResponse,
 };
 use bytesize::ByteSize;
 use clap::ValueEnum;
 use comrak::{markdown_to_html, ComrakOptions};
+use libflate::gzip::Encoder;
 use percent_encoding::{percent_decode_str, utf8_percent_encode};
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
 
-use crate::archive::ArchiveMethod;
+use crate::archive::{ArchiveMethod, ArchiveOptions};
+use crate::args::GroupBy;
 use crate::auth::CurrentUser;
+use crate::config::MiniserveConfig;
 use crate::errors::{self, RuntimeError};
+use crate::file_utils::{self, is_older_than};
+use crate::i18n::Language;
 use crate::renderer;
+use crate::tree::DirectoryTree;
 
 use self::percent_encode_sets::COMPONENT;
 
 /// "percent-encode sets" as defined by WHATWG specs:
 /// https://url.spec.whatwg.org/#percent-encoded-bytes
-mod percent_encode_sets {
+pub mod percent_encode_sets {
     use percent_encoding::{AsciiSet, CONTROLS};
     pub const QUERY: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'<').add(b'>');
     pub const PATH: &AsciiSet = &QUERY.add(b'?').add(b'`').add(b'{').add(b'}');
@@ -47,11 +64,27 @@ pub struct ListingQueryParameters {
     pub sort: Option<SortingMethod>,
     pub order: Option<SortingOrder>,
     pub raw: Option<bool>,
+    page: Option<usize>,
+    tree: Option<bool>,
+    format: Option<ListingFormat>,
     download: Option<ArchiveMethod>,
+    #[serde(rename = "download-flat")]
+    download_flat: Option<ArchiveMethod>,
+    login: Option<bool>,
+}
+
+/// Alternative formats a listing can be requested in
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingFormat {
+    /// Machine-readable index of the entries
+    Json,
 }
 
 /// Available sorting methods
-#[derive(Deserialize, Default, Clone, EnumString, Display, Copy, ValueEnum)]
+#[derive(
+    Deserialize, Default, Clone, EnumString, Display, Copy, ValueEnum, PartialEq, Eq, Hash,
+)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub enum SortingMethod {
@@ -64,10 +97,15 @@ pub enum SortingMethod {
 
     /// Sort by last modification date (natural sort: follows alphanumerical order)
     Date,
+
+    /// Sort by the title given in the metadata sidecar, falling back to the name
+    Title,
 }
 
 /// Available sorting orders
-#[derive(Deserialize, Default, Clone, EnumString, Display, Copy, ValueEnum)]
+#[derive(
+    Deserialize, Default, Clone, EnumString, Display, Copy, ValueEnum, PartialEq, Eq, Hash,
+)]
 pub enum SortingOrder {
     /// Ascending order
     #[serde(alias = "asc")]
@@ -81,7 +119,7 @@ pub enum SortingOrder {
     Desc,
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 /// Possible entry types
 pub enum EntryType {
     /// Entry is a directory
@@ -89,9 +127,13 @@ pub enum EntryType {
 
     /// Entry is a file
     File,
+
+    /// Entry is a symlink whose target does not exist
+    BrokenSymlink,
 }
 
 /// Entry
+#[derive(Clone)]
 pub struct Entry {
     /// Name of the entry
     pub name: String,
@@ -110,6 +152,21 @@ pub struct Entry {
 
     /// Path of symlink pointed to
     pub symlink_info: Option<String>,
+
+    /// Whether the entry is a symlink
+    pub is_symlink: bool,
+
+    /// Guessed content type of the entry. Only available for EntryType::File
+    pub mime_type: Option<String>,
+
+    /// Title and description from the metadata sidecar of the directory
+    pub metadata: Option<EntryMetadata>,
+
+    /// Names and values of the extended attributes shown with --show-xattrs
+    pub xattrs: Vec<(String, String)>,
+
+    /// Names of the nested directories collapsed into this one by --collapse-single-dirs
+    pub collapsed: Vec<String>,
 }
 
 impl Entry {
@@ -120,6 +177,7 @@ impl Entry {
         size: Option<bytesize::ByteSize>,
         last_modification_date: Option<SystemTime>,
         symlink_info: Option<String>,
+        mime_type: Option<String>,
     ) -> Self {
         Self {
             name,
@@ -128,9 +186,43 @@ impl Entry {
             size,
             last_modification_date,
             symlink_info,
+            is_symlink: false,
+            mime_type,
+            metadata: None,
+            xattrs: Vec::new(),
+            collapsed: Vec::new(),
         }
     }
 
+    /// Marks the entry as being a symlink
+    fn symlink(mut self, is_symlink: bool) -> Self {
+        self.is_symlink = is_symlink;
+        self
+    }
+
+    /// Sets the extended attributes shown for the entry
+    fn xattrs(mut self, xattrs: Vec<(String, String)>) -> Self {
+        self.xattrs = xattrs;
+        self
+    }
+
+    /// Collapses the given chain of nested directories into the entry, linking to the deepest one
+    fn collapsed(mut self, collapsed: Vec<String>) -> Self {
+        for name in &collapsed {
+            self.link = format!("{}/{}", self.link, utf8_percent_encode(name, COMPONENT));
+        }
+        self.collapsed = collapsed;
+        self
+    }
+
+    /// Returns the title from the metadata sidecar, or the name if there is none
+    pub fn title(&self) -> &str {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.title.as_deref())
+            .unwrap_or(&self.name)
+    }
+
     /// Returns whether the entry is a directory
     pub fn is_dir(&self) -> bool {
         self.entry_type == EntryType::Directory
@@ -140,6 +232,86 @@ impl Entry {
     pub fn is_file(&self) -> bool {
         self.entry_type == EntryType::File
     }
+
+    /// Returns whether the entry is a broken symlink
+    pub fn is_broken_symlink(&self) -> bool {
+        self.entry_type == EntryType::BrokenSymlink
+    }
+}
+
+/// An entry of the JSON index of a directory
+#[derive(Serialize)]
+struct JsonIndexEntry<'a> {
+    name: &'a str,
+
+    #[serde(rename = "type")]
+    entry_type: &'static str,
+
+    link: &'a str,
+
+    /// Size of files in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+
+    /// Last modification as seconds since the UNIX epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<u64>,
+
+    is_symlink: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mime_type: Option<&'a str>,
+}
+
+impl<'a> From<&'a Entry> for JsonIndexEntry<'a> {
+    fn from(entry: &'a Entry) -> Self {
+        Self {
+            name: &entry.name,
+            entry_type: match entry.entry_type {
+                EntryType::Directory => "directory",
+                EntryType::File => "file",
+                EntryType::BrokenSymlink => "broken_symlink",
+            },
+            link: &entry.link,
+            size: entry.size.map(|size| size.as_u64()),
+            modified: entry
+                .last_modification_date
+                .and_then(|date| date.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+            is_symlink: entry.is_symlink,
+            mime_type: entry.mime_type.as_deref(),
+        }
+    }
+}
+
+/// Name of the optional file in a directory that describes its entries
+pub const METADATA_SIDECAR_FILE: &str = ".meta.json";
+
+/// Friendly title and description of an entry, read from the metadata sidecar
+///
+/// The sidecar is a JSON object mapping file names to their metadata, e.g.
+/// `{"report.pdf": {"title": "Annual report", "description": "All figures of 2024"}}`.
+#[derive(Deserialize, Clone, Default)]
+pub struct EntryMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Reads the metadata sidecar of `dir`
+///
+/// Missing or malformed sidecars are treated as empty, so that they don't break the listing.
+fn read_metadata_sidecar(dir: &Path) -> HashMap<String, EntryMetadata> {
+    let path = dir.join(METADATA_SIDECAR_FILE);
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return HashMap::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|err| {
+        log::warn!(
+            "Ignoring malformed metadata sidecar {}: {err}",
+            path.display()
+        );
+        HashMap::new()
+    })
 }
 
 /// One entry in the path to the listed directory
@@ -157,11 +329,182 @@ impl Breadcrumb {
     }
 }
 
+/// How many directory listings are cached at most
+const LISTING_CACHE_CAPACITY: usize = 256;
+
+/// The sorted entries of a directory along with its rendered readme
+type Listing = (Vec<Entry>, Option<(String, String)>);
+
+/// Request path and sorting a cached listing was read for
+type ListingCacheKey = (String, Option<SortingMethod>, Option<SortingOrder>);
+
+/// A directory listing as read at `created`
+struct CachedListing {
+    created: Instant,
+    dir_modified: Option<SystemTime>,
+    listing: Listing,
+}
+
+/// Recently read directory listings, reused for the configured time unless the directory changed
+pub struct ListingCache {
+    ttl: Option<Duration>,
+    listings: Mutex<HashMap<ListingCacheKey, CachedListing>>,
+}
+
+impl ListingCache {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            listings: Mutex::default(),
+        }
+    }
+
+    /// Returns the cached listing of `dir`, if it's neither expired nor outdated
+    fn get(&self, key: &ListingCacheKey, dir: &Path) -> Option<Listing> {
+        let ttl = self.ttl?;
+        let dir_modified = modification_date(dir);
+        self.listings
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|cached| cached.created.elapsed() < ttl && cached.dir_modified == dir_modified)
+            .map(|cached| cached.listing.clone())
+    }
+
+    /// Caches the listing of `dir`, making room by evicting old listings if needed
+    fn insert(&self, key: ListingCacheKey, dir: &Path, listing: Listing) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+
+        let mut listings = self.listings.lock().unwrap();
+        if listings.len() >= LISTING_CACHE_CAPACITY {
+            listings.retain(|_, cached| cached.created.elapsed() < ttl);
+        }
+        if listings.len() >= LISTING_CACHE_CAPACITY {
+            let oldest = listings
+                .iter()
+                .min_by_key(|(_, cached)| cached.created)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                listings.remove(&oldest);
+            }
+        }
+
+        listings.insert(
+            key,
+            CachedListing {
+                created: Instant::now(),
+                dir_modified: modification_date(dir),
+                listing,
+            },
+        );
+    }
+
+    /// Drops the cached listings of the request path `prefix` and everything below it, or all
+    /// cached listings if no prefix is given
+    pub fn invalidate(&self, prefix: Option<&str>) {
+        let mut listings = self.listings.lock().unwrap();
+        let Some(prefix) = prefix.map(|prefix| prefix.trim_end_matches('/')) else {
+            listings.clear();
+            return;
+        };
+
+        listings.retain(|(path, _, _), _| {
+            let path = percent_decode_str(path).decode_utf8_lossy();
+            let path = path.trim_end_matches('/');
+            !(path == prefix || path.starts_with(&format!("{prefix}/")))
+        });
+    }
+}
+
+/// Returns the last modification date of `path`, if available
+fn modification_date(path: &Path) -> Option<SystemTime> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Serves the single served file
+///
+/// Like the directory service, this answers range requests so that media players can seek.
 pub async fn file_handler(req: HttpRequest) -> actix_web::Result<actix_files::NamedFile> {
     let path = &req.app_data::<crate::MiniserveConfig>().unwrap().path;
     actix_files::NamedFile::open(path).map_err(Into::into)
 }
 
+/// Builds the full URI of the request, including scheme and host
+///
+/// The external base URL is used instead of the one of the request if it's configured.
+fn absolute_uri(req: &HttpRequest) -> Result<Uri, actix_web::error::HttpError> {
+    let conf = req.app_data::<crate::MiniserveConfig>().unwrap();
+    match &conf.external_base_url {
+        Some(base) => Uri::builder()
+            .scheme(base.scheme_str().unwrap_or("http"))
+            .authority(base.authority().map(|a| a.as_str()).unwrap_or_default())
+            .path_and_query(format!("{}{}", external_base_path(conf), req.uri()))
+            .build(),
+        None => Uri::builder()
+            .scheme(req.connection_info().scheme())
+            .authority(req.connection_info().host())
+            .path_and_query(req.uri().to_string())
+            .build(),
+    }
+}
+
+/// Path of the external base URL without a trailing slash, or an empty string if there's none
+pub fn external_base_path(conf: &crate::MiniserveConfig) -> &str {
+    conf.external_base_url
+        .as_ref()
+        .map(|base| base.path().trim_end_matches('/'))
+        .unwrap_or_default()
+}
+
+/// Shows a landing page linking to the single served file instead of serving it directly
+pub async fn single_file_landing(req: HttpRequest) -> actix_web::Result<HttpResponse> {
+    let conf = req.app_data::<crate::MiniserveConfig>().unwrap();
+    let file_name = conf
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let download_link = format!(
+        "{}/{}",
+        conf.route_prefix,
+        utf8_percent_encode(&file_name, COMPONENT)
+    );
+    let size = conf
+        .path
+        .metadata()
+        .ok()
+        .map(|metadata| ByteSize::b(metadata.len()));
+    let abs_uri = absolute_uri(&req)?;
+
+    Ok(HttpResponse::Ok().content_type(mime::TEXT_HTML_UTF_8).body(
+        renderer::single_file_landing(
+            &file_name,
+            &download_link,
+            size,
+            &abs_uri,
+            conf,
+            Language::for_request(&req, conf),
+        )
+        .into_string(),
+    ))
+}
+
+/// Serves the single served file when it's requested by its name from the landing page
+pub async fn single_file_download(
+    req: HttpRequest,
+    file_name: web::Path<String>,
+) -> actix_web::Result<actix_files::NamedFile> {
+    let conf = req.app_data::<crate::MiniserveConfig>().unwrap();
+    if conf.path.file_name() != Some(file_name.as_str().as_ref()) {
+        return Err(RuntimeError::RouteNotFoundError(req.path().to_string()).into());
+    }
+    file_handler(req).await
+}
+
 /// List a directory and renders a HTML file accordingly
 /// Adapted from https://docs.rs/actix-web/0.7.13/src/actix_web/fs.rs.html#564
 pub fn directory_listing(
@@ -183,17 +526,21 @@ pub fn directory_listing(
     let serve_path = req.path();
 
     let base = Path::new(serve_path);
+    // Links to entries have to include the path of the external base URL
+    let link_base = match conf.external_base_url {
+        Some(_) => Path::new(external_base_path(conf)).join(serve_path.trim_start_matches('/')),
+        None => base.to_path_buf(),
+    };
+    // With --relative-links, entries are linked relative to the listed directory instead
+    let entry_base = if conf.relative_links {
+        Path::new("")
+    } else {
+        link_base.as_path()
+    };
     let random_route_abs = format!("/{}", conf.route_prefix);
-    let abs_uri = {
-        let res = Uri::builder()
-            .scheme(req.connection_info().scheme())
-            .authority(req.connection_info().host())
-            .path_and_query(req.uri().to_string())
-            .build();
-        match res {
-            Ok(uri) => uri,
-            Err(err) => return Ok(ServiceResponse::from_err(err, req.clone())),
-        }
+    let abs_uri = match absolute_uri(req) {
+        Ok(uri) => uri,
+        Err(err) => return Ok(ServiceResponse::from_err(err, req.clone())),
     };
     let is_root = base.parent().is_none() || Path::new(&req.path()) == Path::new(&random_route_abs);
 
@@ -204,17 +551,39 @@ pub fn directory_listing(
     .display()
     .to_string();
 
+    if let Some(max_depth) = conf.max_depth {
+        let depth = Path::new(&encoded_dir)
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .count();
+        if depth > max_depth {
+            return Ok(ServiceResponse::from_err(
+                RuntimeError::MaxDepthError(max_depth),
+                req.clone(),
+            ));
+        }
+    }
+
     let breadcrumbs = {
-        let title = conf
-            .title
-            .clone()
-            .unwrap_or_else(|| req.connection_info().host().into());
+        let title = conf.title.clone().unwrap_or_else(|| {
+            abs_uri
+                .authority()
+                .map(|a| a.to_string())
+                .unwrap_or_else(|| req.connection_info().host().into())
+        });
 
         let decoded = percent_decode_str(&encoded_dir).decode_utf8_lossy();
 
         let mut res: Vec<Breadcrumb> = Vec::new();
-        let mut link_accumulator = format!("{}/", &conf.route_prefix);
+        let mut link_accumulator = format!("{}{}/", external_base_path(conf), &conf.route_prefix);
         let mut components = Path::new(&*decoded).components().peekable();
+        let mut levels_up = Path::new(&*decoded)
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .count();
+
+        // Names of the single-child directories collapsed into the next breadcrumb
+        let mut collapsed_names = String::new();
 
         while let Some(c) = components.next() {
             let name;
@@ -225,26 +594,707 @@ pub fn directory_listing(
                 }
                 Component::Normal(s) => {
                     name = s.to_string_lossy().to_string();
+                    levels_up -= 1;
                     link_accumulator
                         .push_str(&(utf8_percent_encode(&name, COMPONENT).to_string() + "/"));
+
+                    // This directory only contains the next one, so both share a breadcrumb
+                    let fs_path = dir.path.ancestors().nth(levels_up);
+                    if conf.collapse_single_dirs
+                        && levels_up > 0
+                        && fs_path.is_some_and(|path| single_child_dir(path, conf).is_some())
+                    {
+                        collapsed_names.push_str(&name);
+                        collapsed_names.push('/');
+                        continue;
+                    }
                 }
                 _ => name = "".to_string(),
             };
+            let name = std::mem::take(&mut collapsed_names) + &name;
 
             res.push(Breadcrumb::new(
                 name,
-                if components.peek().is_some() {
-                    link_accumulator.clone()
-                } else {
+                if components.peek().is_none() {
                     ".".to_string()
+                } else if conf.relative_links {
+                    "../".repeat(levels_up)
+                } else {
+                    link_accumulator.clone()
                 },
             ));
         }
         res
     };
 
-    let query_params = extract_query_parameters(req);
-    let mut entries: Vec<Entry> = Vec::new();
+    let mut query_params = extract_query_parameters(req);
+
+    // With --upload-requires-auth, the listing doesn't ask for credentials unless asked to log in
+    if query_params.login == Some(true) && conf.upload_requires_auth {
+        if current_user.is_none() {
+            return Ok(ServiceResponse::from_err(
+                RuntimeError::InvalidHttpCredentials,
+                req.clone(),
+            ));
+        }
+        return Ok(ServiceResponse::new(
+            req.clone(),
+            HttpResponse::SeeOther()
+                .insert_header((header::LOCATION, link_base.display().to_string()))
+                .finish(),
+        ));
+    }
+
+    // Remember the sorting chosen by the user, or fall back to the one remembered before
+    let sorting_cookies = match query_params.sort {
+        Some(sort) => sorting_cookies(sort, query_params.order, &conf.route_prefix),
+        None => {
+            (query_params.sort, query_params.order) = sorting_from_cookies(req);
+            Vec::new()
+        }
+    };
+
+    // Enabled trees are served by `directory_tree`
+    if query_params.tree == Some(true) && !conf.tree_api {
+        return Ok(ServiceResponse::new(
+            req.clone(),
+            HttpResponse::Forbidden()
+                .content_type(mime::TEXT_PLAIN_UTF_8)
+                .body("Tree API is disabled."),
+        ));
+    }
+
+    let cache_key = (
+        serve_path.to_string(),
+        query_params.sort,
+        query_params.order,
+    );
+    let listing_cache = req.app_data::<web::Data<ListingCache>>();
+    let ((entries, readme), complete) =
+        match listing_cache.and_then(|cache| cache.get(&cache_key, &dir.path)) {
+            Some(listing) => (listing, true),
+            None => {
+                let (listing, complete) = read_listing(dir, entry_base, conf, &query_params)?;
+                // Partial listings are not cached so that the next request tries again
+                if let Some(cache) = listing_cache.filter(|_| complete) {
+                    cache.insert(cache_key, &dir.path, listing.clone());
+                }
+                (listing, complete)
+            }
+        };
+
+    if query_params.format == Some(ListingFormat::Json) {
+        if !conf.emit_json_index {
+            return Ok(ServiceResponse::new(
+                req.clone(),
+                HttpResponse::Forbidden()
+                    .content_type(mime::TEXT_PLAIN_UTF_8)
+                    .body("JSON index is disabled."),
+            ));
+        }
+        let index: Vec<JsonIndexEntry> = entries.iter().map(JsonIndexEntry::from).collect();
+        return Ok(ServiceResponse::new(
+            req.clone(),
+            HttpResponse::Ok().json(serde_json::json!({
+                "complete": complete,
+                "entries": index,
+            })),
+        ));
+    }
+
+    // Flat downloads only contain the files of the directory itself
+    let files_only = query_params.download_flat.is_some();
+    if let Some(archive_method) = query_params.download.or(query_params.download_flat) {
+        let enabled = if files_only {
+            conf.flat_zip_enabled && matches!(archive_method, ArchiveMethod::Zip)
+        } else {
+            archive_method.is_enabled(conf.tar_enabled, conf.tar_gz_enabled, conf.zip_enabled)
+                && !(is_root && conf.no_root_archive)
+        };
+        if !enabled {
+            return Ok(ServiceResponse::new(
+                req.clone(),
+                HttpResponse::Forbidden()
+                    .content_type(mime::TEXT_PLAIN_UTF_8)
+                    .body("Archive creation is disabled."),
+            ));
+        }
+        let file_name = format!(
+            "{}.{}",
+            dir.path.file_name().unwrap().to_str().unwrap(),
+            archive_method.extension()
+        );
+        let options = ArchiveOptions {
+            skip_symlinks: conf.no_symlinks,
+            manifest: conf.archive_manifest,
+            skip_empty_dirs: conf.archive_skip_empty_dirs,
+            flat: conf.archive_flat || files_only,
+            files_only,
+            memory_limit: conf.archive_memory_limit,
+            filters: conf.filters.clone(),
+        };
+
+        // Serve previously created archives as regular files, so that downloads can be resumed
+        let cached_archive = conf.archive_cache_dir.as_ref().and_then(|cache_dir| {
+            archive_method
+                .cached_archive_name(&dir.path, &options)
+                .map(|name| cache_dir.join(name))
+        });
+        if let Some(cached_archive) = cached_archive.as_ref().filter(|path| path.is_file()) {
+            let file = actix_files::NamedFile::open(cached_archive)?
+                .set_content_type(
+                    archive_method
+                        .content_type()
+                        .parse()
+                        .unwrap_or(mime::APPLICATION_OCTET_STREAM),
+                )
+                .set_content_disposition(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(file_name)],
+                });
+            let file = if archive_method.is_compressible() {
+                file
+            } else {
+                file.set_content_encoding(ContentEncoding::Identity)
+            };
+            return Ok(ServiceResponse::new(req.clone(), file.into_response(req)));
+        }
+
+        let mut resp = HttpResponse::Ok();
+        resp.content_type(archive_method.content_type())
+            .append_header(("Content-Transfer-Encoding", "binary"))
+            .append_header((
+                "Content-Disposition",
+                format!("attachment; filename={file_name:?}"),
+            ));
+        if !archive_method.is_compressible() {
+            // Keeps the compression middleware from touching the body
+            resp.insert_header((header::CONTENT_ENCODING, ContentEncoding::Identity.as_str()));
+        }
+
+        // Don't bother creating the archive if the client only wants to know the headers
+        if req.method() == Method::HEAD {
+            return Ok(ServiceResponse::new(
+                req.clone(),
+                resp.body(actix_web::body::None::new()),
+            ));
+        }
+
+        log::info!(
+            "Creating an archive ({extension}) of {path}...",
+            extension = archive_method.extension(),
+            path = &dir.path.display().to_string()
+        );
+
+        // We will create the archive in a separate thread, and stream the content using a pipe.
+        // The pipe is made of a futures channel, and an adapter to implement the `Write` trait.
+        // Include 10 messages of buffer for erratic connection speeds.
+        let (tx, rx) = futures::channel::mpsc::channel::<io::Result<actix_web::web::Bytes>>(10);
+        let pipe = crate::pipe::Pipe::new(tx);
+
+        // Start the actual archive creation in a separate thread.
+        let dir = dir.path.to_path_buf();
+        std::thread::spawn(move || {
+            let created = match cached_archive {
+                Some(cache_file) => {
+                    archive_method.create_cached_archive(&dir, &options, &cache_file, pipe)
+                }
+                None => archive_method.create_archive(dir, &options, pipe),
+            };
+            if let Err(err) = created {
+                log::error!("Error during archive creation: {:?}", err);
+            }
+        });
+
+        Ok(ServiceResponse::new(
+            req.clone(),
+            resp.body(actix_web::body::BodyStream::new(rx)),
+        ))
+    } else {
+        let readme = match (readme, &conf.auto_readme) {
+            (None, Some(template)) => Some(auto_readme(template, &encoded_dir, &entries, conf)),
+            (readme, _) => readme,
+        };
+        let description = conf
+            .descriptions
+            .then(|| read_description(&dir.path, conf.no_symlinks))
+            .flatten();
+        // Partial listings aren't written, so that the file doesn't keep them around
+        if let Some(write_index) = conf.write_index.as_ref().filter(|_| complete) {
+            let index_page = render_written_index(
+                entries.clone(),
+                readme.clone(),
+                description.clone(),
+                &abs_uri,
+                is_root,
+                &breadcrumbs,
+                &encoded_dir,
+                conf,
+            );
+            write_index_file(dir.path.join(write_index), index_page);
+        }
+        let (entries, next_page) = lazy_listing_batch(entries, conf.lazy_listing, &query_params);
+        let page = renderer::page(
+            entries,
+            next_page,
+            readme,
+            description,
+            complete,
+            &abs_uri,
+            is_root,
+            query_params,
+            &breadcrumbs,
+            &encoded_dir,
+            conf,
+            current_user,
+            Language::for_request(req, conf),
+        )
+        .into_string();
+        let mut resp = listing_response(req, conf, page);
+        if conf.emit_json_index {
+            let link = format!(
+                "<{}?format=json>; rel=\"alternate\"; type=\"application/json\"",
+                link_base.display()
+            );
+            if let Ok(link) = HeaderValue::from_str(&link) {
+                resp.headers_mut().insert(header::LINK, link);
+            }
+        }
+        for cookie in sorting_cookies {
+            resp.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+        Ok(ServiceResponse::new(req.clone(), resp))
+    }
+}
+
+/// Marks the files written by --write-index, so that other files of the same name are neither
+/// replaced nor hidden
+const WRITTEN_INDEX_MARKER: &str = "<!-- Written by miniserve -->\n";
+
+/// Renders the listing written by --write-index
+///
+/// As the file is served to everyone, it's rendered for an anonymous visitor in the default
+/// sorting and language, without lazy loading and without the CSRF token of the upload forms.
+#[allow(clippy::too_many_arguments)]
+fn render_written_index(
+    mut entries: Vec<Entry>,
+    readme: Option<(String, String)>,
+    description: Option<Description>,
+    abs_uri: &Uri,
+    is_root: bool,
+    breadcrumbs: &[Breadcrumb],
+    encoded_dir: &str,
+    conf: &MiniserveConfig,
+) -> String {
+    sort_entries(&mut entries, None, None, conf);
+    let index_conf = MiniserveConfig {
+        upload_csrf_secret: None,
+        ..conf.clone()
+    };
+    let page = renderer::page(
+        entries,
+        None,
+        readme,
+        description,
+        true,
+        abs_uri,
+        is_root,
+        ListingQueryParameters::default(),
+        breadcrumbs,
+        encoded_dir,
+        &index_conf,
+        None,
+        conf.lang,
+    );
+    format!("{WRITTEN_INDEX_MARKER}{}", page.into_string())
+}
+
+/// Returns whether the file at `path` was written by --write-index
+fn is_written_index(path: &Path) -> bool {
+    let mut start = [0; WRITTEN_INDEX_MARKER.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok_and(|_| start == WRITTEN_INDEX_MARKER.as_bytes())
+}
+
+/// Writes the listing of --write-index to `path` in the background, unless a file that
+/// miniserve didn't write exists there
+fn write_index_file(path: PathBuf, page: String) {
+    actix_web::rt::spawn(async move {
+        let written = web::block(move || {
+            if path.symlink_metadata().is_ok() && !is_written_index(&path) {
+                log::warn!(
+                    "Not writing the listing to {}, as the file wasn't written by miniserve",
+                    path.display()
+                );
+                return Ok(());
+            }
+            std::fs::write(&path, page).map_err(|err| (path, err))
+        })
+        .await;
+        match written {
+            Ok(Err((path, err))) => {
+                log::error!("Couldn't write the listing to {}: {err}", path.display())
+            }
+            Err(err) => log::error!("Couldn't write the listing: {err}"),
+            Ok(Ok(())) => (),
+        }
+    });
+}
+
+/// Name of the cookie remembering the sorting method chosen by the user
+const SORT_COOKIE: &str = "miniserve_sort";
+
+/// Name of the cookie remembering the sorting order chosen by the user
+const ORDER_COOKIE: &str = "miniserve_order";
+
+/// How long the sorting chosen by the user is remembered
+const SORTING_COOKIE_MAX_AGE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Builds the cookies remembering the sorting chosen by the user for all listings
+fn sorting_cookies(
+    sort: SortingMethod,
+    order: Option<SortingOrder>,
+    route_prefix: &str,
+) -> Vec<HeaderValue> {
+    let cookie = |name: &str, value: &dyn std::fmt::Display| {
+        format!(
+            "{name}={value}; Path={route_prefix}/; Max-Age={}; SameSite=Lax",
+            SORTING_COOKIE_MAX_AGE.as_secs()
+        )
+    };
+
+    [
+        Some(cookie(SORT_COOKIE, &sort)),
+        order.map(|order| cookie(ORDER_COOKIE, &order)),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|cookie| HeaderValue::from_str(&cookie).ok())
+    .collect()
+}
+
+/// Reads the sorting remembered by the cookies of the request
+fn sorting_from_cookies(req: &HttpRequest) -> (Option<SortingMethod>, Option<SortingOrder>) {
+    let mut sorting = (None, None);
+    let cookies = req
+        .headers()
+        .get_all(header::COOKIE)
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(';'));
+    for cookie in cookies {
+        match cookie.trim().split_once('=') {
+            Some((SORT_COOKIE, value)) => sorting.0 = value.parse().ok(),
+            Some((ORDER_COOKIE, value)) => sorting.1 = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    // An order on its own is meaningless, as the links in the listing only carry both
+    match sorting {
+        (None, _) => (None, None),
+        sorting => sorting,
+    }
+}
+
+/// Contents of the description file of a directory
+#[derive(Clone)]
+pub enum Description {
+    /// Read from `.description.txt`
+    Text(String),
+
+    /// Read from `.description.html`
+    Html(String),
+}
+
+/// Reads the description file of a directory, preferring the HTML variant if both exist
+fn read_description(dir: &Path, no_symlinks: bool) -> Option<Description> {
+    let read = |file_name: &str| {
+        let path = dir.join(file_name);
+        let is_symlink = path.symlink_metadata().ok()?.is_symlink();
+        if is_symlink && no_symlinks {
+            return None;
+        }
+        std::fs::read_to_string(path).ok()
+    };
+
+    read(".description.html")
+        .map(Description::Html)
+        .or_else(|| read(".description.txt").map(Description::Text))
+}
+
+/// Slices the batch requested by the `page` query parameter out of the entries of a listing
+///
+/// Also returns the number of the following batch, if there is one. Raw listings requested
+/// without a page are left complete, so that they can still be mirrored.
+fn lazy_listing_batch(
+    entries: Vec<Entry>,
+    batch_size: Option<usize>,
+    query_params: &ListingQueryParameters,
+) -> (Vec<Entry>, Option<usize>) {
+    let Some(batch_size) = batch_size else {
+        return (entries, None);
+    };
+    if query_params.raw == Some(true) && query_params.page.is_none() {
+        return (entries, None);
+    }
+
+    let page = query_params.page.unwrap_or_default();
+    let start = page.saturating_mul(batch_size);
+    let next_page = (entries.len() > start.saturating_add(batch_size)).then_some(page + 1);
+    let batch = entries.into_iter().skip(start).take(batch_size).collect();
+
+    (batch, next_page)
+}
+
+/// Progress of reading a directory on a separate thread
+enum ReadEvent {
+    Entry(Entry),
+    Done(io::Result<Option<(String, String)>>),
+}
+
+/// Builds the response for a rendered listing, gzipping it if enabled and accepted by the client
+fn listing_response(req: &HttpRequest, conf: &MiniserveConfig, html: String) -> HttpResponse {
+    let mut resp = HttpResponse::Ok();
+    resp.content_type(mime::TEXT_HTML_UTF_8);
+
+    // With --compress-response, the compression middleware already takes care of this
+    if !conf.compress_listings || conf.compress_response {
+        return resp.body(html);
+    }
+
+    resp.insert_header((header::VARY, "Accept-Encoding"));
+    if !accepts_gzip(req) {
+        return resp.body(html);
+    }
+
+    let compressed = Encoder::new(Vec::new()).and_then(|mut encoder| {
+        encoder.write_all(html.as_bytes())?;
+        encoder.finish().into_result()
+    });
+    match compressed {
+        Ok(body) => resp
+            .insert_header((header::CONTENT_ENCODING, "gzip"))
+            .body(body),
+        Err(err) => {
+            log::error!("Failed to compress listing: {err}");
+            resp.body(html)
+        }
+    }
+}
+
+/// Returns whether the client accepts gzip-encoded responses
+fn accepts_gzip(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.split(',').any(|item| {
+                let mut parts = item.split(';');
+                let accepted = parts
+                    .next()
+                    .is_some_and(|coding| matches!(coding.trim(), "gzip" | "*"));
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                accepted && quality > 0.0
+            })
+        })
+}
+
+/// Renders the README generated from the `--auto-readme` template for a directory
+fn auto_readme(
+    template: &str,
+    encoded_dir: &str,
+    entries: &[Entry],
+    conf: &crate::MiniserveConfig,
+) -> (String, String) {
+    let files = entries.iter().filter(|entry| entry.is_file());
+    let total_size = files
+        .clone()
+        .filter_map(|entry| entry.size)
+        .map(|size| size.as_u64());
+    let readme = template
+        .replace(
+            "{dir}",
+            &percent_decode_str(encoded_dir).decode_utf8_lossy(),
+        )
+        .replace("{file_count}", &files.count().to_string())
+        .replace(
+            "{total_size}",
+            &renderer::format_size(ByteSize::b(total_size.sum()), conf.si_units),
+        );
+    (
+        "README".to_string(),
+        markdown_to_html(&readme, &ComrakOptions::default()),
+    )
+}
+
+/// Reads the entries of a directory sorted as requested, along with its rendered readme
+///
+/// Also returns whether the listing is complete, which it isn't if reading the directory took
+/// longer than the configured timeout.
+fn read_listing(
+    dir: &actix_files::Directory,
+    base: &Path,
+    conf: &MiniserveConfig,
+    query_params: &ListingQueryParameters,
+) -> io::Result<(Listing, bool)> {
+    let ((mut entries, readme), complete) = match conf.io_timeout {
+        Some(timeout) => read_entries_with_timeout(dir, base, conf, timeout)?,
+        None => {
+            let mut entries = Vec::new();
+            let readme = read_entries(dir, base, conf, |entry| {
+                entries.push(entry);
+                true
+            })?;
+            ((entries, readme), true)
+        }
+    };
+
+    if conf.metadata_sidecar {
+        let mut sidecar = read_metadata_sidecar(&dir.path);
+        for entry in &mut entries {
+            entry.metadata = sidecar.remove(&entry.name);
+        }
+    }
+
+    sort_entries(&mut entries, query_params.sort, query_params.order, conf);
+
+    Ok(((entries, readme), complete))
+}
+
+/// Sorts the entries of a listing by the given method and order, or the default ones
+fn sort_entries(
+    entries: &mut [Entry],
+    sort: Option<SortingMethod>,
+    order: Option<SortingOrder>,
+    conf: &MiniserveConfig,
+) {
+    match sort.unwrap_or(conf.default_sorting_method) {
+        SortingMethod::Name => entries.sort_by(|e1, e2| {
+            alphanumeric_sort::compare_str(e1.name.to_lowercase(), e2.name.to_lowercase())
+        }),
+        SortingMethod::Title => entries.sort_by(|e1, e2| {
+            alphanumeric_sort::compare_str(e1.title().to_lowercase(), e2.title().to_lowercase())
+        }),
+        SortingMethod::Size => entries.sort_by(|e1, e2| {
+            // If we can't get the size of the entry (directory for instance)
+            // let's consider it's 0b
+            e2.size
+                .unwrap_or_else(|| ByteSize::b(0))
+                .cmp(&e1.size.unwrap_or_else(|| ByteSize::b(0)))
+        }),
+        SortingMethod::Date => entries.sort_by(|e1, e2| {
+            // If, for some reason, we can't get the last modification date of an entry
+            // let's consider it was modified on UNIX_EPOCH (01/01/19270 00:00:00)
+            e2.last_modification_date
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+                .cmp(&e1.last_modification_date.unwrap_or(SystemTime::UNIX_EPOCH))
+        }),
+    };
+
+    if let SortingOrder::Asc = order.unwrap_or(conf.default_sorting_order) {
+        entries.reverse()
+    }
+
+    // List directories first
+    if conf.dirs_first {
+        entries.sort_by_key(|e| !e.is_dir());
+    }
+
+    // Group entries by their type, keeping the order within each group
+    if let Some(GroupBy::Type) = conf.group_by {
+        entries.sort_by_key(|e| match (e.is_dir(), e.is_symlink) {
+            (true, false) => 0,
+            (true, true) => 1,
+            (false, false) => 2,
+            (false, true) => 3,
+        });
+    }
+
+    // Move pinned entries to the top, keeping the order among them
+    if !conf.pin_first.is_empty() {
+        entries.sort_by_key(|e| !conf.pin_first.contains(&e.name));
+    }
+}
+
+/// Reads the entries of a directory on the blocking thread pool, giving up after `timeout`
+///
+/// Slow filesystems such as network mounts can block reads indefinitely, in which case the
+/// entries read so far are returned and the read is left to finish in the pool, which bounds
+/// how many threads such reads can tie up. As the listing renderer can't await, the entries are
+/// streamed back through a channel, which is also where the deadline is enforced.
+fn read_entries_with_timeout(
+    dir: &actix_files::Directory,
+    base: &Path,
+    conf: &MiniserveConfig,
+    timeout: Duration,
+) -> io::Result<(Listing, bool)> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let thread_dir = actix_files::Directory::new(dir.base.clone(), dir.path.clone());
+    let thread_base = base.to_path_buf();
+    let thread_conf = conf.clone();
+    actix_web::rt::task::spawn_blocking(move || {
+        let readme = read_entries(&thread_dir, &thread_base, &thread_conf, |entry| {
+            tx.send(ReadEvent::Entry(entry)).is_ok()
+        });
+        let _ = tx.send(ReadEvent::Done(readme));
+    });
+
+    let deadline = Instant::now() + timeout;
+    let mut entries = Vec::new();
+    loop {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(ReadEvent::Entry(entry)) => entries.push(entry),
+            Ok(ReadEvent::Done(readme)) => return Ok(((entries, readme?), true)),
+            Err(_) => {
+                log::warn!(
+                    "Reading {} took longer than {:?}, returning a partial listing",
+                    dir.path.display(),
+                    timeout
+                );
+                return Ok(((entries, None), false));
+            }
+        }
+    }
+}
+
+/// Returns the name of the only entry of `dir` if it's a directory, ignoring hidden entries
+/// unless they're shown
+fn single_child_dir(dir: &Path, conf: &MiniserveConfig) -> Option<String> {
+    let mut entries =
+        dir.read_dir().ok()?.filter_map(Result::ok).filter(|entry| {
+            conf.show_hidden || !entry.file_name().to_string_lossy().starts_with('.')
+        });
+    let child = entries.next()?;
+    if entries.next().is_some() || !child.file_type().ok()?.is_dir() {
+        return None;
+    }
+    Some(child.file_name().to_string_lossy().to_string())
+}
+
+/// Returns the names of the chain of directories below `dir` that each only contain the next one
+fn single_child_dirs(dir: &Path, conf: &MiniserveConfig) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut dir = dir.to_path_buf();
+    while let Some(name) = single_child_dir(&dir, conf) {
+        dir.push(&name);
+        names.push(name);
+    }
+    names
+}
+
+/// Reads the entries of a directory, passing each one to `on_entry` until it returns `false`
+///
+/// Returns the rendered readme of the directory, if any.
+fn read_entries(
+    dir: &actix_files::Directory,
+    base: &Path,
+    conf: &MiniserveConfig,
+    mut on_entry: impl FnMut(Entry) -> bool,
+) -> io::Result<Option<(String, String)>> {
     let mut readme: Option<(String, String)> = None;
     let readme_rx: Regex = Regex::new("^readme([.](md|txt))?$").unwrap();
 
@@ -274,31 +1324,72 @@ pub fn directory_listing(
                 if conf.no_symlinks && is_symlink {
                     continue;
                 }
-                let last_modification_date = match metadata.modified() {
-                    Ok(date) => Some(date),
-                    Err(_) => None,
-                };
+                let last_modification_date = metadata.modified().ok();
 
                 if metadata.is_dir() {
-                    entries.push(Entry::new(
-                        file_name,
-                        EntryType::Directory,
-                        file_url,
-                        None,
-                        last_modification_date,
-                        symlink_dest,
-                    ));
+                    // Symlinks aren't followed, as they could lead back up the chain
+                    let collapsed = if conf.collapse_single_dirs && !is_symlink {
+                        single_child_dirs(&entry.path(), conf)
+                    } else {
+                        Vec::new()
+                    };
+                    if !on_entry(
+                        Entry::new(
+                            file_name,
+                            EntryType::Directory,
+                            file_url,
+                            None,
+                            last_modification_date,
+                            symlink_dest,
+                            None,
+                        )
+                        .symlink(is_symlink)
+                        .collapsed(collapsed),
+                    ) {
+                        return Ok(None);
+                    }
                 } else if metadata.is_file() {
-                    entries.push(Entry::new(
-                        file_name.clone(),
-                        EntryType::File,
-                        file_url,
-                        Some(ByteSize::b(metadata.len())),
-                        last_modification_date,
-                        symlink_dest,
-                    ));
-                    if conf.readme && readme_rx.is_match(&file_name.to_lowercase()) {
-                        let ext = file_name.split('.').last().unwrap().to_lowercase();
+                    // Don't list the file written by --write-index
+                    if conf.write_index.as_ref() == Some(&file_name)
+                        && is_written_index(&entry.path())
+                    {
+                        continue;
+                    }
+                    if !file_utils::matches_filters(&file_name, &conf.filters) {
+                        continue;
+                    }
+                    if conf
+                        .hide_files_older_than
+                        .is_some_and(|max_age| is_older_than(&metadata, max_age))
+                    {
+                        continue;
+                    }
+                    // The previews are chosen based on the content type
+                    let mime_type = (conf.show_mimetype || conf.preview).then(|| {
+                        mime_guess::from_path(&file_name)
+                            .first_or_octet_stream()
+                            .essence_str()
+                            .to_string()
+                    });
+                    if !on_entry(
+                        Entry::new(
+                            file_name.clone(),
+                            EntryType::File,
+                            file_url,
+                            Some(ByteSize::b(metadata.len())),
+                            last_modification_date,
+                            symlink_dest,
+                            mime_type,
+                        )
+                        .symlink(is_symlink)
+                        .xattrs(file_utils::xattrs(&entry.path(), &conf.show_xattrs)),
+                    ) {
+                        return Ok(None);
+                    }
+                    if (conf.readme || conf.auto_readme.is_some())
+                        && readme_rx.is_match(&file_name.to_lowercase())
+                    {
+                        let ext = file_name.split('.').next_back().unwrap().to_lowercase();
                         readme = Some((
                             file_name.to_string(),
                             if ext == "md" {
@@ -312,107 +1403,69 @@ pub fn directory_listing(
                         ));
                     }
                 }
+            } else if is_symlink && conf.show_broken_symlinks && !conf.no_symlinks {
+                let symlink_dest = std::fs::read_link(entry.path())
+                    .ok()
+                    .map(|path| path.to_string_lossy().into_owned());
+                if !on_entry(
+                    Entry::new(
+                        file_name,
+                        EntryType::BrokenSymlink,
+                        file_url,
+                        None,
+                        None,
+                        symlink_dest,
+                        None,
+                    )
+                    .symlink(true),
+                ) {
+                    return Ok(None);
+                }
             } else {
                 continue;
             }
         }
     }
 
-    match query_params.sort.unwrap_or(conf.default_sorting_method) {
-        SortingMethod::Name => entries.sort_by(|e1, e2| {
-            alphanumeric_sort::compare_str(e1.name.to_lowercase(), e2.name.to_lowercase())
-        }),
-        SortingMethod::Size => entries.sort_by(|e1, e2| {
-            // If we can't get the size of the entry (directory for instance)
-            // let's consider it's 0b
-            e2.size
-                .unwrap_or_else(|| ByteSize::b(0))
-                .cmp(&e1.size.unwrap_or_else(|| ByteSize::b(0)))
-        }),
-        SortingMethod::Date => entries.sort_by(|e1, e2| {
-            // If, for some reason, we can't get the last modification date of an entry
-            // let's consider it was modified on UNIX_EPOCH (01/01/19270 00:00:00)
-            e2.last_modification_date
-                .unwrap_or(SystemTime::UNIX_EPOCH)
-                .cmp(&e1.last_modification_date.unwrap_or(SystemTime::UNIX_EPOCH))
-        }),
-    };
+    Ok(readme)
+}
 
-    if let SortingOrder::Asc = query_params.order.unwrap_or(conf.default_sorting_order) {
-        entries.reverse()
-    }
+/// Returns whether the query string asks for the tree below a directory
+pub fn is_tree_query(query: &str) -> bool {
+    Query::<ListingQueryParameters>::from_query(query).is_ok_and(|query| query.tree == Some(true))
+}
 
-    // List directories first
-    if conf.dirs_first {
-        entries.sort_by_key(|e| !e.is_dir());
-    }
+/// Serves the tree below the directory requested with `?tree=true` as JSON
+///
+/// The tree is read on a blocking thread, as it can take a while for large directories.
+pub async fn directory_tree(req: HttpRequest) -> Result<HttpResponse, RuntimeError> {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    let dir = crate::listed_dir_path(req.path(), conf)
+        .ok_or_else(|| RuntimeError::RouteNotFoundError(req.path().to_string()))?;
 
-    if let Some(archive_method) = query_params.download {
-        if !archive_method.is_enabled(conf.tar_enabled, conf.tar_gz_enabled, conf.zip_enabled) {
-            return Ok(ServiceResponse::new(
-                req.clone(),
-                HttpResponse::Forbidden()
-                    .content_type(mime::TEXT_PLAIN_UTF_8)
-                    .body("Archive creation is disabled."),
-            ));
+    if let Some(max_depth) = conf.max_depth {
+        let depth = dir.strip_prefix(&conf.path).map_or(0, |dir| {
+            dir.components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .count()
+        });
+        if depth > max_depth {
+            return Err(RuntimeError::MaxDepthError(max_depth));
         }
-        log::info!(
-            "Creating an archive ({extension}) of {path}...",
-            extension = archive_method.extension(),
-            path = &dir.path.display().to_string()
-        );
-
-        let file_name = format!(
-            "{}.{}",
-            dir.path.file_name().unwrap().to_str().unwrap(),
-            archive_method.extension()
-        );
-
-        // We will create the archive in a separate thread, and stream the content using a pipe.
-        // The pipe is made of a futures channel, and an adapter to implement the `Write` trait.
-        // Include 10 messages of buffer for erratic connection speeds.
-        let (tx, rx) = futures::channel::mpsc::channel::<io::Result<actix_web::web::Bytes>>(10);
-        let pipe = crate::pipe::Pipe::new(tx);
+    }
 
-        // Start the actual archive creation in a separate thread.
-        let dir = dir.path.to_path_buf();
-        let skip_symlinks = conf.no_symlinks;
-        std::thread::spawn(move || {
-            if let Err(err) = archive_method.create_archive(dir, skip_symlinks, pipe) {
-                log::error!("Error during archive creation: {:?}", err);
-            }
-        });
+    let (show_hidden, no_symlinks, filters) =
+        (conf.show_hidden, conf.no_symlinks, conf.filters.clone());
+    let tree = web::block(move || DirectoryTree::read(&dir, show_hidden, no_symlinks, &filters))
+        .await
+        .map_err(|e| {
+            RuntimeError::IoError(
+                "Failed to read the directory tree".to_string(),
+                io::Error::other(e),
+            )
+        })?;
 
-        Ok(ServiceResponse::new(
-            req.clone(),
-            HttpResponse::Ok()
-                .content_type(archive_method.content_type())
-                .append_header(("Content-Transfer-Encoding", "binary"))
-                .append_header((
-                    "Content-Disposition",
-                    format!("attachment; filename={file_name:?}"),
-                ))
-                .body(actix_web::body::BodyStream::new(rx)),
-        ))
-    } else {
-        Ok(ServiceResponse::new(
-            req.clone(),
-            HttpResponse::Ok().content_type(mime::TEXT_HTML_UTF_8).body(
-                renderer::page(
-                    entries,
-                    readme,
-                    &abs_uri,
-                    is_root,
-                    query_params,
-                    &breadcrumbs,
-                    &encoded_dir,
-                    conf,
-                    current_user,
-                )
-                .into_string(),
-            ),
-        ))
-    }
+    Ok(HttpResponse::Ok().json(tree))
 }
 
 pub fn extract_query_parameters(req: &HttpRequest) -> ListingQueryParameters {
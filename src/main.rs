@@ -1,40 +1,56 @@
 use std::io::{self, IsTerminal, Write};
 use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use actix_files::NamedFile;
 use actix_web::{
-    dev::{fn_service, ServiceRequest, ServiceResponse},
-    http::header::ContentType,
-    middleware, web, App, HttpRequest, HttpResponse, Responder,
+    body::BoxBody,
+    dev::{fn_service, Payload, Service, ServiceRequest, ServiceResponse},
+    error::PayloadError,
+    guard,
+    http::{
+        header::{
+            self, ContentDisposition, ContentType, DispositionParam, DispositionType, HeaderValue,
+        },
+        Method, StatusCode,
+    },
+    middleware, web, App, HttpMessage, HttpRequest, HttpResponse, Responder,
 };
 use actix_web_httpauth::middleware::HttpAuthentication;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{crate_version, CommandFactory, Parser};
 use colored::*;
 use fast_qr::QRBuilder;
+use futures::{future::Either, prelude::*};
 use log::{error, warn};
 
+mod api;
 mod archive;
 mod args;
+mod audit;
 mod auth;
 mod config;
 mod consts;
 mod errors;
 mod file_op;
 mod file_utils;
+mod i18n;
 mod listing;
 mod pipe;
 mod renderer;
+mod sitemap;
+mod tree;
+mod tus;
 
 use crate::config::MiniserveConfig;
 use crate::errors::{RuntimeError, StartupError};
 
-static STYLESHEET: &str = grass::include!("data/style.scss");
-
 fn main() -> Result<()> {
-    let args = args::CliArgs::parse();
+    let mut args = args::CliArgs::parse();
 
     if let Some(shell) = args.print_completions {
         let mut clap_app = args::CliArgs::command();
@@ -50,6 +66,15 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    // The directory holding stdin is removed once it's dropped after the server stopped
+    let _stdin_dir = if args.from_stdin {
+        let (dir, path) = read_stdin_to_file(&args.download_name)?;
+        args.path = Some(path);
+        Some(dir)
+    } else {
+        None
+    };
+
     let miniserve_config = MiniserveConfig::try_from_args(args)?;
 
     run(miniserve_config).inspect_err(|e| {
@@ -59,19 +84,34 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Buffers all of stdin into a file named `file_name` inside a new temporary directory
+fn read_stdin_to_file(file_name: &str) -> Result<(tempfile::TempDir, PathBuf)> {
+    let dir = tempfile::tempdir().context("Couldn't create a temporary directory for stdin")?;
+    let path = dir.path().join(file_name);
+    let mut file = std::fs::File::create(&path)
+        .with_context(|| format!("Couldn't create {path:?} to hold stdin"))?;
+    io::copy(&mut io::stdin().lock(), &mut file).context("Couldn't read stdin")?;
+    Ok((dir, path))
+}
+
 #[actix_web::main(miniserve)]
 async fn run(miniserve_config: MiniserveConfig) -> Result<(), StartupError> {
-    let log_level = if miniserve_config.verbose {
+    let log_level = if miniserve_config.verbose || miniserve_config.log_downloads {
         simplelog::LevelFilter::Info
     } else {
         simplelog::LevelFilter::Warn
     };
 
+    let mut log_config = simplelog::ConfigBuilder::new();
+    log_config.set_time_format_rfc2822();
+    // Downloads are logged without the access log unless --verbose is set as well
+    if !miniserve_config.verbose {
+        log_config.add_filter_ignore_str("actix_web::middleware::logger");
+    }
+
     simplelog::TermLogger::init(
         log_level,
-        simplelog::ConfigBuilder::new()
-            .set_time_format_rfc2822()
-            .build(),
+        log_config.build(),
         simplelog::TerminalMode::Mixed,
         if io::stdout().is_terminal() {
             simplelog::ColorChoice::Auto
@@ -88,8 +128,41 @@ async fn run(miniserve_config: MiniserveConfig) -> Result<(), StartupError> {
         ));
     }
 
+    if let Some(manifest) = &miniserve_config.verify_manifest {
+        // When serving a single file, its name is relative to its directory
+        let root = if miniserve_config.path.is_dir() {
+            miniserve_config.path.as_path()
+        } else {
+            miniserve_config.path.parent().unwrap_or(Path::new(""))
+        };
+        // Nothing is served yet, so there's no harm in blocking here
+        let mismatches = file_utils::verify_manifest(root, manifest);
+
+        for file_name in &mismatches {
+            if miniserve_config.verify_manifest_warn_only {
+                warn!("{file_name} doesn't match the verification manifest");
+            } else {
+                error!("{file_name} doesn't match the verification manifest");
+            }
+        }
+        if !mismatches.is_empty() && !miniserve_config.verify_manifest_warn_only {
+            return Err(StartupError::ManifestMismatch(mismatches.len()));
+        }
+    }
+
     let inside_config = miniserve_config.clone();
 
+    // After changing the root directory, the served directory is found at the new root
+    #[cfg(unix)]
+    let inside_config = if miniserve_config.chroot {
+        MiniserveConfig {
+            path: PathBuf::from("/"),
+            ..inside_config
+        }
+    } else {
+        inside_config
+    };
+
     let canon_path = miniserve_config
         .path
         .canonicalize()
@@ -107,11 +180,13 @@ async fn run(miniserve_config: MiniserveConfig) -> Result<(), StartupError> {
 
     let path_string = canon_path.to_string_lossy();
 
-    println!(
-        "{name} v{version}",
-        name = "miniserve".bold(),
-        version = crate_version!()
-    );
+    if !miniserve_config.quiet {
+        println!(
+            "{name} v{version}",
+            name = "miniserve".bold(),
+            version = crate_version!()
+        );
+    }
     if !miniserve_config.path_explicitly_chosen {
         // If the path to serve has NOT been explicitly chosen and if this is NOT an interactive
         // terminal, we should refuse to start for security reasons. This would be the case when
@@ -161,6 +236,10 @@ async fn run(miniserve_config: MiniserveConfig) -> Result<(), StartupError> {
             ifaces.sort();
         }
 
+        if !miniserve_config.display_interfaces.is_empty() {
+            ifaces.retain(|addr| miniserve_config.display_interfaces.contains(addr));
+        }
+
         ifaces
             .into_iter()
             .map(|addr| match addr {
@@ -186,13 +265,28 @@ async fn run(miniserve_config: MiniserveConfig) -> Result<(), StartupError> {
         .map(|sock| sock.to_string().green().bold().to_string())
         .collect::<Vec<_>>();
 
-    let stylesheet = web::Data::new(
-        [
-            STYLESHEET,
-            inside_config.default_color_scheme.css(),
-            inside_config.default_color_scheme_dark.css_dark().as_str(),
-        ]
-        .join("\n"),
+    let stylesheet = web::Data::new(renderer::stylesheet(
+        &inside_config.default_color_scheme,
+        &inside_config.default_color_scheme_dark,
+    ));
+
+    let sitemap_cache = web::Data::new(sitemap::SitemapCache::default());
+    let upload_limiter = web::Data::new(file_op::UploadLimiter::new(
+        inside_config.max_concurrent_uploads,
+    ));
+    let upload_progress = web::Data::new(file_op::UploadProgress::default());
+    let tus_uploads = web::Data::new(tus::TusUploads::default());
+    let mime_sniff_cache = web::Data::new(file_utils::MimeSniffCache::default());
+    let listing_cache = web::Data::new(listing::ListingCache::new(inside_config.listing_cache_ttl));
+    let auth_throttle = web::Data::new(auth::AuthThrottle::new(
+        inside_config.auth_max_attempts,
+        inside_config.auth_lockout,
+    ));
+    let idle_tracker = web::Data::new(IdleTracker::default());
+    let last_activity = idle_tracker.clone();
+    let audit_log = web::Data::new(
+        audit::AuditLog::open(inside_config.audit_log.as_deref(), &inside_config.path)
+            .map_err(|e| StartupError::IoError("Failed to open the audit log".to_string(), e))?,
     );
 
     let srv = actix_web::HttpServer::new(move || {
@@ -200,27 +294,66 @@ async fn run(miniserve_config: MiniserveConfig) -> Result<(), StartupError> {
             .wrap(configure_header(&inside_config.clone()))
             .app_data(inside_config.clone())
             .app_data(stylesheet.clone())
+            .app_data(sitemap_cache.clone())
+            .app_data(upload_limiter.clone())
+            .app_data(upload_progress.clone())
+            .app_data(tus_uploads.clone())
+            .app_data(audit_log.clone())
+            .app_data(listing_cache.clone())
+            .app_data(mime_sniff_cache.clone())
+            .app_data(idle_tracker.clone())
+            .app_data(auth_throttle.clone())
+            .wrap_fn(sniff_content_type)
+            .wrap_fn(serve_media_for_streaming)
+            .wrap_fn(log_downloads)
+            .wrap_fn(serve_html_as_text)
+            .wrap(middleware::from_fn(force_download))
+            .wrap(middleware::from_fn(resolve_served_file))
+            .wrap_fn(limit_request_body)
+            .wrap_fn(reject_disabled_methods)
+            .wrap_fn(reject_disallowed_hosts)
             .wrap_fn(errors::error_page_middleware)
-            .wrap(middleware::Logger::default())
+            .wrap(access_logger(&inside_config))
+            .wrap(middleware::from_fn(assign_request_id))
+            .wrap(middleware::from_fn(set_server_header))
             .wrap(middleware::Condition::new(
                 miniserve_config.compress_response,
                 middleware::Compress::default(),
             ))
+            .wrap(middleware::from_fn(track_activity))
             .route(&inside_config.favicon_route, web::get().to(favicon))
             .route(&inside_config.css_route, web::get().to(css))
             .service(
                 web::scope(&inside_config.route_prefix)
                     .wrap(middleware::Condition::new(
-                        !inside_config.auth.is_empty(),
+                        !inside_config.auth.is_empty() && !inside_config.upload_requires_auth,
                         actix_web::middleware::Compat::new(HttpAuthentication::basic(
                             auth::handle_auth,
                         )),
                     ))
+                    .wrap(middleware::Condition::new(
+                        inside_config.upload_requires_auth,
+                        actix_web::middleware::Compat::new(HttpAuthentication::with_fn(
+                            auth::handle_optional_auth,
+                        )),
+                    ))
                     .configure(|c| configure_app(c, &inside_config)),
             )
+            .configure(|c| {
+                if inside_config.redirect_root_to_prefix {
+                    c.route("/", web::get().to(redirect_to_prefix));
+                }
+            })
             .default_service(web::get().to(error_404))
     });
 
+    let srv = match miniserve_config.max_connections {
+        Some(max_connections) => srv
+            .max_connections(max_connections)
+            .max_connection_rate(max_connections),
+        None => srv,
+    };
+
     let srv = socket_addresses.iter().try_fold(srv, |srv, addr| {
         let listener = create_tcp_listener(*addr)
             .map_err(|e| StartupError::IoError(format!("Failed to bind server to {addr}"), e))?;
@@ -237,45 +370,129 @@ async fn run(miniserve_config: MiniserveConfig) -> Result<(), StartupError> {
         srv.map_err(|e| StartupError::IoError(format!("Failed to bind server to {addr}"), e))
     })?;
 
+    // With --redirect-http, a second server redirects plain HTTP requests to HTTPS
+    let redirect_srv = match miniserve_config.redirect_http {
+        Some(redirect_port) => {
+            let https_port = miniserve_config.port;
+            let srv = actix_web::HttpServer::new(move || {
+                App::new().default_service(web::to(move |req: HttpRequest| async move {
+                    let location = https_redirect_location(&req, https_port);
+                    HttpResponse::MovedPermanently()
+                        .insert_header((header::LOCATION, location))
+                        .finish()
+                }))
+            });
+            let srv = miniserve_config
+                .interfaces
+                .iter()
+                .map(|&interface| SocketAddr::new(interface, redirect_port))
+                .try_fold(srv, |srv, addr| {
+                    create_tcp_listener(addr)
+                        .and_then(|listener| srv.listen(listener))
+                        .map_err(|e| {
+                            StartupError::IoError(format!("Failed to bind server to {addr}"), e)
+                        })
+                })?;
+            Some(srv.shutdown_timeout(0).run())
+        }
+        None => None,
+    };
+
+    #[cfg(unix)]
+    drop_privileges(&miniserve_config)?;
+
     let srv = srv.shutdown_timeout(0).run();
 
-    println!("Bound to {}", display_sockets.join(", "));
+    if let Some(idle_timeout) = miniserve_config.shutdown_after_idle {
+        let handles = std::iter::once(srv.handle())
+            .chain(redirect_srv.as_ref().map(|srv| srv.handle()))
+            .collect::<Vec<_>>();
+        let quiet = miniserve_config.quiet;
+        actix_web::rt::spawn(async move {
+            loop {
+                let idle_for = last_activity.idle_for();
+                if idle_for >= idle_timeout {
+                    break;
+                }
+                actix_web::rt::time::sleep(idle_timeout - idle_for).await;
+            }
+            if !quiet {
+                println!(
+                    "Shutting down after {} without requests",
+                    humantime::format_duration(idle_timeout)
+                );
+            }
+            for handle in handles {
+                handle.stop(true).await;
+            }
+        });
+    }
 
-    println!("Serving path {}", path_string.yellow().bold());
+    if !miniserve_config.quiet {
+        println!("Bound to {}", display_sockets.join(", "));
 
-    println!(
-        "Available at (non-exhaustive list):\n    {}\n",
-        display_urls
-            .iter()
-            .map(|url| url.green().bold().to_string())
-            .collect::<Vec<_>>()
-            .join("\n    "),
-    );
+        println!("Serving path {}", path_string.yellow().bold());
 
-    // print QR code to terminal
-    if miniserve_config.show_qrcode && io::stdout().is_terminal() {
-        for url in display_urls
-            .iter()
-            .filter(|url| !url.contains("//127.0.0.1:") && !url.contains("//[::1]:"))
-        {
-            match QRBuilder::new(url.clone()).ecl(consts::QR_EC_LEVEL).build() {
-                Ok(qr) => {
-                    println!("QR code for {}:", url.green().bold());
-                    qr.print();
-                }
-                Err(e) => {
-                    error!("Failed to render QR to terminal: {:?}", e);
-                }
-            };
+        println!(
+            "Available at (non-exhaustive list):\n    {}\n",
+            display_urls
+                .iter()
+                .map(|url| url.green().bold().to_string())
+                .collect::<Vec<_>>()
+                .join("\n    "),
+        );
+
+        // print QR code to terminal
+        if miniserve_config.show_qrcode && io::stdout().is_terminal() {
+            for url in display_urls
+                .iter()
+                .filter(|url| !url.contains("//127.0.0.1:") && !url.contains("//[::1]:"))
+            {
+                match QRBuilder::new(url.clone()).ecl(consts::QR_EC_LEVEL).build() {
+                    Ok(qr) => {
+                        println!("QR code for {}:", url.green().bold());
+                        qr.print();
+                    }
+                    Err(e) => {
+                        error!("Failed to render QR to terminal: {:?}", e);
+                    }
+                };
+            }
+        }
+
+        if io::stdout().is_terminal() {
+            println!("Quit by pressing CTRL-C");
         }
     }
 
-    if io::stdout().is_terminal() {
-        println!("Quit by pressing CTRL-C");
+    match redirect_srv {
+        Some(redirect_srv) => futures::future::try_join(srv, redirect_srv)
+            .await
+            .map(|_| ()),
+        None => srv.await,
     }
+    .map_err(|e| StartupError::IoError("".to_owned(), e))
+}
+
+/// Builds the HTTPS URL for the host and path of a plain HTTP request
+fn https_redirect_location(req: &HttpRequest, https_port: u16) -> String {
+    let conn_info = req.connection_info();
+    let host = conn_info.host();
+    // Strip the port of the plain HTTP listener, taking care of IPv6 addresses like `[::1]:80`
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.ends_with(']') => name,
+        _ => host,
+    };
+    let path = req
+        .uri()
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
 
-    srv.await
-        .map_err(|e| StartupError::IoError("".to_owned(), e))
+    if https_port == 443 {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}:{https_port}{path}")
+    }
 }
 
 /// Allows us to set low-level socket options
@@ -295,6 +512,52 @@ fn create_tcp_listener(addr: SocketAddr) -> io::Result<TcpListener> {
     Ok(TcpListener::from(socket))
 }
 
+/// Changes the root directory and switches to the configured user and group
+///
+/// This happens after binding, so that privileged ports can be used without serving files as root.
+#[cfg(unix)]
+fn drop_privileges(conf: &MiniserveConfig) -> Result<(), StartupError> {
+    use nix::unistd;
+
+    if !conf.chroot && conf.user.is_none() && conf.group.is_none() {
+        return Ok(());
+    }
+
+    let drop = || -> nix::Result<()> {
+        if conf.chroot {
+            unistd::chroot(&conf.path)?;
+            unistd::chdir("/")?;
+        }
+        if let Some(gid) = conf.group {
+            unistd::setgroups(&[gid])?;
+            unistd::setgid(gid)?;
+        }
+        if let Some(uid) = conf.user {
+            unistd::setuid(uid)?;
+        }
+        Ok(())
+    };
+    drop().map_err(|e| StartupError::IoError("Failed to drop privileges".to_string(), e.into()))?;
+
+    // Fail early instead of on every request if the new user can't read the served path
+    let served_path = if conf.chroot {
+        PathBuf::from("/")
+    } else {
+        conf.path.clone()
+    };
+    let accessible = if served_path.is_dir() {
+        std::fs::read_dir(&served_path).map(|_| ())
+    } else {
+        std::fs::File::open(&served_path).map(|_| ())
+    };
+    accessible.map_err(|e| {
+        StartupError::IoError(
+            "The served path is not accessible after dropping privileges".to_string(),
+            e,
+        )
+    })
+}
+
 fn configure_header(conf: &MiniserveConfig) -> middleware::DefaultHeaders {
     conf.header.iter().flatten().fold(
         middleware::DefaultHeaders::new(),
@@ -302,6 +565,610 @@ fn configure_header(conf: &MiniserveConfig) -> middleware::DefaultHeaders {
     )
 }
 
+/// Rejects requests with a body larger than allowed by `--max-request-body`
+///
+/// Requests announcing a larger body are answered with 413 right away, other requests fail as
+/// soon as the received body exceeds the limit.
+fn limit_request_body<S, B>(
+    mut req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = actix_web::Result<ServiceResponse>> + 'static
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: actix_web::body::MessageBody + 'static,
+    S::Future: 'static,
+{
+    let limit = req
+        .app_data::<MiniserveConfig>()
+        .and_then(|conf| conf.max_request_body);
+
+    if let Some(limit) = limit {
+        let content_length = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.parse::<u64>().ok());
+        if content_length.is_some_and(|length| length > limit) {
+            let res: ServiceResponse<BoxBody> =
+                req.error_response(RuntimeError::PayloadTooLargeError(limit));
+            return Either::Left(future::ok(res));
+        }
+
+        let mut received = 0;
+        let payload = req.take_payload().map(move |chunk| {
+            let chunk = chunk?;
+            received += chunk.len() as u64;
+            if received > limit {
+                Err(PayloadError::Overflow)
+            } else {
+                Ok(chunk)
+            }
+        });
+        req.set_payload(Payload::Stream {
+            payload: Box::pin(payload),
+        });
+    }
+
+    let fut = srv.call(req);
+    Either::Right(async { Ok(fut.await?.map_into_boxed_body()) })
+}
+
+/// Rejects requests whose Host header isn't allowed, to protect against DNS rebinding
+fn reject_disallowed_hosts<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = actix_web::Result<ServiceResponse>> + 'static
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: actix_web::body::MessageBody + 'static,
+    S::Future: 'static,
+{
+    let allowed_hosts = req
+        .app_data::<MiniserveConfig>()
+        .map(|conf| conf.allowed_hosts.as_slice())
+        .unwrap_or_default();
+
+    if !allowed_hosts.is_empty() {
+        // HTTP/2 requests carry the host in the URI instead of the Host header
+        let host = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+            .unwrap_or_default()
+            .to_string();
+        if !allowed_hosts.contains(&config::normalize_host(&host)) {
+            let res = req.error_response(RuntimeError::HostNotAllowedError(host));
+            return Either::Left(future::ok(res));
+        }
+    }
+
+    let fut = srv.call(req);
+    Either::Right(async { Ok(fut.await?.map_into_boxed_body()) })
+}
+
+/// Rejects requests using one of the disabled methods before they are routed
+fn reject_disabled_methods<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = actix_web::Result<ServiceResponse>> + 'static
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: actix_web::body::MessageBody + 'static,
+    S::Future: 'static,
+{
+    let disabled = req
+        .app_data::<MiniserveConfig>()
+        .is_some_and(|conf| conf.disabled_methods.contains(req.method()));
+    if disabled {
+        let method = req.method().to_string();
+        let res = req.error_response(RuntimeError::MethodNotAllowedError(method));
+        return Either::Left(future::ok(res));
+    }
+
+    let fut = srv.call(req);
+    Either::Right(async { Ok(fut.await?.map_into_boxed_body()) })
+}
+
+/// Overrides the content type of served files with the one detected from their contents
+fn sniff_content_type<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = actix_web::Result<ServiceResponse<B>>> + 'static
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: 'static,
+    S::Future: 'static,
+{
+    let served_file = req
+        .app_data::<MiniserveConfig>()
+        .filter(|conf| conf.mime_sniff)
+        .and_then(|_| served_file(&req));
+    let cache = req
+        .app_data::<web::Data<file_utils::MimeSniffCache>>()
+        .cloned();
+
+    let fut = srv.call(req);
+    async move {
+        let mut res = fut.await?;
+        if let (Some(served_file), Some(cache)) = (served_file, cache) {
+            if res.status().is_success() {
+                if let Some(mime_type) = cache.sniff(&served_file) {
+                    res.headers_mut()
+                        .insert(header::CONTENT_TYPE, HeaderValue::from_static(mime_type));
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Keeps track of when the last request was handled, for --shutdown-after-idle
+struct IdleTracker {
+    last_request: Mutex<Instant>,
+
+    /// Number of requests whose response hasn't been sent completely yet
+    in_flight: AtomicUsize,
+}
+
+impl Default for IdleTracker {
+    fn default() -> Self {
+        Self {
+            last_request: Mutex::new(Instant::now()),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl IdleTracker {
+    /// Records that a request is being handled now
+    fn touch(&self) {
+        *self.last_request.lock().unwrap() = Instant::now();
+    }
+
+    /// Returns how long ago the last request was handled, which is never while responses are
+    /// still being sent
+    fn idle_for(&self) -> Duration {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            return Duration::ZERO;
+        }
+        self.last_request.lock().unwrap().elapsed()
+    }
+}
+
+/// Counts a request as in flight until it's dropped along with the response body
+struct InFlightGuard(web::Data<IdleTracker>);
+
+impl InFlightGuard {
+    fn new(tracker: web::Data<IdleTracker>) -> Self {
+        tracker.touch();
+        tracker.in_flight.fetch_add(1, Ordering::SeqCst);
+        Self(tracker)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.touch();
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Response body which keeps its request in flight until it has been sent or dropped
+struct InFlightBody {
+    body: BoxBody,
+    _guard: Option<InFlightGuard>,
+}
+
+impl actix_web::body::MessageBody for InFlightBody {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> actix_web::body::BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<web::Bytes, Self::Error>>> {
+        std::pin::Pin::new(&mut self.body).poll_next(cx)
+    }
+}
+
+/// Builds the access log middleware, which also logs the request ID if --request-id-header is set
+///
+/// With --log-redact, query strings are left out of the logged request line and referer.
+fn access_logger(conf: &MiniserveConfig) -> middleware::Logger {
+    let request_id = conf
+        .request_id_header
+        .as_ref()
+        .map(|header| format!(" %{{{header}}}i"))
+        .unwrap_or_default();
+
+    if conf.log_redact {
+        middleware::Logger::new(&format!(
+            r#"%a "%{{request_line}}xi" %s %b "%{{referer}}xi" "%{{User-Agent}}i" %T{request_id}"#
+        ))
+        .custom_request_replace("request_line", |req| {
+            let query = if req.query_string().is_empty() {
+                ""
+            } else {
+                REDACTED_QUERY
+            };
+            format!("{} {}{query} {:?}", req.method(), req.path(), req.version())
+        })
+        .custom_request_replace("referer", |req| {
+            let referer = req.headers().get(header::REFERER);
+            match referer.and_then(|referer| referer.to_str().ok()) {
+                Some(referer) => match referer.split_once('?') {
+                    Some((referer, _)) => format!("{referer}{REDACTED_QUERY}"),
+                    None => referer.to_string(),
+                },
+                None => "-".to_string(),
+            }
+        })
+    } else if conf.request_id_header.is_some() {
+        middleware::Logger::new(&format!(
+            r#"%a "%r" %s %b "%{{Referer}}i" "%{{User-Agent}}i" %T{request_id}"#
+        ))
+    } else {
+        middleware::Logger::default()
+    }
+}
+
+/// Placeholder for query strings left out of the access log
+const REDACTED_QUERY: &str = "?[redacted]";
+
+/// Makes sure every request carries an ID in the header given by --request-id-header, and returns
+/// it with the response
+///
+/// This runs before the access log middleware, so that generated IDs are logged as well.
+async fn assign_request_id(
+    mut req: ServiceRequest,
+    next: middleware::Next<impl actix_web::body::MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl actix_web::body::MessageBody>> {
+    let request_id = req
+        .app_data::<MiniserveConfig>()
+        .and_then(|conf| conf.request_id_header.clone())
+        .and_then(|header| {
+            let id = match req.headers().get(&header) {
+                Some(id) => id.clone(),
+                None => HeaderValue::from_str(&nanoid::nanoid!()).ok()?,
+            };
+            Some((header, id))
+        });
+    if let Some((header, id)) = &request_id {
+        req.headers_mut().insert(header.clone(), id.clone());
+    }
+
+    let mut res = next.call(req).await?;
+    if let Some((header, id)) = request_id {
+        res.headers_mut().insert(header, id);
+    }
+    Ok(res)
+}
+
+/// Sets the Server header of every response to the one given by --server-header, or strips it if
+/// --no-server-header is set
+async fn set_server_header(
+    req: ServiceRequest,
+    next: middleware::Next<impl actix_web::body::MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl actix_web::body::MessageBody>> {
+    let server_header = req
+        .app_data::<MiniserveConfig>()
+        .map(|conf| (conf.server_header.clone(), conf.no_server_header));
+
+    let mut res = next.call(req).await?;
+    match server_header {
+        Some((Some(value), _)) => {
+            res.headers_mut().insert(header::SERVER, value);
+        }
+        Some((None, true)) => {
+            res.headers_mut().remove(header::SERVER);
+        }
+        _ => {}
+    }
+    Ok(res)
+}
+
+/// Records the time of every request, and keeps it in flight until its response body has been
+/// sent, so that long downloads don't count as idle time
+///
+/// Unlike most other middlewares, this one is built with `from_fn`, which boxes the services it
+/// wraps. Being the outermost middleware, this keeps the type of the whole chain small enough
+/// to compile.
+async fn track_activity(
+    req: ServiceRequest,
+    next: middleware::Next<impl actix_web::body::MessageBody + 'static>,
+) -> actix_web::Result<ServiceResponse<impl actix_web::body::MessageBody>> {
+    let guard = req
+        .app_data::<web::Data<IdleTracker>>()
+        .cloned()
+        .map(InFlightGuard::new);
+
+    let res = next.call(req).await?;
+    Ok(res.map_body(|_, body| InFlightBody {
+        body: BoxBody::new(body),
+        _guard: guard,
+    }))
+}
+
+/// Prepares served audio and video files for streaming if --enable-media-streaming is set
+///
+/// Players seek by requesting ranges of the file, which the file service already answers. For
+/// that to work, the file has to be served with a type the browser can play and must not be
+/// compressed, as compressed responses can't be seeked in.
+fn serve_media_for_streaming<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = actix_web::Result<ServiceResponse<B>>> + 'static
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: 'static,
+    S::Future: 'static,
+{
+    let media_type = req
+        .app_data::<MiniserveConfig>()
+        .filter(|conf| conf.media_streaming)
+        .and_then(|_| served_file(&req))
+        .and_then(|path| file_utils::media_content_type(&path));
+
+    let fut = srv.call(req);
+    async move {
+        let mut res = fut.await?;
+        if let Some(media_type) = media_type.filter(|_| res.status().is_success()) {
+            let headers = res.headers_mut();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(media_type));
+            headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+            // Keeps the compression middleware from touching the body
+            headers.insert(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static("identity"),
+            );
+        }
+        Ok(res)
+    }
+}
+
+/// Overrides the content type of served HTML files with plain text if --serve-html-as-text is set
+fn serve_html_as_text<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = actix_web::Result<ServiceResponse<B>>> + 'static
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: 'static,
+    S::Future: 'static,
+{
+    let is_html = req
+        .app_data::<MiniserveConfig>()
+        .filter(|conf| conf.serve_html_as_text)
+        .and_then(|_| served_file(&req))
+        .and_then(|path| path.extension().map(|ext| ext.to_ascii_lowercase()))
+        .is_some_and(|ext| ext == "html" || ext == "htm");
+
+    let fut = srv.call(req);
+    async move {
+        let mut res = fut.await?;
+        if is_html && res.status().is_success() {
+            let headers = res.headers_mut();
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; charset=utf-8"),
+            );
+            headers.insert(
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            );
+        }
+        Ok(res)
+    }
+}
+
+/// Returns whether the request asks for a file to be downloaded with `?download-file=true`
+fn is_forced_download(req: &ServiceRequest) -> bool {
+    req.query_string()
+        .split('&')
+        .any(|param| param == "download-file=true")
+}
+
+/// Makes browsers download files requested with `?download-file=true` instead of opening them
+///
+/// Like `track_activity`, this is built with `from_fn` to box the middlewares it wraps.
+async fn force_download(
+    req: ServiceRequest,
+    next: middleware::Next<impl actix_web::body::MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl actix_web::body::MessageBody>> {
+    let file_name = is_forced_download(&req)
+        .then(|| served_file(&req))
+        .flatten()
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+
+    let mut res = next.call(req).await?;
+    if let Some(file_name) = file_name.filter(|_| res.status().is_success()) {
+        let disposition = ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        };
+        if let Ok(disposition) = HeaderValue::from_str(&disposition.to_string()) {
+            res.headers_mut()
+                .insert(header::CONTENT_DISPOSITION, disposition);
+        }
+    }
+    Ok(res)
+}
+
+/// Logs every file that has been served completely if --log-downloads is set
+fn log_downloads<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = actix_web::Result<ServiceResponse>> + 'static
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: actix_web::body::MessageBody + 'static,
+    S::Future: 'static,
+{
+    let served_file = req
+        .app_data::<MiniserveConfig>()
+        .filter(|conf| conf.log_downloads)
+        .and_then(|conf| {
+            let path = served_file(&req)?;
+            let relative = path.strip_prefix(&conf.path).unwrap_or(&path);
+            Some(format!("/{}", relative.display()))
+        });
+
+    let fut = srv.call(req);
+    async move {
+        let res = fut.await?.map_into_boxed_body();
+        let Some(served_file) = served_file.filter(|_| res.status().is_success()) else {
+            return Ok(res);
+        };
+
+        let client = res
+            .request()
+            .peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let user = res
+            .request()
+            .extensions()
+            .get::<auth::CurrentUser>()
+            .map(|user| user.name.clone())
+            .unwrap_or_else(|| "-".to_string());
+        let download = format!("{served_file} to {client} as {user}");
+
+        Ok(res.map_body(|_, body| {
+            BoxBody::new(DownloadLogBody {
+                body,
+                sent: 0,
+                download: Some(download),
+            })
+        }))
+    }
+}
+
+/// Response body that logs the download once it has been sent completely
+struct DownloadLogBody {
+    body: BoxBody,
+
+    /// Number of bytes sent so far
+    sent: u64,
+
+    /// Description of the download, taken once it has been logged
+    download: Option<String>,
+}
+
+impl actix_web::body::MessageBody for DownloadLogBody {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> actix_web::body::BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<web::Bytes, Self::Error>>> {
+        let poll = std::pin::Pin::new(&mut self.body).poll_next(cx);
+        match &poll {
+            std::task::Poll::Ready(Some(Ok(bytes))) => self.sent += bytes.len() as u64,
+            std::task::Poll::Ready(None) => {
+                if let Some(download) = self.download.take() {
+                    log::info!("Served {download} ({} bytes)", self.sent);
+                }
+            }
+            _ => (),
+        }
+        poll
+    }
+}
+
+/// Path of the file served for a request, as resolved by `resolve_served_file`
+struct ServedFile(PathBuf);
+
+/// Resolves the file served for the request once for all the middlewares that depend on it,
+/// which read it from the request extensions with `served_file`
+///
+/// Like `track_activity`, this is built with `from_fn` to box the middlewares it wraps.
+async fn resolve_served_file(
+    req: ServiceRequest,
+    next: middleware::Next<impl actix_web::body::MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl actix_web::body::MessageBody>> {
+    let served_file = req
+        .app_data::<MiniserveConfig>()
+        .filter(|conf| {
+            conf.mime_sniff
+                || conf.media_streaming
+                || conf.serve_html_as_text
+                || conf.log_downloads
+                || is_forced_download(&req)
+        })
+        .and_then(|conf| served_file_path(req.path(), conf));
+    if let Some(path) = served_file {
+        req.extensions_mut().insert(ServedFile(path));
+    }
+
+    next.call(req).await
+}
+
+/// Returns the file served for the request, if it's a file at all
+fn served_file(req: &ServiceRequest) -> Option<PathBuf> {
+    req.extensions()
+        .get::<ServedFile>()
+        .map(|served_file| served_file.0.clone())
+}
+
+/// Returns the path of the file served for the request path, if it's a file at all
+fn served_file_path(request_path: &str, conf: &MiniserveConfig) -> Option<PathBuf> {
+    if conf.path.is_file() {
+        return Some(conf.path.clone());
+    }
+
+    served_path(request_path, conf).filter(|path| path.is_file())
+}
+
+/// Returns the path of the directory listed for the request path, if it's a directory at all
+fn listed_dir_path(request_path: &str, conf: &MiniserveConfig) -> Option<PathBuf> {
+    served_path(request_path, conf).filter(|path| path.is_dir())
+}
+
+/// Returns the path the request path is served from, if it may be served at all
+fn served_path(request_path: &str, conf: &MiniserveConfig) -> Option<PathBuf> {
+    let relative_path = request_path.strip_prefix(conf.route_prefix.as_str())?;
+    let relative_path = percent_encoding::percent_decode_str(relative_path)
+        .decode_utf8()
+        .ok()?;
+    let relative_path = file_utils::sanitize_path(&*relative_path, conf.show_hidden)?;
+    let mut path = conf.path.join(&relative_path);
+    if conf.case_insensitive && !path.exists() {
+        path = file_utils::resolve_case_insensitive(&conf.path, &relative_path, !conf.no_symlinks)?;
+    }
+    is_path_allowed(&path, conf).then_some(path)
+}
+
+/// Returns whether the entry at `path` may be served at all
+fn is_path_allowed(path: &Path, conf: &MiniserveConfig) -> bool {
+    // deny symlinks if conf.no_symlinks
+    if conf.no_symlinks && path.is_symlink() {
+        return false;
+    }
+    // deny files not matching conf.filters
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    if path.is_file() && !file_utils::matches_filters(&file_name, &conf.filters) {
+        return false;
+    }
+    // deny files hidden because of their age if conf.block_old_files
+    let blocked_age = conf.hide_files_older_than.filter(|_| conf.block_old_files);
+    !blocked_age.is_some_and(|max_age| {
+        path.metadata().is_ok_and(|metadata| {
+            metadata.is_file() && file_utils::is_older_than(&metadata, max_age)
+        })
+    })
+}
+
 /// Configures the Actix application
 ///
 /// This is where we configure the app to serve an index file, the file listing, or a single file.
@@ -342,8 +1209,67 @@ fn configure_app(app: &mut web::ServiceConfig, conf: &MiniserveConfig) {
                 if !path_base.ends_with("html") {
                     path_base = format!("{}.html", path_base);
                 }
-                let file = NamedFile::open_async(conf.path.join(path_base)).await?;
-                let res = file.into_response(&req);
+                let res = match NamedFile::open_async(conf.path.join(path_base)).await {
+                    Ok(file) => file.into_response(&req),
+                    // With --static-site, the site's own 404 page is served for missing paths
+                    Err(_) if conf.static_site => {
+                        let file = NamedFile::open_async(conf.path.join("404.html")).await?;
+                        let mut res = file.into_response(&req);
+                        *res.status_mut() = StatusCode::NOT_FOUND;
+                        res
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                Ok(ServiceResponse::new(req, res))
+            }));
+        }
+
+        // Handle --case-insensitive option.
+        //
+        // If the requested path doesn't exist exactly, we look for it ignoring case. Files are
+        // served directly, while directories are redirected to their actual path so that the
+        // links in their listing are correct.
+        if conf.case_insensitive {
+            files = files.default_handler(fn_service(|req: ServiceRequest| async {
+                let (req, _) = req.into_parts();
+                let conf = req
+                    .app_data::<MiniserveConfig>()
+                    .expect("Could not get miniserve config");
+                let requested =
+                    percent_encoding::percent_decode_str(req.match_info().unprocessed())
+                        .decode_utf8_lossy();
+                let resolved =
+                    file_utils::sanitize_path(&*requested, conf.show_hidden).and_then(|path| {
+                        file_utils::resolve_case_insensitive(&conf.path, &path, !conf.no_symlinks)
+                    });
+
+                // Files that are denied stay hidden regardless of the case of their name
+                let resolved = resolved.filter(|path| is_path_allowed(path, conf));
+
+                let res = match resolved {
+                    Some(path) if path.is_dir() => {
+                        let relative = path.strip_prefix(&conf.path).unwrap_or(&path);
+                        let location = relative.components().fold(
+                            conf.route_prefix.clone(),
+                            |location, component| {
+                                let name = component.as_os_str().to_string_lossy();
+                                let name = percent_encoding::utf8_percent_encode(
+                                    &name,
+                                    listing::percent_encode_sets::COMPONENT,
+                                );
+                                format!("{location}/{name}")
+                            },
+                        );
+                        HttpResponse::Found()
+                            .insert_header((header::LOCATION, format!("{location}/")))
+                            .finish()
+                    }
+                    Some(path) => NamedFile::open_async(path).await?.into_response(&req),
+                    None => {
+                        let err = RuntimeError::RouteNotFoundError(req.path().to_string());
+                        return Ok(ServiceResponse::from_err(err, req));
+                    }
+                };
                 Ok(ServiceResponse::new(req, res))
             }));
         }
@@ -352,41 +1278,158 @@ fn configure_app(app: &mut web::ServiceConfig, conf: &MiniserveConfig) {
             files = files.use_hidden_files();
         }
 
-        let base_path = conf.path.clone();
-        let no_symlinks = conf.no_symlinks;
+        let conf = conf.clone();
         files
             .show_files_listing()
             .files_listing_renderer(listing::directory_listing)
             .prefer_utf8(true)
             .redirect_to_slash_directory()
-            .path_filter(move |path, _| {
-                // deny symlinks if conf.no_symlinks
-                !(no_symlinks && base_path.join(path).is_symlink())
-            })
+            .path_filter(move |path, _| is_path_allowed(&conf.path.join(path), &conf))
     };
 
-    if conf.path.is_file() {
+    // Like the version footer, the version route can be hidden
+    if !conf.hide_version_footer {
+        app.route("/__miniserve_internal/version", web::get().to(version));
+    }
+
+    // Describes the enabled endpoints, so clients can discover what's available
+    app.route(
+        "/__miniserve_internal/openapi.json",
+        web::get().to(api::openapi),
+    );
+
+    // A configured robots.txt takes precedence over one in the served directory
+    if let Some(robots_txt) = conf.robots_txt.clone() {
+        app.route(
+            "/robots.txt",
+            web::get().to(move || {
+                let robots_txt = robots_txt.clone();
+                async move {
+                    HttpResponse::Ok()
+                        .content_type(mime::TEXT_PLAIN_UTF_8)
+                        .body(robots_txt)
+                }
+            }),
+        );
+    }
+
+    // Custom routes are registered first so they take precedence over the served files
+    for route in &conf.custom_routes {
+        let (status, body) = (route.status, route.body.clone());
+        app.route(
+            &route.path,
+            web::get().to(move || {
+                let body = body.clone();
+                async move {
+                    HttpResponse::build(status)
+                        .content_type(mime::TEXT_PLAIN_UTF_8)
+                        .body(body)
+                }
+            }),
+        );
+    }
+
+    if conf.path.is_file() && conf.single_file_landing {
+        // Handle single files behind a landing page
+        app.service(web::resource(["", "/"]).route(web::get().to(listing::single_file_landing)));
+        app.service(web::resource("/{file_name}").route(web::to(listing::single_file_download)));
+    } else if conf.path.is_file() {
         // Handle single files
         app.service(web::resource(["", "/"]).route(web::to(listing::file_handler)));
     } else {
+        if conf.sitemap {
+            app.service(web::resource("/sitemap.xml").route(web::get().to(sitemap::sitemap)));
+        }
+        if conf.listing_cache_ttl.is_some() {
+            // Allow invalidating cached listings
+            app.service(web::resource("/api").route(web::post().to(api::api_command)));
+        }
         if conf.file_upload {
             // Allow file upload
             app.service(web::resource("/upload").route(web::post().to(file_op::upload_file)));
+            app.service(
+                web::resource("/upload/progress").route(web::get().to(file_op::upload_progress)),
+            );
+            if conf.tus {
+                // Allow resumable uploads
+                app.service(
+                    web::resource("/upload/tus")
+                        .route(web::method(Method::OPTIONS).to(tus::tus_options))
+                        .route(web::post().to(tus::tus_create)),
+                );
+                app.service(
+                    web::resource("/upload/tus/{id}")
+                        .route(web::method(Method::OPTIONS).to(tus::tus_options))
+                        .route(web::head().to(tus::tus_head))
+                        .route(web::patch().to(tus::tus_patch)),
+                );
+            }
+        }
+        if conf.tree_api && !conf.disable_indexing {
+            // The listing renderer can't wait for the tree to be read on a blocking thread, so
+            // directory trees have their own handler
+            let tree_conf = conf.clone();
+            app.service(
+                web::resource(["", "/{path:.*}"])
+                    .guard(guard::Get())
+                    .guard(guard::fn_guard(move |ctx| {
+                        listing::is_tree_query(ctx.head().uri.query().unwrap_or_default())
+                            && listed_dir_path(ctx.head().uri.path(), &tree_conf).is_some()
+                    }))
+                    .to(listing::directory_tree),
+            );
         }
         // Handle directories
         app.service(dir_service());
     }
 }
 
+/// Redirects the root to the route prefix if --redirect-root-to-prefix is set
+async fn redirect_to_prefix(req: HttpRequest) -> impl Responder {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, format!("{}/", conf.route_prefix)))
+        .finish()
+}
+
 async fn error_404(req: HttpRequest) -> Result<HttpResponse, RuntimeError> {
     Err(RuntimeError::RouteNotFoundError(req.path().to_string()))
 }
 
-async fn favicon() -> impl Responder {
-    let logo = include_str!("../data/logo.svg");
-    HttpResponse::Ok()
-        .insert_header(ContentType(mime::IMAGE_SVG))
-        .body(logo)
+/// Returns the version of miniserve and how it was built, to tell which build is running
+async fn version() -> impl Responder {
+    let features = [("tls", cfg!(feature = "tls"))]
+        .into_iter()
+        .filter_map(|(feature, enabled)| enabled.then_some(feature))
+        .collect::<Vec<_>>();
+    HttpResponse::Ok().json(serde_json::json!({
+        "version": crate_version!(),
+        "commit": option_env!("MINISERVE_GIT_COMMIT"),
+        "features": features,
+    }))
+}
+
+/// Serves the favicon of the theme given by the `theme` query parameter, or the logo if it has
+/// none
+async fn favicon(req: HttpRequest) -> impl Responder {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    let themed_favicon = req
+        .query_string()
+        .split('&')
+        .find_map(|param| param.strip_prefix("theme="))
+        .and_then(|theme| conf.themed_favicons.get(theme));
+
+    match themed_favicon {
+        Some((mime, favicon)) => HttpResponse::Ok()
+            .insert_header(ContentType(mime.clone()))
+            .body(favicon.clone()),
+        None => {
+            let logo = include_str!("../data/logo.svg");
+            HttpResponse::Ok()
+                .insert_header(ContentType(mime::IMAGE_SVG))
+                .body(logo)
+        }
+    }
 }
 
 async fn css(stylesheet: web::Data<String>) -> impl Responder {
@@ -10,18 +10,31 @@ use fast_qr::{
     QRBuilder,
 };
 use maud::{html, Markup, PreEscaped, DOCTYPE};
+use percent_encoding::percent_decode_str;
 use strum::{Display, IntoEnumIterator};
 
-use crate::auth::CurrentUser;
+use crate::args::FooterLink;
+use crate::auth::{csrf_token, CurrentUser};
 use crate::consts;
-use crate::listing::{Breadcrumb, Entry, ListingQueryParameters, SortingMethod, SortingOrder};
+use crate::file_op::UPLOAD_MARKER_FILE;
+use crate::file_utils;
+use crate::i18n::{Language, Translations};
+use crate::listing::{
+    external_base_path, Breadcrumb, Description, Entry, EntryMetadata, ListingQueryParameters,
+    SortingMethod, SortingOrder,
+};
 use crate::{archive::ArchiveMethod, MiniserveConfig};
 
+static STYLESHEET: &str = grass::include!("data/style.scss");
+
 #[allow(clippy::too_many_arguments)]
 /// Renders the file listing
 pub fn page(
     entries: Vec<Entry>,
+    next_page: Option<usize>,
     readme: Option<(String, String)>,
+    description: Option<Description>,
+    complete: bool,
     abs_uri: &Uri,
     is_root: bool,
     query_params: ListingQueryParameters,
@@ -29,56 +42,79 @@ pub fn page(
     encoded_dir: &str,
     conf: &MiniserveConfig,
     current_user: Option<&CurrentUser>,
+    lang: Language,
 ) -> Markup {
     // If query_params.raw is true, we want render a minimal directory listing
     if query_params.raw.is_some() && query_params.raw.unwrap() {
-        return raw(entries, is_root);
+        return raw(entries, is_root, conf.si_units);
     }
 
-    let upload_route = format!("{}/upload", &conf.route_prefix);
+    let upload_route = format!("{}{}/upload", external_base_path(conf), &conf.route_prefix);
     let (sort_method, sort_order) = (query_params.sort, query_params.order);
 
+    let csrf_token = conf
+        .upload_csrf_secret
+        .as_deref()
+        .map(|secret| csrf_token(secret, current_user));
+
     let upload_action = build_upload_action(&upload_route, encoded_dir, sort_method, sort_order);
     let mkdir_action = build_mkdir_action(&upload_route, encoded_dir);
 
     let title_path = breadcrumbs_to_path_string(breadcrumbs);
+    let strings = lang.translations();
+
+    // Anonymous visitors don't get to see the upload forms if uploads require authentication
+    let may_upload = !conf.upload_requires_auth || current_user.is_some();
+    let file_upload = conf.file_upload && may_upload;
+    let mkdir_enabled = conf.mkdir_enabled && may_upload;
+    // Instead, they are offered to log in
+    let login_offered = !may_upload && (conf.file_upload || conf.mkdir_enabled);
 
-    let upload_allowed = conf.allowed_upload_dir.is_empty()
+    let upload_allowed = (conf.allowed_upload_dir.is_empty()
         || conf
             .allowed_upload_dir
             .iter()
-            .any(|x| encoded_dir.starts_with(&format!("/{x}")));
+            .any(|x| encoded_dir.starts_with(&format!("/{x}"))))
+        && (!conf.sidecar_upload_control || has_upload_marker(encoded_dir, conf));
 
     html! {
         (DOCTYPE)
-        html {
-            (page_header(&title_path, conf.file_upload, &conf.favicon_route, &conf.css_route))
-
-            body #drop-container
+        html lang=(lang) {
+            (page_header(&title_path, file_upload, conf.preview, conf, conf.open_graph.then(|| OpenGraph {
+                title: &title_path,
+                description: strings.listing_description.to_string(),
+                url: abs_uri.to_string(),
+                image: None,
+            })))
+
+            body #drop-container class={ "listing-" (conf.listing_style) }
             {
                 div.toolbar_box_group {
-                    @if conf.file_upload {
+                    @if file_upload {
                         div.drag-form {
                             div.form_title {
-                                h1 { "Drop your file here to upload it" }
+                                h1 { (strings.drop_to_upload) }
                             }
                         }
                     }
 
-                    @if conf.mkdir_enabled {
+                    @if mkdir_enabled {
                         div.form {
                             div.form_title {
-                                h1 { "Create a new directory" }
+                                h1 { (strings.create_directory_title) }
                             }
                         }
                     }
                 }
                 nav {
-                    (qr_spoiler(conf.show_qrcode, abs_uri))
-                    (color_scheme_selector(conf.hide_theme_selector))
+                    (qr_spoiler(conf.show_qrcode, abs_uri, strings))
+                    (color_scheme_selector(conf.hide_theme_selector, &conf.themes, strings))
                 }
                 div.container {
                     span #top { }
+                    @if let Some(banner) = &conf.banner {
+                        (banner_message(banner, strings))
+                    }
                     h1.title dir="ltr" {
                         @for el in breadcrumbs {
                             @if el.link == "." {
@@ -93,66 +129,122 @@ pub fn page(
                         }
                     }
                     div.toolbar {
-                        @if conf.tar_enabled || conf.tar_gz_enabled || conf.zip_enabled {
+                        @if conf.tar_enabled || conf.tar_gz_enabled || conf.zip_enabled || conf.flat_zip_enabled {
                             div.download {
                                 @for archive_method in ArchiveMethod::iter() {
-                                    @if archive_method.is_enabled(conf.tar_enabled, conf.tar_gz_enabled, conf.zip_enabled) {
-                                        (archive_button(archive_method, sort_method, sort_order))
+                                    @if archive_method.is_enabled(conf.tar_enabled, conf.tar_gz_enabled, conf.zip_enabled) && !(is_root && conf.no_root_archive) {
+                                        (archive_button(archive_method, sort_method, sort_order, strings))
+                                    }
+                                }
+                                @if conf.flat_zip_enabled {
+                                    a href="?download-flat=zip" {
+                                        (strings.download_files)
                                     }
                                 }
                             }
                         }
                         div.toolbar_box_group {
-                            @if conf.file_upload && upload_allowed {
+                            @if file_upload && upload_allowed {
                                 div.toolbar_box {
                                     form id="file_submit" action=(upload_action) method="POST" enctype="multipart/form-data" {
-                                        p { "Select a file to upload or drag it anywhere into the window" }
+                                        // The token has to be the first field of the form
+                                        @if let Some(csrf_token) = &csrf_token {
+                                            input type="hidden" name="csrf_token" value=(csrf_token) {}
+                                        }
+                                        p { (strings.upload_prompt) }
                                         div {
                                             @match &conf.uploadable_media_type {
                                                 Some(accept) => {input #file-input accept=(accept) type="file" name="file_to_upload" required="" multiple {}},
                                                 None => {input #file-input type="file" name="file_to_upload" required="" multiple {}}
                                             }
-                                            button type="submit" { "Upload file" }
+                                            button type="submit" { (strings.upload_file) }
                                         }
+                                        progress #upload-progress value="0" max="1" hidden {}
                                     }
                                 }
                             }
-                            @if conf.mkdir_enabled && upload_allowed {
+                            @if mkdir_enabled && upload_allowed {
                                 div.toolbar_box {
                                     form id="mkdir" action=(mkdir_action) method="POST" enctype="multipart/form-data" {
-                                        p { "Specify a directory name to create" }
+                                        @if let Some(csrf_token) = &csrf_token {
+                                            input type="hidden" name="csrf_token" value=(csrf_token) {}
+                                        }
+                                        p { (strings.mkdir_prompt) }
                                         div.toolbar_box {
-                                            input type="text" name="mkdir" required="" placeholder="Directory name" {}
-                                            button type="submit" { "Create directory" }
+                                            input type="text" name="mkdir" required="" placeholder=(strings.directory_name) {}
+                                            button type="submit" { (strings.create_directory) }
                                         }
                                     }
                                 }
                             }
+                            @if login_offered && upload_allowed {
+                                div.toolbar_box {
+                                    a #login href="?login=true" { (strings.log_in_to_upload) }
+                                }
+                            }
                         }
                     }
-                    table {
-                        thead {
-                            th.name { (build_link("name", "Name", sort_method, sort_order)) }
-                            th.size { (build_link("size", "Size", sort_method, sort_order)) }
-                            th.date { (build_link("date", "Last modification", sort_method, sort_order)) }
+                    @if !complete {
+                        p.partial-listing { (strings.partial_listing) }
+                    }
+                    @if let Some(description) = description {
+                        div.description {
+                            @match description {
+                                Description::Html(html) if conf.trust_description_html => (PreEscaped(html)),
+                                Description::Html(text) | Description::Text(text) => p { (text) },
+                            }
                         }
-                        tbody {
+                    }
+                    @if conf.listing_style == ListingStyle::Grid {
+                        div.grid {
                             @if !is_root {
-                                tr {
-                                    td colspan="3" {
-                                        p {
-                                            span.root-chevron { (chevron_left()) }
-                                            a.root href=(parametrized_link("../", sort_method, sort_order, false)) {
-                                                "Parent directory"
-                                            }
+                                div.grid-item {
+                                    p {
+                                        span.root-chevron { (chevron_left()) }
+                                        a.root href=(parametrized_link("../", sort_method, sort_order, false)) {
+                                            (strings.parent_directory)
                                         }
                                     }
                                 }
                             }
                             @for entry in entries {
-                                (entry_row(entry, sort_method, sort_order, false))
+                                (grid_item(entry, sort_method, sort_order, conf.si_units))
                             }
                         }
+                    } @else {
+                        table {
+                            thead {
+                                th.name {
+                                    (build_link("name", strings.name, sort_method, sort_order))
+                                    @if conf.metadata_sidecar {
+                                        (build_link("title", strings.title, sort_method, sort_order))
+                                    }
+                                }
+                                th.size { (build_link("size", strings.size, sort_method, sort_order)) }
+                                th.date { (build_link("date", strings.last_modification, sort_method, sort_order)) }
+                            }
+                            tbody {
+                                @if !is_root {
+                                    tr {
+                                        td colspan="3" {
+                                            p {
+                                                span.root-chevron { (chevron_left()) }
+                                                a.root href=(parametrized_link("../", sort_method, sort_order, false)) {
+                                                    (strings.parent_directory)
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                                @for entry in entries {
+                                    (entry_row(entry, sort_method, sort_order, false, conf.show_mimetype, conf.show_download_links, conf.si_units))
+                                }
+                            }
+                        }
+                    }
+                    @if let Some(next_page) = next_page {
+                        div #lazy-listing data-next-page=(next_page) {}
+                        (lazy_listing_script())
                     }
                     @if let Some(readme) = readme {
                         div id="readme" {
@@ -165,10 +257,19 @@ pub fn page(
                     a.back href="#top" {
                         (arrow_up())
                     }
+                    @if conf.preview {
+                        (preview_modal(strings))
+                    }
                     div.footer {
+                        @if !conf.footer_links.is_empty() {
+                            (footer_links(&conf.footer_links))
+                        }
                         @if conf.show_wget_footer {
                             (wget_footer(abs_uri, conf.title.as_deref(), current_user.map(|x| &*x.name)))
                         }
+                        @if conf.show_disk_space {
+                            (disk_space_footer(conf, strings))
+                        }
                         @if !conf.hide_version_footer {
                             (version_footer())
                         }
@@ -180,7 +281,7 @@ pub fn page(
 }
 
 /// Renders the file listing
-pub fn raw(entries: Vec<Entry>, is_root: bool) -> Markup {
+pub fn raw(entries: Vec<Entry>, is_root: bool, si_units: bool) -> Markup {
     html! {
         (DOCTYPE)
         html {
@@ -204,7 +305,7 @@ pub fn raw(entries: Vec<Entry>, is_root: bool) -> Markup {
                             }
                         }
                         @for entry in entries {
-                            (entry_row(entry, None, None, true))
+                            (entry_row(entry, None, None, true, false, false, si_units))
                         }
                     }
                 }
@@ -213,6 +314,85 @@ pub fn raw(entries: Vec<Entry>, is_root: bool) -> Markup {
     }
 }
 
+/// Partial: banner message, which stays hidden once dismissed until the message changes
+fn banner_message(message: &str, strings: &Translations) -> Markup {
+    html! {
+        div.banner data-message=(message) {
+            p { (message) }
+            button.banner-dismiss type="button" title=(strings.dismiss) { "×" }
+        }
+        (PreEscaped(r#"
+        <script>
+            (function() {
+                const banner = document.querySelector('.banner');
+                if (localStorage.getItem('dismissedBanner') === banner.dataset.message) {
+                    banner.remove();
+                    return;
+                }
+                banner.querySelector('.banner-dismiss').addEventListener('click', function() {
+                    localStorage.setItem('dismissedBanner', banner.dataset.message);
+                    banner.remove();
+                });
+            })();
+        </script>
+        "#))
+    }
+}
+
+/// Partial: script loading the remaining batches of a lazy listing when scrolling to its end
+fn lazy_listing_script() -> Markup {
+    html! {
+        (PreEscaped(r#"
+        <script>
+            (function() {
+                const sentinel = document.querySelector('#lazy-listing');
+                const listing = document.querySelector('div.grid, table tbody');
+                let loading = false;
+
+                // fetches the next batch and appends its entries to the listing
+                function loadNextBatch() {
+                    if (loading) {
+                        return;
+                    }
+                    loading = true;
+                    const url = new URL(location.href);
+                    url.searchParams.set('page', sentinel.dataset.nextPage);
+                    fetch(url)
+                        .then(response => response.text())
+                        .then(html => {
+                            const batch = new DOMParser().parseFromString(html, 'text/html');
+                            const entries = batch.querySelector('div.grid, table tbody').children;
+                            for (const entry of Array.from(entries)) {
+                                // the link to the parent directory is already listed
+                                if (!entry.querySelector('a.root')) {
+                                    listing.appendChild(document.importNode(entry, true));
+                                }
+                            }
+                            const next = batch.querySelector('#lazy-listing');
+                            observer.unobserve(sentinel);
+                            if (next) {
+                                sentinel.dataset.nextPage = next.dataset.nextPage;
+                                // observing again loads the next batch if the end is still visible
+                                observer.observe(sentinel);
+                            } else {
+                                sentinel.remove();
+                            }
+                        })
+                        .finally(() => loading = false);
+                }
+
+                const observer = new IntersectionObserver(function(entries) {
+                    if (entries.some(entry => entry.isIntersecting)) {
+                        loadNextBatch();
+                    }
+                });
+                observer.observe(sentinel);
+            })();
+        </script>
+        "#))
+    }
+}
+
 /// Renders the QR code SVG
 fn qr_code_svg(url: &Uri, margin: usize) -> Result<String, QRCodeError> {
     let qr = QRBuilder::new(url.to_string())
@@ -232,6 +412,46 @@ fn breadcrumbs_to_path_string(breadcrumbs: &[Breadcrumb]) -> String {
         .join("/")
 }
 
+/// Returns whether the directory contains the marker file allowing uploads to it
+fn has_upload_marker(encoded_dir: &str, conf: &MiniserveConfig) -> bool {
+    let dir = percent_decode_str(encoded_dir).decode_utf8_lossy();
+    conf.path
+        .join(dir.trim_start_matches('/'))
+        .join(UPLOAD_MARKER_FILE)
+        .is_file()
+}
+
+// Partial: custom footer links
+fn footer_links(links: &[FooterLink]) -> Markup {
+    html! {
+        nav.footer-links {
+            @for link in links {
+                a href=(link.url) { (link.text) }
+            }
+        }
+    }
+}
+
+// Partial: free space of the served filesystem
+fn disk_space_footer(conf: &MiniserveConfig, strings: &Translations) -> Markup {
+    let Some((free, total)) = file_utils::disk_space(&conf.path) else {
+        return html! {};
+    };
+    let text = strings
+        .disk_space
+        .replace(
+            "{free}",
+            &format_size(bytesize::ByteSize::b(free), conf.si_units),
+        )
+        .replace(
+            "{total}",
+            &format_size(bytesize::ByteSize::b(total), conf.si_units),
+        );
+    html! {
+        div.disk-space { (text) }
+    }
+}
+
 // Partial: version footer
 fn version_footer() -> Markup {
     html! {
@@ -311,7 +531,19 @@ const THEME_PICKER_CHOICES: &[(&str, &str)] = &[
     ("Monokai (dark)", "monokai"),
 ];
 
-#[derive(Debug, Clone, ValueEnum, Display)]
+/// Available layouts of the file listing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ListingStyle {
+    /// One table row per entry
+    #[default]
+    Table,
+
+    /// Tiles in a grid, suited for media directories
+    Grid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum, Display)]
 pub enum ThemeSlug {
     #[strum(serialize = "squirrel")]
     Squirrel,
@@ -323,6 +555,16 @@ pub enum ThemeSlug {
     Monokai,
 }
 
+/// Assembles the stylesheet served to the clients from the base style and the default themes
+pub fn stylesheet(color_scheme: &ThemeSlug, color_scheme_dark: &ThemeSlug) -> String {
+    [
+        STYLESHEET,
+        color_scheme.css(),
+        color_scheme_dark.css_dark().as_str(),
+    ]
+    .join("\n")
+}
+
 impl ThemeSlug {
     pub fn css(&self) -> &str {
         match self {
@@ -339,12 +581,12 @@ impl ThemeSlug {
 }
 
 /// Partial: qr code spoiler
-fn qr_spoiler(show_qrcode: bool, content: &Uri) -> Markup {
+fn qr_spoiler(show_qrcode: bool, content: &Uri, strings: &Translations) -> Markup {
     html! {
         @if show_qrcode {
             div {
                 p {
-                    "QR code"
+                    (strings.qr_code)
                 }
                 div.qrcode #qrcode title=(PreEscaped(content.to_string())) {
                     @match qr_code_svg(content, consts::SVG_QR_MARGIN) {
@@ -358,15 +600,23 @@ fn qr_spoiler(show_qrcode: bool, content: &Uri) -> Markup {
 }
 
 /// Partial: color scheme selector
-fn color_scheme_selector(hide_theme_selector: bool) -> Markup {
+fn color_scheme_selector(
+    hide_theme_selector: bool,
+    themes: &[ThemeSlug],
+    strings: &Translations,
+) -> Markup {
+    let choices = THEME_PICKER_CHOICES.iter().filter(|(_, slug)| {
+        *slug == "default" || themes.iter().any(|theme| theme.to_string() == *slug)
+    });
+
     html! {
         @if !hide_theme_selector {
             div {
                 p {
-                    "Change theme..."
+                    (strings.change_theme)
                 }
                 ul.theme {
-                    @for color_scheme in THEME_PICKER_CHOICES {
+                    @for color_scheme in choices {
                         li data-theme=(color_scheme.1) {
                             (color_scheme_link(color_scheme))
                         }
@@ -393,6 +643,7 @@ fn archive_button(
     archive_method: ArchiveMethod,
     sort_method: Option<SortingMethod>,
     sort_order: Option<SortingOrder>,
+    strings: &Translations,
 ) -> Markup {
     let link = if sort_method.is_none() && sort_order.is_none() {
         format!("?download={archive_method}")
@@ -404,7 +655,7 @@ fn archive_button(
         )
     };
 
-    let text = format!("Download .{}", archive_method.extension());
+    let text = format!("{} .{}", strings.download, archive_method.extension());
 
     html! {
         a href=(link) {
@@ -488,6 +739,9 @@ fn entry_row(
     sort_method: Option<SortingMethod>,
     sort_order: Option<SortingOrder>,
     raw: bool,
+    show_mimetype: bool,
+    show_download_link: bool,
+    si_units: bool,
 ) -> Markup {
     html! {
         tr {
@@ -503,34 +757,60 @@ fn entry_row(
                         }@else {
                             a.directory href=(parametrized_link(&entry.link, sort_method, sort_order, raw)) {
                                 (entry.name) "/"
+                                @for name in &entry.collapsed {
+                                    (name) "/"
+                                }
                             }
                         }
+                        @if !raw {
+                            (entry_metadata(entry.metadata.as_ref()))
+                        }
                     } @else if entry.is_file() {
                         @if let Some(symlink_dest) = entry.symlink_info {
-                            a.symlink href=(&entry.link) {
+                            a.symlink href=(&entry.link) data-mime-type=[&entry.mime_type] {
                                 (entry.name)
                                 span.symlink-symbol { }
                                 a.file {(symlink_dest)}
                             }
                         }@else {
-                            a.file href=(&entry.link) {
+                            a.file href=(&entry.link) data-mime-type=[&entry.mime_type] {
                                 (entry.name)
                             }
                         }
 
                         @if !raw {
+                            @if show_download_link {
+                                a.download-link href=(format!("{}?download-file=true", entry.link)) title="Download" download { "⤓" }
+                            }
+                            (entry_metadata(entry.metadata.as_ref()))
+                            @for (name, value) in &entry.xattrs {
+                                span.xattr title=(name) { (value) }
+                            }
+                            @if let Some(mime_type) = entry.mime_type.as_ref().filter(|_| show_mimetype) {
+                                span.mime-type {
+                                    (mime_type)
+                                }
+                            }
                             @if let Some(size) = entry.size {
                                 span.mobile-info.size {
-                                    (maud::display(size))
+                                    (format_size(size, si_units))
                                 }
                             }
                         }
+                    } @else if entry.is_broken_symlink() {
+                        span.broken-symlink title="Broken symlink" {
+                            (entry.name)
+                            span.symlink-symbol { }
+                            @if let Some(symlink_dest) = entry.symlink_info {
+                                span.broken-symlink-target { (symlink_dest) }
+                            }
+                        }
                     }
                 }
             }
             td.size-cell {
                 @if let Some(size) = entry.size {
-                    (maud::display(size))
+                    (format_size(size, si_units))
                 }
             }
             td.date-cell {
@@ -549,6 +829,84 @@ fn entry_row(
     }
 }
 
+/// Partial: title and description of an entry from the metadata sidecar
+fn entry_metadata(metadata: Option<&EntryMetadata>) -> Markup {
+    html! {
+        @if let Some(metadata) = metadata {
+            @if let Some(title) = &metadata.title {
+                span.entry-title { (title) }
+            }
+            @if let Some(description) = &metadata.description {
+                span.entry-description { (description) }
+            }
+        }
+    }
+}
+
+/// Formats a file size in SI (kB, base 1000) or IEC (KiB, base 1024) units
+pub fn format_size(size: bytesize::ByteSize, si_units: bool) -> String {
+    if si_units {
+        size.display().si().to_string()
+    } else {
+        size.display().iec().to_string()
+    }
+}
+
+/// Partial: grid item for an entry
+fn grid_item(
+    entry: Entry,
+    sort_method: Option<SortingMethod>,
+    sort_order: Option<SortingOrder>,
+    si_units: bool,
+) -> Markup {
+    html! {
+        div.grid-item {
+            @if entry.is_dir() {
+                a.directory href=(parametrized_link(&entry.link, sort_method, sort_order, false)) {
+                    (entry.name) "/"
+                    @for name in &entry.collapsed {
+                        (name) "/"
+                    }
+                }
+            } @else if entry.is_file() {
+                a.file href=(&entry.link) data-mime-type=[&entry.mime_type] {
+                    (entry.name)
+                }
+                @if let Some(size) = entry.size {
+                    span.size {
+                        (format_size(size, si_units))
+                    }
+                }
+            } @else if entry.is_broken_symlink() {
+                span.broken-symlink title="Broken symlink" {
+                    (entry.name)
+                }
+            }
+            @if let Some(modification_timer) = humanize_systemtime(entry.last_modification_date) {
+                span.history {
+                    (modification_timer)
+                }
+            }
+        }
+    }
+}
+
+/// Partial: modal in which files are previewed
+fn preview_modal(strings: &Translations) -> Markup {
+    html! {
+        div #preview-modal hidden {
+            div.preview-content {
+                div.preview-header {
+                    span #preview-title { }
+                    a #preview-download download { (strings.download) }
+                    button #preview-close type="button" { "✕" }
+                }
+                div #preview-body { }
+            }
+        }
+    }
+}
+
 /// Partial: up arrow
 fn arrow_up() -> Markup {
     PreEscaped("⇪".to_string())
@@ -569,8 +927,43 @@ fn chevron_down() -> Markup {
     PreEscaped("▾".to_string())
 }
 
+/// Contents of the Open Graph meta tags used for link previews
+struct OpenGraph<'a> {
+    title: &'a str,
+    description: String,
+    url: String,
+    image: Option<String>,
+}
+
+/// Partial: Open Graph meta tags
+fn open_graph_tags(open_graph: &OpenGraph) -> Markup {
+    html! {
+        meta property="og:type" content="website";
+        meta property="og:title" content=(open_graph.title);
+        meta property="og:description" content=(open_graph.description);
+        meta property="og:url" content=(open_graph.url);
+        @if let Some(image) = &open_graph.image {
+            meta property="og:image" content=(image);
+        }
+    }
+}
+
 /// Partial: page header
-fn page_header(title: &str, file_upload: bool, favicon_route: &str, css_route: &str) -> Markup {
+fn page_header(
+    title: &str,
+    file_upload: bool,
+    preview: bool,
+    conf: &MiniserveConfig,
+    open_graph: Option<OpenGraph>,
+) -> Markup {
+    // Themes saved by the browser are only applied if they are still offered
+    let allowed_themes: Vec<_> = conf.themes.iter().map(ToString::to_string).collect();
+    let allowed_themes = serde_json::to_string(&allowed_themes).unwrap_or_default();
+    let themed_favicons: Vec<_> = conf.themed_favicons.keys().collect();
+    let themed_favicons = serde_json::to_string(&themed_favicons).unwrap_or_default();
+    let favicon_route = format!("{}{}", external_base_path(conf), conf.favicon_route);
+    let css_route = format!("{}{}", external_base_path(conf), conf.css_route);
+
     html! {
         head {
             meta charset="utf-8";
@@ -578,23 +971,48 @@ fn page_header(title: &str, file_upload: bool, favicon_route: &str, css_route: &
             meta name="viewport" content="width=device-width, initial-scale=1";
             meta name="color-scheme" content="dark light";
 
-            link rel="icon" type="image/svg+xml" href={ (favicon_route) };
-            link rel="stylesheet" href={ (css_route) };
+            link #favicon rel="icon" href={ (favicon_route) };
+            @if let Some(integrity) = &conf.css_integrity {
+                link rel="stylesheet" href={ (css_route) } integrity=(integrity) crossorigin="anonymous";
+            } @else {
+                link rel="stylesheet" href={ (css_route) };
+            }
 
             title { (title) }
 
+            @if let Some(open_graph) = &open_graph {
+                (open_graph_tags(open_graph))
+            }
+
+            script {
+                (PreEscaped(format!("const allowedThemes = {allowed_themes};")))
+                (PreEscaped(format!("const themedFavicons = {themed_favicons};")))
+            }
             (PreEscaped(r#"
                 <script>
                     // updates the color scheme by setting the theme data attribute
                     // on body and saving the new theme to local storage
                     function updateColorScheme(name) {
-                        if (name && name != "default") {
+                        if (name && name != "default" && allowedThemes.includes(name)) {
                             localStorage.setItem('theme', name);
                             document.body.setAttribute("data-theme", name)
                         } else {
                             localStorage.removeItem('theme');
                             document.body.removeAttribute("data-theme")
                         }
+                        updateFavicon(name);
+                    }
+
+                    // shows the favicon of the theme, if it has one of its own
+                    function updateFavicon(name) {
+                        const favicon = document.querySelector('#favicon');
+                        const url = new URL(favicon.href);
+                        if (themedFavicons.includes(name)) {
+                            url.searchParams.set('theme', name);
+                        } else {
+                            url.searchParams.delete('theme');
+                        }
+                        favicon.href = url.href;
                     }
 
                     // read theme from local storage and apply it to body
@@ -617,8 +1035,44 @@ fn page_header(title: &str, file_upload: bool, favicon_route: &str, css_route: &
                         const dropContainer = document.querySelector('#drop-container');
                         const dragForm = document.querySelector('.drag-form');
                         const fileInput = document.querySelector('#file-input');
+                        const uploadProgress = document.querySelector('#upload-progress');
                         const collection = [];
 
+                        // sends all selected files in a single request, so that its progress
+                        // reflects the total bytes uploaded across the whole batch
+                        function uploadFiles() {
+                            const xhr = new XMLHttpRequest();
+                            xhr.open('POST', file_submit.action);
+                            xhr.upload.onprogress = function(e) {
+                                if (e.lengthComputable) {
+                                    uploadProgress.max = e.total;
+                                    uploadProgress.value = e.loaded;
+                                }
+                            };
+                            xhr.onload = function() {
+                                if (xhr.status >= 400) {
+                                    document.open();
+                                    document.write(xhr.responseText);
+                                    document.close();
+                                } else if (xhr.responseURL && xhr.responseURL !== file_submit.action) {
+                                    location.href = xhr.responseURL;
+                                } else {
+                                    location.reload();
+                                }
+                            };
+                            xhr.onerror = function() {
+                                uploadProgress.hidden = true;
+                            };
+                            uploadProgress.value = 0;
+                            uploadProgress.hidden = false;
+                            xhr.send(new FormData(file_submit));
+                        }
+
+                        file_submit.onsubmit = function(e) {
+                            e.preventDefault();
+                            uploadFiles();
+                        };
+
                         dropContainer.ondragover = function(e) {
                             e.preventDefault();
                         }
@@ -642,13 +1096,84 @@ fn page_header(title: &str, file_upload: bool, favicon_route: &str, css_route: &
                         dropContainer.ondrop = function(e) {
                             e.preventDefault();
                             fileInput.files = e.dataTransfer.files;
-                            file_submit.submit();
+                            uploadFiles();
                             dragForm.style.display = 'none';
                         };
                     }
                 </script>
                 "#))
             }
+
+            @if preview {
+                (PreEscaped(r#"
+                <script>
+                    addEventListener("DOMContentLoaded", function() {
+                        const modal = document.querySelector('#preview-modal');
+                        const title = document.querySelector('#preview-title');
+                        const download = document.querySelector('#preview-download');
+                        const body = document.querySelector('#preview-body');
+
+                        // creates the element showing the linked file, or null if the file
+                        // type can't be previewed
+                        function previewElement(link, mimeType) {
+                            if (mimeType.startsWith('image/')) {
+                                const img = document.createElement('img');
+                                img.src = link.href;
+                                return img;
+                            }
+                            if (mimeType.startsWith('video/')) {
+                                const video = document.createElement('video');
+                                video.src = link.href;
+                                video.controls = true;
+                                return video;
+                            }
+                            if (mimeType.startsWith('text/') || mimeType === 'application/json') {
+                                const pre = document.createElement('pre');
+                                fetch(link.href)
+                                    .then(response => response.text())
+                                    .then(text => pre.textContent = text);
+                                return pre;
+                            }
+                            return null;
+                        }
+
+                        function closePreview() {
+                            modal.hidden = true;
+                            body.replaceChildren();
+                        }
+
+                        document.addEventListener('click', function(e) {
+                            const link = e.target.closest('a[data-mime-type]');
+                            if (!link || e.ctrlKey || e.metaKey || e.shiftKey) {
+                                return;
+                            }
+                            const element = previewElement(link, link.dataset.mimeType);
+                            if (!element) {
+                                // fall back to navigating to the file
+                                return;
+                            }
+                            e.preventDefault();
+                            title.textContent = decodeURIComponent(link.pathname.split('/').pop());
+                            download.href = link.href;
+                            body.replaceChildren(element);
+                            modal.hidden = false;
+                        });
+
+                        modal.addEventListener('click', function(e) {
+                            if (e.target === modal) {
+                                closePreview();
+                            }
+                        });
+                        document.querySelector('#preview-close').addEventListener('click', closePreview);
+                        addEventListener('keydown', function(e) {
+                            if (e.key === 'Escape') {
+                                closePreview();
+                            }
+                        });
+                    });
+                </script>
+                "#))
+            }
         }
     }
 }
@@ -666,17 +1191,84 @@ fn humanize_systemtime(time: Option<SystemTime>) -> Option<String> {
     time.map(|time| time.humanize())
 }
 
+/// Renders a landing page with a download link for a single served file
+pub fn single_file_landing(
+    file_name: &str,
+    download_link: &str,
+    size: Option<bytesize::ByteSize>,
+    abs_uri: &Uri,
+    conf: &MiniserveConfig,
+    lang: Language,
+) -> Markup {
+    let strings = lang.translations();
+    let open_graph = conf.open_graph.then(|| {
+        let download_uri = format!(
+            "{}://{}{download_link}",
+            abs_uri.scheme_str().unwrap_or("http"),
+            abs_uri.authority().map(|a| a.as_str()).unwrap_or_default()
+        );
+        let is_image = mime_guess::from_path(file_name)
+            .first()
+            .is_some_and(|mime| mime.type_() == mime::IMAGE);
+        OpenGraph {
+            title: file_name,
+            description: match size {
+                Some(size) => format!(
+                    "{} ({})",
+                    strings.download,
+                    format_size(size, conf.si_units)
+                ),
+                None => strings.download.to_string(),
+            },
+            url: abs_uri.to_string(),
+            image: is_image.then_some(download_uri),
+        }
+    });
+
+    html! {
+        (DOCTYPE)
+        html lang=(lang) {
+            (page_header(file_name, false, false, conf, open_graph))
+
+            body {
+                nav {
+                    (color_scheme_selector(conf.hide_theme_selector, &conf.themes, strings))
+                }
+                div.container {
+                    h1.title dir="ltr" {
+                        bdi { (file_name) }
+                    }
+                    @if let Some(size) = size {
+                        p.size { (format_size(size, conf.si_units)) }
+                    }
+                    div.download {
+                        a href=(download_link) download=(file_name) {
+                            (strings.download)
+                        }
+                    }
+                    div.footer {
+                        @if !conf.hide_version_footer {
+                            (version_footer())
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Renders an error on the webpage
 pub fn render_error(
     error_description: &str,
     error_code: StatusCode,
     conf: &MiniserveConfig,
     return_address: &str,
+    lang: Language,
 ) -> Markup {
     html! {
         (DOCTYPE)
-        html {
-            (page_header(&error_code.to_string(), false, &conf.favicon_route, &conf.css_route))
+        html lang=(lang) {
+            (page_header(&error_code.to_string(), false, false, conf, None))
 
             body
             {
@@ -689,7 +1281,7 @@ pub fn render_error(
                     @if conf.route_prefix.is_empty() && !conf.disable_indexing {
                         div.error-nav {
                             a.error-back href=(return_address) {
-                                "Go back to file listing"
+                                (lang.translations().back_to_listing)
                             }
                         }
                     }
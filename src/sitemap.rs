@@ -0,0 +1,173 @@
+//! Generation of a sitemap.xml enumerating all served files
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use maud::{html, PreEscaped};
+use percent_encoding::utf8_percent_encode;
+use regex::Regex;
+
+use crate::{
+    config::MiniserveConfig, errors::RuntimeError, file_utils::matches_filters,
+    listing::percent_encode_sets::COMPONENT,
+};
+
+/// How long a generated sitemap is reused before the served directory is walked again
+const SITEMAP_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// The most recently generated sitemap, keyed by the base URL it was generated for
+#[derive(Default)]
+pub struct SitemapCache(Mutex<Option<(Instant, String, String)>>);
+
+impl SitemapCache {
+    /// Drops the cached sitemap, so that the next request walks the served directory again
+    pub fn invalidate(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+/// Handles requests for the sitemap
+pub async fn sitemap(
+    req: HttpRequest,
+    cache: web::Data<SitemapCache>,
+) -> Result<HttpResponse, RuntimeError> {
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    let base_url = match &conf.external_base_url {
+        Some(base) => format!(
+            "{}{}",
+            base.to_string().trim_end_matches('/'),
+            conf.route_prefix
+        ),
+        None => {
+            let info = req.connection_info();
+            format!("{}://{}{}", info.scheme(), info.host(), conf.route_prefix)
+        }
+    };
+
+    let cached = cache
+        .0
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|(created, base, _)| created.elapsed() < SITEMAP_CACHE_TTL && *base == base_url)
+        .map(|(_, _, xml)| xml.clone());
+
+    let xml = match cached {
+        Some(xml) => xml,
+        None => {
+            let root = conf.path.clone();
+            let show_hidden = conf.show_hidden;
+            let no_symlinks = conf.no_symlinks;
+            let filters = conf.filters.clone();
+            let files = web::block(move || {
+                let mut files = Vec::new();
+                let mut visited = HashSet::new();
+                collect_files(
+                    &root,
+                    "",
+                    show_hidden,
+                    no_symlinks,
+                    &filters,
+                    &mut visited,
+                    &mut files,
+                );
+                files
+            })
+            .await
+            .map_err(|e| {
+                RuntimeError::IoError(
+                    "Failed to generate sitemap".to_string(),
+                    std::io::Error::other(e),
+                )
+            })?;
+
+            let xml = render_sitemap(&base_url, files);
+            *cache.0.lock().unwrap() = Some((Instant::now(), base_url, xml.clone()));
+            xml
+        }
+    };
+
+    Ok(HttpResponse::Ok().content_type(mime::TEXT_XML).body(xml))
+}
+
+/// Recursively collects the encoded URL paths and modification dates of all files below `dir`
+fn collect_files(
+    dir: &Path,
+    url_path: &str,
+    show_hidden: bool,
+    no_symlinks: bool,
+    filters: &[Regex],
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<(String, Option<SystemTime>)>,
+) {
+    // Guard against symlink loops
+    if !dir
+        .canonicalize()
+        .is_ok_and(|canonical| visited.insert(canonical))
+    {
+        return;
+    }
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut entries = entries.filter_map(Result::ok).collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !show_hidden && file_name.starts_with('.') {
+            continue;
+        }
+
+        let is_symlink = entry
+            .file_type()
+            .map(|file_type| file_type.is_symlink())
+            .unwrap_or(false);
+        if no_symlinks && is_symlink {
+            continue;
+        }
+
+        let Ok(metadata) = std::fs::metadata(entry.path()) else {
+            continue;
+        };
+        let entry_url = format!("{url_path}/{}", utf8_percent_encode(&file_name, COMPONENT));
+
+        if metadata.is_dir() {
+            collect_files(
+                &entry.path(),
+                &entry_url,
+                show_hidden,
+                no_symlinks,
+                filters,
+                visited,
+                files,
+            );
+        } else if metadata.is_file() && matches_filters(&file_name, filters) {
+            files.push((entry_url, metadata.modified().ok()));
+        }
+    }
+}
+
+/// Renders the sitemap XML document
+fn render_sitemap(base_url: &str, files: Vec<(String, Option<SystemTime>)>) -> String {
+    html! {
+        (PreEscaped(r#"<?xml version="1.0" encoding="UTF-8"?>"#))
+        urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" {
+            @for (url_path, modified) in files {
+                url {
+                    loc { (base_url) (url_path) }
+                    @if let Some(modified) = modified {
+                        lastmod { (DateTime::<Utc>::from(modified).format("%Y-%m-%d")) }
+                    }
+                }
+            }
+        }
+    }
+    .into_string()
+}
@@ -0,0 +1,160 @@
+//! Recursive JSON representation of a served directory tree
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::file_utils::matches_filters;
+
+/// How many directory levels below the requested one are walked at most
+const TREE_MAX_DEPTH: usize = 32;
+
+/// How many entries a single tree contains at most
+const TREE_MAX_NODES: usize = 10_000;
+
+/// Type of a node in the tree
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeNodeType {
+    Directory,
+    File,
+}
+
+/// A file or directory in the tree
+#[derive(Serialize)]
+pub struct TreeNode {
+    name: String,
+
+    #[serde(rename = "type")]
+    node_type: TreeNodeType,
+
+    /// Size of files in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+
+    /// Entries of directories, sorted by name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<TreeNode>>,
+}
+
+/// The full tree below a directory
+#[derive(Serialize)]
+pub struct DirectoryTree {
+    /// Whether some entries were left out because the tree is too deep or too large
+    truncated: bool,
+
+    root: TreeNode,
+}
+
+/// State shared while walking the tree
+struct TreeWalker<'a> {
+    show_hidden: bool,
+    no_symlinks: bool,
+    filters: &'a [Regex],
+    nodes: usize,
+    truncated: bool,
+    visited: HashSet<PathBuf>,
+}
+
+impl DirectoryTree {
+    /// Walks the tree below `dir`, respecting the hidden file, symlink and filter settings
+    pub fn read(dir: &Path, show_hidden: bool, no_symlinks: bool, filters: &[Regex]) -> Self {
+        let mut walker = TreeWalker {
+            show_hidden,
+            no_symlinks,
+            filters,
+            nodes: 0,
+            truncated: false,
+            visited: HashSet::new(),
+        };
+        let root = TreeNode {
+            name: dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            node_type: TreeNodeType::Directory,
+            size: None,
+            children: Some(walker.children(dir, 0)),
+        };
+
+        Self {
+            truncated: walker.truncated,
+            root,
+        }
+    }
+}
+
+impl TreeWalker<'_> {
+    /// Collects the entries of `dir`, recursing into subdirectories
+    fn children(&mut self, dir: &Path, depth: usize) -> Vec<TreeNode> {
+        // Guard against symlink loops
+        if !dir
+            .canonicalize()
+            .is_ok_and(|canonical| self.visited.insert(canonical))
+        {
+            return Vec::new();
+        }
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut entries = entries.filter_map(Result::ok).collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut children = Vec::new();
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !self.show_hidden && name.starts_with('.') {
+                continue;
+            }
+
+            let is_symlink = entry
+                .file_type()
+                .map(|file_type| file_type.is_symlink())
+                .unwrap_or(false);
+            if self.no_symlinks && is_symlink {
+                continue;
+            }
+
+            let Ok(metadata) = std::fs::metadata(entry.path()) else {
+                continue;
+            };
+            if !metadata.is_dir() && !matches_filters(&name, self.filters) {
+                continue;
+            }
+
+            if self.nodes >= TREE_MAX_NODES {
+                self.truncated = true;
+                break;
+            }
+            self.nodes += 1;
+
+            if metadata.is_dir() {
+                let grandchildren = if depth < TREE_MAX_DEPTH {
+                    self.children(&entry.path(), depth + 1)
+                } else {
+                    self.truncated = true;
+                    Vec::new()
+                };
+                children.push(TreeNode {
+                    name,
+                    node_type: TreeNodeType::Directory,
+                    size: None,
+                    children: Some(grandchildren),
+                });
+            } else {
+                children.push(TreeNode {
+                    name,
+                    node_type: TreeNodeType::File,
+                    size: Some(metadata.len()),
+                    children: None,
+                });
+            }
+        }
+
+        children
+    }
+}
@@ -0,0 +1,429 @@
+//! Resumable uploads following the core protocol and the creation extension of tus 1.0
+//!
+//! See <https://tus.io/protocols/resumable-upload> for the protocol.
+
+use std::collections::HashMap;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{error::PayloadError, http::header, web, HttpMessage, HttpRequest, HttpResponse};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::{
+    audit::AuditLog,
+    auth::CurrentUser,
+    config::MiniserveConfig,
+    errors::RuntimeError,
+    file_op::{
+        acquire_upload_permit, check_csrf_token, create_upload_target_dir, ensure_no_symlinks,
+        expected_upload_hash, finish_upload, move_verified_upload, pipe_written_upload, set_mode,
+        upload_file_name, upload_target_dir, FileOpQueryParameters, UploadLimiter,
+    },
+};
+
+/// The only supported version of the protocol
+pub const TUS_VERSION: &str = "1.0.0";
+
+/// Content type of the data appended to uploads
+pub const TUS_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+/// Header announcing the supported versions of the protocol
+pub const TUS_VERSION_HEADER: &str = "Tus-Version";
+
+const TUS_RESUMABLE_HEADER: &str = "Tus-Resumable";
+const TUS_EXTENSION_HEADER: &str = "Tus-Extension";
+const UPLOAD_LENGTH_HEADER: &str = "Upload-Length";
+const UPLOAD_OFFSET_HEADER: &str = "Upload-Offset";
+const UPLOAD_METADATA_HEADER: &str = "Upload-Metadata";
+
+/// Unfinished uploads are discarded after this long without a request resuming them
+const TUS_UPLOAD_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// State of an upload that hasn't been completed yet
+struct TusUpload {
+    /// Directory the file is uploaded to
+    target_dir: PathBuf,
+
+    /// Path the file is moved to once it's complete
+    file_path: PathBuf,
+
+    /// Hidden path the data is written to until the upload is complete
+    partial_path: PathBuf,
+
+    /// Total size of the upload in bytes
+    length: u64,
+
+    /// Number of bytes received so far
+    offset: u64,
+
+    /// Name of the user who created the upload, if any
+    owner: Option<String>,
+
+    /// When the upload was created or last resumed
+    last_activity: Instant,
+
+    /// Held while data is appended, so that only one request at a time appends to the upload
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// Uploads that haven't been completed yet, by their ID
+#[derive(Default)]
+pub struct TusUploads(Mutex<HashMap<String, TusUpload>>);
+
+impl TusUploads {
+    /// Discards the uploads that haven't been resumed for too long and deletes their data
+    async fn remove_expired(&self) {
+        let mut expired = Vec::new();
+        self.0.lock().unwrap().retain(|_, upload| {
+            // Uploads receiving data right now are never stale
+            let is_expired = upload.last_activity.elapsed() > TUS_UPLOAD_EXPIRY
+                && upload.lock.try_lock().is_ok();
+            if is_expired {
+                expired.push(upload.partial_path.clone());
+            }
+            !is_expired
+        });
+
+        for partial_path in expired {
+            if let Err(err) = tokio::fs::remove_file(&partial_path).await {
+                log::warn!("Failed to remove {}: {err}", partial_path.display());
+            }
+        }
+    }
+}
+
+/// Checks whether the client speaks the supported version of the protocol
+fn check_tus_version(req: &HttpRequest) -> Result<(), RuntimeError> {
+    match req.headers().get(TUS_RESUMABLE_HEADER) {
+        Some(version) if version == TUS_VERSION => Ok(()),
+        _ => Err(RuntimeError::TusVersionError),
+    }
+}
+
+/// Parses a numeric header like Upload-Length or Upload-Offset
+fn numeric_header(req: &HttpRequest, name: &str) -> Result<u64, RuntimeError> {
+    req.headers()
+        .get(name)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse().ok())
+        .ok_or_else(|| RuntimeError::InvalidHttpRequestError(format!("Missing or invalid {name}")))
+}
+
+/// Extracts the name of the uploaded file from the Upload-Metadata header, which consists of
+/// comma-separated pairs of keys and base64 encoded values
+fn metadata_file_name(req: &HttpRequest) -> Result<String, RuntimeError> {
+    req.headers()
+        .get(UPLOAD_METADATA_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| pair.trim().split_once(' '))
+        .find(|(key, _)| *key == "filename")
+        .and_then(|(_, value)| BASE64.decode(value).ok())
+        .and_then(|name| String::from_utf8(name).ok())
+        .ok_or_else(|| {
+            RuntimeError::InvalidHttpRequestError(
+                "Upload-Metadata lacks the name of the file".to_string(),
+            )
+        })
+}
+
+/// Returns the name of the user performing the request, if any
+fn current_user_name(req: &HttpRequest) -> Option<String> {
+    req.extensions()
+        .get::<CurrentUser>()
+        .map(|user| user.name.clone())
+}
+
+/// Computes the SHA-256 checksum of the file at `path`
+async fn file_sha256(path: &Path) -> Result<Vec<u8>, RuntimeError> {
+    let read_error = |e| RuntimeError::IoError(format!("Failed to read {}", path.display()), e);
+
+    let mut file = File::open(path).await.map_err(read_error)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buffer).await.map_err(read_error)? {
+            0 => break,
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Moves a complete upload to its final path, verifying it against the upload hash manifest
+async fn complete_upload(
+    upload: TusUpload,
+    conf: &MiniserveConfig,
+    req: &HttpRequest,
+    audit_log: &AuditLog,
+) -> Result<(), RuntimeError> {
+    if !conf.overwrite_files && upload.file_path.exists() {
+        let _ = tokio::fs::remove_file(&upload.partial_path).await;
+        return Err(RuntimeError::DuplicateFileError);
+    }
+
+    let file_name_path = upload
+        .file_path
+        .strip_prefix(&upload.target_dir)
+        .unwrap_or(&upload.file_path);
+    let hash_matches = match expected_upload_hash(file_name_path, conf)? {
+        Some(expected_hash) => file_sha256(&upload.partial_path).await? == expected_hash,
+        None => true,
+    };
+    if let Some(command) = &conf.upload_pipe {
+        pipe_written_upload(command, &upload.partial_path, &upload.file_path).await?;
+    }
+    move_verified_upload(
+        &upload.partial_path,
+        &upload.file_path,
+        hash_matches,
+        conf.overwrite_files,
+    )
+    .await?;
+    set_mode(&upload.file_path, conf.upload_file_mode).await?;
+
+    finish_upload(
+        &upload.file_path,
+        upload.target_dir,
+        upload.length,
+        conf,
+        req,
+        audit_log,
+    )
+    .await
+}
+
+/// Announces the supported version and extensions of the protocol
+pub async fn tus_options() -> HttpResponse {
+    HttpResponse::NoContent()
+        .insert_header((TUS_RESUMABLE_HEADER, TUS_VERSION))
+        .insert_header((TUS_VERSION_HEADER, TUS_VERSION))
+        .insert_header((TUS_EXTENSION_HEADER, "creation"))
+        .finish()
+}
+
+/// Creates an upload of the file named in the Upload-Metadata header in the directory given by
+/// the `path` query parameter
+pub async fn tus_create(
+    req: HttpRequest,
+    query: web::Query<FileOpQueryParameters>,
+    uploads: web::Data<TusUploads>,
+    audit_log: web::Data<AuditLog>,
+) -> Result<HttpResponse, RuntimeError> {
+    check_tus_version(&req)?;
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+    let target_dir = upload_target_dir(&req, &query).await?;
+    check_csrf_token(&req, None)?;
+    create_upload_target_dir(&target_dir, conf).await?;
+
+    if !tokio::fs::metadata(&target_dir)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+    {
+        return Err(RuntimeError::InvalidPathError(format!(
+            "cannot upload file to {}, since it's not a directory",
+            target_dir.display()
+        )));
+    }
+
+    let length = numeric_header(&req, UPLOAD_LENGTH_HEADER)?;
+    if let Some(limit) = conf.max_request_body.filter(|&limit| length > limit) {
+        return Err(RuntimeError::PayloadTooLargeError(limit));
+    }
+    uploads.remove_expired().await;
+
+    // Until it's complete, the upload is written to a hidden file with this suffix
+    let id = nanoid::nanoid!();
+    let partial_suffix = format!(".{id}.part");
+    let file_name_path =
+        upload_file_name(&metadata_file_name(&req)?, conf, 1 + partial_suffix.len())?;
+    ensure_no_symlinks(&target_dir, conf)?;
+    expected_upload_hash(&file_name_path, conf)?;
+
+    let file_path = target_dir.join(&file_name_path);
+    if !conf.overwrite_files && file_path.exists() {
+        return Err(RuntimeError::DuplicateFileError);
+    }
+
+    let file_name = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let partial_path = file_path.with_file_name(format!(".{file_name}{partial_suffix}"));
+    match File::create(&partial_path).await {
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => Err(
+            RuntimeError::InsufficientPermissionsError(target_dir.display().to_string()),
+        ),
+        Err(err) => Err(RuntimeError::IoError(
+            format!("Failed to create {}", file_path.display()),
+            err,
+        )),
+        Ok(_) => Ok(()),
+    }?;
+
+    let upload = TusUpload {
+        target_dir,
+        file_path,
+        partial_path,
+        length,
+        offset: 0,
+        owner: current_user_name(&req),
+        last_activity: Instant::now(),
+        lock: Default::default(),
+    };
+
+    // Empty files are complete right away
+    if length == 0 {
+        complete_upload(upload, conf, &req, &audit_log).await?;
+    } else {
+        uploads.0.lock().unwrap().insert(id.clone(), upload);
+    }
+
+    Ok(HttpResponse::Created()
+        .insert_header((TUS_RESUMABLE_HEADER, TUS_VERSION))
+        .insert_header((
+            header::LOCATION,
+            format!("{}/upload/tus/{id}", conf.route_prefix),
+        ))
+        .finish())
+}
+
+/// Reports how many bytes of an upload have been received
+pub async fn tus_head(
+    req: HttpRequest,
+    id: web::Path<String>,
+    uploads: web::Data<TusUploads>,
+) -> Result<HttpResponse, RuntimeError> {
+    check_tus_version(&req)?;
+
+    let uploads = uploads.0.lock().unwrap();
+    let upload = uploads
+        .get(id.as_str())
+        .filter(|upload| upload.owner == current_user_name(&req))
+        .ok_or_else(|| RuntimeError::RouteNotFoundError(req.path().to_string()))?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((TUS_RESUMABLE_HEADER, TUS_VERSION))
+        .insert_header((UPLOAD_OFFSET_HEADER, upload.offset.to_string()))
+        .insert_header((UPLOAD_LENGTH_HEADER, upload.length.to_string()))
+        .insert_header(header::CacheControl(vec![header::CacheDirective::NoStore]))
+        .finish())
+}
+
+/// Appends the request body to an upload at the offset given by the Upload-Offset header,
+/// completing the upload once all of its bytes have been received
+pub async fn tus_patch(
+    req: HttpRequest,
+    id: web::Path<String>,
+    mut payload: web::Payload,
+    uploads: web::Data<TusUploads>,
+    limiter: web::Data<UploadLimiter>,
+    audit_log: web::Data<AuditLog>,
+) -> Result<HttpResponse, RuntimeError> {
+    check_tus_version(&req)?;
+    let conf = req.app_data::<MiniserveConfig>().unwrap();
+
+    if req.content_type() != TUS_CONTENT_TYPE {
+        return Err(RuntimeError::TusContentTypeError);
+    }
+    let offset = numeric_header(&req, UPLOAD_OFFSET_HEADER)?;
+
+    let not_found = || RuntimeError::RouteNotFoundError(req.path().to_string());
+    let (lock, partial_path, length) = {
+        let mut uploads = uploads.0.lock().unwrap();
+        let upload = uploads
+            .get_mut(id.as_str())
+            .filter(|upload| upload.owner == current_user_name(&req))
+            .ok_or_else(not_found)?;
+        if upload.offset != offset {
+            return Err(RuntimeError::TusOffsetMismatchError(upload.offset));
+        }
+        upload.last_activity = Instant::now();
+        (
+            upload.lock.clone(),
+            upload.partial_path.clone(),
+            upload.length,
+        )
+    };
+
+    // Another request is appending to the upload right now, so its offset is about to change
+    let _guard = lock
+        .try_lock_owned()
+        .map_err(|_| RuntimeError::TusOffsetMismatchError(offset))?;
+
+    // Held until the data is written
+    let _permit = acquire_upload_permit(&limiter).await?;
+
+    let write_error =
+        |e| RuntimeError::IoError(format!("Failed to write to {}", partial_path.display()), e);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(&partial_path)
+        .await
+        .map_err(write_error)?;
+    // Drop data appended by an aborted request
+    file.set_len(offset).await.map_err(write_error)?;
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(write_error)?;
+
+    let mut written = 0;
+    let mut result = Ok(());
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                result = Err(match (err, conf.max_request_body) {
+                    (PayloadError::Overflow, Some(limit)) => {
+                        RuntimeError::PayloadTooLargeError(limit)
+                    }
+                    (err, _) => RuntimeError::InvalidHttpRequestError(err.to_string()),
+                });
+                break;
+            }
+        };
+        if offset + written + chunk.len() as u64 > length {
+            result = Err(RuntimeError::InvalidHttpRequestError(
+                "Upload exceeds its Upload-Length".to_string(),
+            ));
+            break;
+        }
+        if let Err(err) = file.write_all(&chunk).await {
+            result = Err(write_error(err));
+            break;
+        }
+        written += chunk.len() as u64;
+    }
+    file.flush().await.map_err(write_error)?;
+
+    // Data received before a failure is kept, so that the client can resume from there
+    let offset = offset + written;
+    let completed = {
+        let mut uploads = uploads.0.lock().unwrap();
+        let upload = uploads.get_mut(id.as_str()).ok_or_else(not_found)?;
+        upload.offset = offset;
+        upload.last_activity = Instant::now();
+        if offset == length {
+            uploads.remove(id.as_str())
+        } else {
+            None
+        }
+    };
+    result?;
+
+    if let Some(upload) = completed {
+        complete_upload(upload, conf, &req, &audit_log).await?;
+    }
+
+    Ok(HttpResponse::NoContent()
+        .insert_header((TUS_RESUMABLE_HEADER, TUS_VERSION))
+        .insert_header((UPLOAD_OFFSET_HEADER, offset.to_string()))
+        .finish())
+}
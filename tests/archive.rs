@@ -1,10 +1,11 @@
 ﻿mod fixtures;
 
-use fixtures::{server, Error, TestServer};
+use fixtures::{server, Error, TestServer, DEEPLY_NESTED_FILE, DIRECTORIES, FILES, HIDDEN_FILES};
 use reqwest::StatusCode;
 use rstest::rstest;
 use select::document::Document;
 use select::predicate::Text;
+use std::io::{Cursor, Read};
 
 #[rstest]
 fn archives_are_disabled(server: TestServer) -> Result<(), Error> {
@@ -84,3 +85,386 @@ fn archives_are_disabled_when_indexing_disabled(#[case] server: TestServer) -> R
 
     Ok(())
 }
+
+#[rstest]
+#[case(server(&["--enable-tar", "--archive-manifest"]), "tar")]
+#[case(server(&["--enable-tar-gz", "--archive-manifest"]), "tar_gz")]
+fn tar_archives_contain_manifest(
+    #[case] server: TestServer,
+    #[case] method: &str,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url().join(&format!("?download={method}"))?)?
+        .error_for_status()?;
+    let bytes = resp.bytes()?;
+    let reader: Box<dyn Read> = match method {
+        "tar_gz" => Box::new(libflate::gzip::Decoder::new(bytes.as_ref())?),
+        _ => Box::new(bytes.as_ref()),
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    let mut manifest = archive
+        .entries()?
+        .map(Result::unwrap)
+        .find(|entry| entry.path().unwrap().ends_with("MANIFEST.sha256"))
+        .expect("No manifest found in archive");
+    let mut content = String::new();
+    manifest.read_to_string(&mut content)?;
+
+    // sha256 of the fixture file content "Test Hello Yes"
+    let expected_hash = "54216d51bee943d7d9fbc5e1a7e13158c6474bcd862dff77cced6e9cf5adea5b";
+    assert!(content
+        .lines()
+        .any(|line| line == format!("{expected_hash}  {}", FILES[0])));
+    assert!(content
+        .lines()
+        .any(|line| line.ends_with(&format!("  {}", DEEPLY_NESTED_FILE))));
+
+    Ok(())
+}
+
+#[rstest]
+fn head_request_to_archive_returns_headers_only(
+    #[with(&["--enable-tar"])] server: TestServer,
+) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::new();
+    let resp = client
+        .head(server.url().join("?download=tar")?)
+        .send()?
+        .error_for_status()?;
+
+    assert_eq!(resp.headers()["content-type"], "application/tar");
+    assert!(resp.headers()["content-disposition"]
+        .to_str()?
+        .starts_with("attachment; filename="));
+    assert!(resp.bytes()?.is_empty());
+
+    Ok(())
+}
+
+/// Downloads an archive of the served directory and returns the names of all its entries
+fn archive_entry_names(server: &TestServer, method: &str) -> Result<Vec<String>, Error> {
+    let bytes = reqwest::blocking::get(server.url().join(&format!("?download={method}"))?)?
+        .error_for_status()?
+        .bytes()?;
+
+    let names = match method {
+        "zip" => {
+            let archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+            archive.file_names().map(str::to_owned).collect()
+        }
+        _ => {
+            let reader: Box<dyn Read> = match method {
+                "tar_gz" => Box::new(libflate::gzip::Decoder::new(bytes.as_ref())?),
+                _ => Box::new(bytes.as_ref()),
+            };
+            tar::Archive::new(reader)
+                .entries()?
+                .map(|entry| Ok(entry?.path()?.to_string_lossy().into_owned()))
+                .collect::<Result<_, Error>>()?
+        }
+    };
+
+    Ok(names)
+}
+
+#[rstest]
+#[case("tar", false)]
+#[case("tar", true)]
+#[case("tar_gz", true)]
+#[case("zip", false)]
+#[case("zip", true)]
+fn archives_can_skip_empty_directories(
+    #[case] method: &str,
+    #[case] skip_empty_dirs: bool,
+) -> Result<(), Error> {
+    let mut args = vec!["--enable-tar", "--enable-tar-gz", "--enable-zip"];
+    if skip_empty_dirs {
+        args.push("--archive-skip-empty-dirs");
+    }
+    let server = server(&args);
+    std::fs::create_dir_all(server.path().join("empty_dir/nested_empty_dir"))?;
+
+    let names = archive_entry_names(&server, method)?;
+    let contains = |name: &str| {
+        names
+            .iter()
+            .any(|entry| entry.trim_end_matches('/').ends_with(name))
+    };
+
+    assert_eq!(contains("/empty_dir"), !skip_empty_dirs);
+    assert_eq!(contains("/empty_dir/nested_empty_dir"), !skip_empty_dirs);
+    for &directory in DIRECTORIES {
+        assert!(contains(&format!("/{}", directory.trim_end_matches('/'))));
+    }
+    for &file in FILES {
+        assert!(contains(&format!("/{file}")));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+#[case("tar")]
+#[case("tar_gz")]
+#[case("zip")]
+fn archives_only_contain_files_matching_the_filter(#[case] method: &str) -> Result<(), Error> {
+    let server = server(&[
+        "--enable-tar",
+        "--enable-tar-gz",
+        "--enable-zip",
+        "--filter",
+        "*.rs",
+    ]);
+
+    let names = archive_entry_names(&server, method)?;
+    assert!(names.iter().any(|name| name.ends_with(DEEPLY_NESTED_FILE)));
+    for &file in FILES {
+        assert!(names
+            .iter()
+            .all(|name| !name.ends_with(&format!("/{file}"))));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+#[case("tar", false)]
+#[case("tar", true)]
+#[case("tar_gz", true)]
+#[case("zip", false)]
+#[case("zip", true)]
+fn archives_can_be_flat(#[case] method: &str, #[case] flat: bool) -> Result<(), Error> {
+    let mut args = vec!["--enable-tar", "--enable-tar-gz", "--enable-zip"];
+    if flat {
+        args.push("--archive-flat");
+    }
+    let server = server(&args);
+    let root_folder = server
+        .path()
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+
+    let names = archive_entry_names(&server, method)?;
+    assert!(!names.is_empty());
+    assert_eq!(
+        names.iter().all(|name| name.starts_with(&root_folder)),
+        !flat
+    );
+
+    let prefix = if flat {
+        String::new()
+    } else {
+        format!("{root_folder}/")
+    };
+    assert!(names.contains(&format!("{prefix}{}", FILES[0])));
+    assert!(names.contains(&format!("{prefix}{DEEPLY_NESTED_FILE}")));
+
+    Ok(())
+}
+
+#[rstest]
+#[case("tar")]
+#[case("zip")]
+fn cached_archives_support_range_requests(#[case] method: &str) -> Result<(), Error> {
+    let cache_dir = assert_fs::TempDir::new()?;
+    let server = server(&[
+        "--enable-tar",
+        "--enable-zip",
+        "--archive-cache-dir",
+        cache_dir.path().to_str().unwrap(),
+    ]);
+    let url = server.url().join(&format!("?download={method}"))?;
+    let client = reqwest::blocking::Client::new();
+
+    // The first download creates the cached archive
+    let archive = client
+        .get(url.clone())
+        .send()?
+        .error_for_status()?
+        .bytes()?;
+    assert_eq!(std::fs::read_dir(cache_dir.path())?.count(), 1);
+
+    // The second one is served from the cache and can be resumed
+    let resp = client
+        .get(url)
+        .header("Range", "bytes=100-")
+        .send()?
+        .error_for_status()?;
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert!(resp.headers()["content-disposition"]
+        .to_str()?
+        .starts_with("attachment; filename="));
+    assert_eq!(resp.bytes()?, archive.slice(100..));
+
+    Ok(())
+}
+
+#[rstest]
+fn cached_archives_are_refreshed_when_nested_files_change() -> Result<(), Error> {
+    let cache_dir = assert_fs::TempDir::new()?;
+    let server = server(&[
+        "--enable-tar",
+        "--archive-cache-dir",
+        cache_dir.path().to_str().unwrap(),
+    ]);
+    let url = server.url().join("?download=tar")?;
+
+    reqwest::blocking::get(url.clone())?
+        .error_for_status()?
+        .bytes()?;
+
+    // Changing a nested file doesn't touch the modification time of the served directory
+    std::fs::write(server.path().join(DEEPLY_NESTED_FILE), "changed content")?;
+    let archive = reqwest::blocking::get(url)?.error_for_status()?.bytes()?;
+    assert_eq!(std::fs::read_dir(cache_dir.path())?.count(), 2);
+
+    let mut entries = tar::Archive::new(archive.as_ref());
+    let mut nested_file = entries
+        .entries()?
+        .filter_map(Result::ok)
+        .find(|entry| entry.path().is_ok_and(|p| p.ends_with(DEEPLY_NESTED_FILE)))
+        .expect("Archive doesn't contain the nested file");
+    let mut content = String::new();
+    nested_file.read_to_string(&mut content)?;
+    assert_eq!(content, "changed content");
+
+    Ok(())
+}
+
+#[rstest]
+fn only_plain_tarballs_are_compressed(
+    #[with(&["-r", "-g", "-z", "--compress-response"])] server: TestServer,
+) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::new();
+
+    for (download, magic) in [("tar_gz", &b"\x1f\x8b"[..]), ("zip", &b"PK"[..])] {
+        let resp = client
+            .get(server.url().join(&format!("?download={download}"))?)
+            .header("Accept-Encoding", "gzip")
+            .send()?
+            .error_for_status()?;
+        assert_eq!(resp.headers()["content-encoding"], "identity");
+        assert!(resp.bytes()?.starts_with(magic));
+    }
+
+    let resp = client
+        .get(server.url().join("?download=tar")?)
+        .header("Accept-Encoding", "gzip")
+        .send()?
+        .error_for_status()?;
+    assert_eq!(resp.headers()["content-encoding"], "gzip");
+    let mut tarball = Vec::new();
+    libflate::gzip::Decoder::new(resp.bytes()?.as_ref())?.read_to_end(&mut tarball)?;
+    let mut entries = tar::Archive::new(Cursor::new(tarball));
+    assert!(entries
+        .entries()?
+        .any(|entry| entry.is_ok_and(|e| e.path().is_ok_and(|p| p.ends_with(FILES[0])))));
+
+    Ok(())
+}
+
+#[rstest]
+fn flat_zip_contains_only_top_level_files(
+    #[with(&["--enable-flat-zip"])] server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed
+        .find(Text)
+        .any(|x| x.text() == "Download all files (flat .zip)"));
+
+    // Only the flat zip is enabled, not the regular one
+    assert_eq!(
+        reqwest::blocking::get(server.url().join("?download=zip")?)?.status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        reqwest::blocking::get(server.url().join("?download-flat=tar")?)?.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let resp = reqwest::blocking::get(server.url().join("?download-flat=zip")?)?;
+    let mut archive = zip::ZipArchive::new(Cursor::new(resp.error_for_status()?.bytes()?))?;
+    let mut names: Vec<_> = archive.file_names().map(str::to_string).collect();
+    names.sort();
+    // Like regular archives, flat ones include hidden files
+    let mut expected: Vec<_> = FILES
+        .iter()
+        .chain(HIDDEN_FILES)
+        .map(|file| file.to_string())
+        .collect();
+    expected.sort();
+    assert_eq!(names, expected);
+
+    let mut content = String::new();
+    archive.by_name(FILES[0])?.read_to_string(&mut content)?;
+    assert_eq!(content, "Test Hello Yes");
+
+    Ok(())
+}
+
+#[rstest]
+fn root_archive_can_be_disabled(
+    #[with(&["-g", "--no-root-archive"])] server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed.find(Text).all(|x| x.text() != "Download .tar.gz"));
+    assert_eq!(
+        reqwest::blocking::get(server.url().join("?download=tar_gz")?)?.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    // Subdirectories can still be downloaded
+    let subdir = server.url().join(DIRECTORIES[0])?;
+    let body = reqwest::blocking::get(subdir.clone())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed.find(Text).any(|x| x.text() == "Download .tar.gz"));
+    assert_eq!(
+        reqwest::blocking::get(subdir.join("?download=tar_gz")?)?.status(),
+        StatusCode::OK
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn zip_archives_above_memory_limit_are_created(
+    #[with(&["-z", "--archive-memory-limit", "1KB"])] server: TestServer,
+) -> Result<(), Error> {
+    let content = vec![b'x'; 64 * 1024];
+    std::fs::write(
+        server.path().join(DIRECTORIES[0]).join("large.bin"),
+        &content,
+    )?;
+
+    let resp = reqwest::blocking::get(server.url().join(DIRECTORIES[0])?.join("?download=zip")?)?;
+    let mut archive = zip::ZipArchive::new(Cursor::new(resp.error_for_status()?.bytes()?))?;
+    let large_file = archive
+        .file_names()
+        .find(|name| name.ends_with("large.bin"))
+        .map(str::to_string)
+        .expect("large.bin is missing");
+    let mut zipped = Vec::new();
+    archive.by_name(&large_file)?.read_to_end(&mut zipped)?;
+    assert_eq!(zipped, content);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+fn selective_tar_archives_skip_symlink_loops() -> Result<(), Error> {
+    let server = server(&["--enable-tar", "--archive-skip-empty-dirs"]);
+    std::os::unix::fs::symlink("..", server.path().join(DIRECTORIES[0]).join("loop"))?;
+
+    let names = archive_entry_names(&server, "tar")?;
+    for &file in FILES {
+        assert!(names.iter().any(|name| name.ends_with(&format!("/{file}"))));
+    }
+    assert!(names.iter().all(|name| !name.contains("/loop/")));
+
+    Ok(())
+}
@@ -177,3 +177,50 @@ fn auth_multiple_accounts_wrong_password(username: &str, password: &str) -> Resu
 
     Ok(())
 }
+
+#[rstest]
+fn auth_locks_out_after_too_many_failures() -> Result<(), Error> {
+    let server = server_no_stderr(&["-a", "testuser:testpassword", "--auth-max-attempts", "3"]);
+    let client = Client::new();
+    let status = |password: &str| -> Result<StatusCode, Error> {
+        Ok(client
+            .get(server.url())
+            .basic_auth("testuser", Some(password))
+            .send()?
+            .status())
+    };
+
+    for _ in 0..3 {
+        assert_eq!(status("wrongpassword")?, StatusCode::UNAUTHORIZED);
+    }
+
+    // Even the right password is rejected during the lockout
+    assert_eq!(status("wrongpassword")?, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(status("testpassword")?, StatusCode::TOO_MANY_REQUESTS);
+
+    Ok(())
+}
+
+#[rstest]
+fn concurrent_authenticated_requests_are_not_locked_out() -> Result<(), Error> {
+    let server = server_no_stderr(&["-a", "testuser:testpassword", "--auth-max-attempts", "1"]);
+
+    // Only failed attempts count, so requests with the right password never hit the limit
+    std::thread::scope(|scope| {
+        let requests = (0..8)
+            .map(|_| {
+                scope.spawn(|| {
+                    Client::new()
+                        .get(server.url())
+                        .basic_auth("testuser", Some("testpassword"))
+                        .send()
+                        .map(|resp| resp.status())
+                })
+            })
+            .collect::<Vec<_>>();
+        for request in requests {
+            assert_eq!(request.join().unwrap()?, StatusCode::OK);
+        }
+        Ok(())
+    })
+}
@@ -64,3 +64,33 @@ fn auth_file_rejects(
 
     Ok(())
 }
+
+#[rstest(
+    client_username,
+    client_password,
+    case("joe", "123"),
+    case("bob", "123"),
+    case("bob", "wrongpassword")
+)]
+fn auth_file_env_authenticates(client_username: &str, client_password: &str) -> Result<(), Error> {
+    // Only read by miniserve because it's passed as argument, so other tests are unaffected
+    std::env::set_var(
+        "MINISERVE_TEST_AUTH_USERS",
+        "joe:123\n\n  bob:sha256:a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3  \n",
+    );
+    let server = server_no_stderr(&["--auth-file-env", "MINISERVE_TEST_AUTH_USERS"]);
+    let status = Client::new()
+        .get(server.url())
+        .basic_auth(client_username, Some(client_password))
+        .send()?
+        .status();
+
+    let expected = if client_password == "123" {
+        StatusCode::OK
+    } else {
+        StatusCode::UNAUTHORIZED
+    };
+    assert_eq!(status, expected);
+
+    Ok(())
+}
@@ -5,8 +5,10 @@ use assert_fs::fixture::TempDir;
 use fixtures::{port, server, tmpdir, Error, TestServer};
 use regex::Regex;
 use rstest::rstest;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
 use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
 #[rstest]
 #[case(&["-i", "12.123.234.12"])]
@@ -87,3 +89,65 @@ fn validate_printed_urls(tmpdir: TempDir, port: u16, #[case] args: &[&str]) -> R
 
     Ok(())
 }
+
+#[rstest]
+fn only_display_interfaces_are_printed(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args(["-i", "0.0.0.0", "--display-interface", "127.0.0.1"])
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // WARN assumes urls list is terminated by an empty line
+    let url_lines = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(|line| line.expect("Error reading stdout"))
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let urls = Regex::new(r"http://[a-zA-Z0-9\.\[\]:/]+")
+        .unwrap()
+        .captures_iter(url_lines.as_str())
+        .map(|caps| caps.get(0).unwrap().as_str())
+        .collect::<Vec<_>>();
+
+    assert_eq!(urls, vec![format!("http://127.0.0.1:{port}")]);
+
+    child.kill()?;
+
+    Ok(())
+}
+
+#[rstest]
+fn quiet_suppresses_startup_output(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--quiet")
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // The server still serves, even though it doesn't announce where
+    let start_wait = Instant::now();
+    let url = format!("http://127.0.0.1:{port}");
+    while reqwest::blocking::get(&url).is_err() {
+        assert!(start_wait.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(100));
+    }
+    reqwest::blocking::get(&url)?.error_for_status()?;
+
+    child.kill()?;
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout)?;
+
+    assert!(!stdout.contains("miniserve v"));
+    assert!(!stdout.contains("Bound to"));
+    assert!(!stdout.contains("Serving path"));
+    assert!(!stdout.contains("Available at"));
+
+    Ok(())
+}
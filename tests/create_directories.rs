@@ -2,6 +2,7 @@ mod fixtures;
 
 use fixtures::{server, Error, TestServer, DIRECTORIES};
 use reqwest::blocking::{multipart, Client};
+use reqwest::StatusCode;
 use rstest::rstest;
 use select::document::Document;
 use select::predicate::{Attr, Text};
@@ -161,3 +162,24 @@ fn prevent_path_transversal_attacks(
 
     Ok(())
 }
+
+/// Creating an existing directory succeeds unless --mkdir-error-on-exists is set.
+#[rstest]
+#[case(server(&["--upload-files", "--mkdir"]), StatusCode::OK)]
+#[case(server(&["--upload-files", "--mkdir", "--mkdir-error-on-exists"]), StatusCode::CONFLICT)]
+fn creating_existing_directories(
+    #[case] server: TestServer,
+    #[case] expected: StatusCode,
+) -> Result<(), Error> {
+    let existing_directory = DIRECTORIES[0].trim_end_matches('/');
+    assert!(server.path().join(existing_directory).is_dir());
+
+    let form = multipart::Form::new().text("mkdir", existing_directory);
+    let resp = Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?;
+    assert_eq!(resp.status(), expected);
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+mod fixtures;
+
+use assert_cmd::Command;
+use fixtures::{server, Error, TestServer, FILES};
+use predicates::str::contains;
+use reqwest::StatusCode;
+use rstest::rstest;
+
+#[rstest]
+#[case(server(&["--route", "/ping=200:pong"]), "ping", StatusCode::OK, "pong")]
+#[case(server(&["--route", "/health=202:"]), "health", StatusCode::ACCEPTED, "")]
+#[case(server(&["--route", "/ping=200:pong", "--route-prefix", "foo"]), "foo/ping", StatusCode::OK, "pong")]
+fn custom_route_returns_configured_response(
+    #[case] server: TestServer,
+    #[case] path: &str,
+    #[case] status: StatusCode,
+    #[case] body: &str,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url().join(path)?)?;
+
+    assert_eq!(resp.status(), status);
+    assert_eq!(resp.text()?, body);
+
+    Ok(())
+}
+
+#[rstest]
+fn custom_route_takes_precedence_over_files(
+    #[with(&["--route", &format!("/{}=200:pong", FILES[0])])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url().join(FILES[0])?)?.error_for_status()?;
+
+    assert_eq!(resp.text()?, "pong");
+
+    Ok(())
+}
+
+#[rstest]
+#[case("/upload")]
+#[case("/api")]
+#[case("/sitemap.xml")]
+#[case("/upload/tus/abc")]
+#[case("/__miniserve_internal/version")]
+fn custom_route_cannot_shadow_internal_routes(#[case] path: &str) -> Result<(), Error> {
+    Command::cargo_bin("miniserve")?
+        .args(["--route", &format!("{path}=200:x")])
+        .assert()
+        .failure()
+        .stderr(contains(format!(
+            "Custom route {path} conflicts with an internal route"
+        )));
+
+    Ok(())
+}
@@ -2,3 +2,5 @@
 openssl req -subj '/CN=localhost' -x509 -newkey rsa:4096 -keyout key_pkcs8.pem -out cert_rsa.pem -nodes -days 3650
 openssl rsa -in key_pkcs8.pem -out key_pkcs1.pem
 openssl req -subj '/CN=localhost' -x509 -nodes -newkey ec -pkeyopt ec_paramgen_curve:secp384r1 -keyout key_ec.pem -out cert_ec.pem -days 3650
+openssl req -subj '/CN=one.localhost' -addext 'subjectAltName=DNS:one.localhost' -x509 -nodes -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -keyout key_sni_one.pem -out cert_sni_one.pem -days 3650
+openssl req -subj '/CN=two.localhost' -addext 'subjectAltName=DNS:two.localhost' -x509 -nodes -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -keyout key_sni_two.pem -out cert_sni_two.pem -days 3650
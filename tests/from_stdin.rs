@@ -0,0 +1,74 @@
+mod fixtures;
+
+use assert_cmd::prelude::*;
+use fixtures::{port, Error};
+use reqwest::StatusCode;
+use rstest::rstest;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+#[rstest]
+fn serves_stdin_as_a_single_file(port: u16) -> Result<(), Error> {
+    let content = (0..=255u8).cycle().take(100_000).collect::<Vec<_>>();
+
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg("--from-stdin")
+        .arg("--download-name")
+        .arg("data.bin")
+        .arg("-p")
+        .arg(port.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    // The server only starts once stdin is closed
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(&content)?;
+    drop(stdin);
+
+    let url = format!("http://localhost:{port}");
+    let start_wait = Instant::now();
+    let resp = loop {
+        match reqwest::blocking::get(&url) {
+            Ok(resp) => break resp,
+            Err(_) => {
+                assert!(start_wait.elapsed() < Duration::from_secs(2));
+                sleep(Duration::from_millis(100));
+            }
+        }
+    };
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers()["content-disposition"]
+        .to_str()?
+        .contains("data.bin"));
+    assert_eq!(resp.bytes()?.as_ref(), content.as_slice());
+
+    // Uploads aren't available
+    let resp = reqwest::blocking::Client::new()
+        .post(format!("{url}/upload?path=/"))
+        .send()?;
+    assert!(resp.status().is_client_error());
+
+    child.kill()?;
+    child.wait()?;
+
+    Ok(())
+}
+
+#[rstest]
+#[case(&["--from-stdin", "-u"])]
+#[case(&["--from-stdin", "some/path"])]
+#[case(&["--from-stdin", "--download-name", "../data.bin"])]
+#[case(&["--download-name", "data.bin"])]
+fn from_stdin_rejects_invalid_args(#[case] args: &[&str]) -> Result<(), Error> {
+    Command::cargo_bin("miniserve")?
+        .args(args)
+        .stdin(Stdio::null())
+        .assert()
+        .failure();
+
+    Ok(())
+}
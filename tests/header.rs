@@ -1,6 +1,6 @@
 mod fixtures;
 
-use fixtures::{server, Error};
+use fixtures::{server, Error, TestServer};
 use rstest::rstest;
 
 #[rstest(headers,
@@ -20,3 +20,49 @@ fn custom_header_set(headers: Vec<String>) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[rstest]
+fn request_ids_are_returned(
+    #[with(&["--request-id-header", "X-Request-Id"])] server: TestServer,
+) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::new();
+
+    // IDs are generated for requests without one, and differ between requests
+    let first = client.get(server.url()).send()?;
+    let second = client.get(server.url().join("does-not-exist")?).send()?;
+    let first = first.headers()["x-request-id"].to_str()?;
+    let second = second.headers()["x-request-id"].to_str()?;
+    assert!(!first.is_empty());
+    assert_ne!(first, second);
+
+    let resp = client
+        .get(server.url())
+        .header("X-Request-Id", "from-the-proxy")
+        .send()?;
+    assert_eq!(resp.headers()["x-request-id"], "from-the-proxy");
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&["--server-header", "myserver"]), Some("myserver"))]
+#[case(server(&["--header", "Server: custom", "--server-header", "myserver"]), Some("myserver"))]
+#[case(server(&["--header", "Server: custom", "--no-server-header"]), None)]
+fn server_header_can_be_configured(
+    #[case] server: TestServer,
+    #[case] expected: Option<&str>,
+) -> Result<(), Error> {
+    // Error responses carry the configured header as well
+    for path in ["", "does-not-exist"] {
+        let resp = reqwest::blocking::get(server.url().join(path)?)?;
+        assert_eq!(
+            resp.headers()
+                .get("server")
+                .map(|value| value.to_str())
+                .transpose()?,
+            expected
+        );
+    }
+
+    Ok(())
+}
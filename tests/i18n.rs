@@ -0,0 +1,33 @@
+mod fixtures;
+
+use fixtures::{server, Error, TestServer};
+use reqwest::header::ACCEPT_LANGUAGE;
+use rstest::rstest;
+use select::{document::Document, node::Node};
+
+#[rstest]
+#[case(server(&["--lang", "de"]), None, "Letzte Änderung")]
+#[case(server(&["--lang", "fr"]), None, "Dernière modification")]
+#[case(server(None::<&str>), Some("de-DE,de;q=0.9"), "Last modification")]
+#[case(server(&["--follow-accept-language"]), Some("de-DE,de;q=0.9"), "Letzte Änderung")]
+#[case(server(&["--follow-accept-language", "--lang", "fr"]), Some("es"), "Dernière modification")]
+fn listing_is_translated(
+    #[case] server: TestServer,
+    #[case] accept_language: Option<&str>,
+    #[case] expected: &str,
+) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::new();
+    let mut req = client.get(server.url());
+    if let Some(accept_language) = accept_language {
+        req = req.header(ACCEPT_LANGUAGE, accept_language);
+    }
+    let body = req.send()?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    assert!(parsed
+        .find(|x: &Node| x.name() == Some("a") && x.text() == expected)
+        .next()
+        .is_some());
+
+    Ok(())
+}
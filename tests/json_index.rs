@@ -0,0 +1,52 @@
+mod fixtures;
+
+use fixtures::{server, Error, TestServer, DIRECTORIES, FILES};
+use reqwest::StatusCode;
+use rstest::rstest;
+use serde_json::Value;
+
+#[rstest]
+fn listings_link_to_their_json_index(
+    #[with(&["--emit-json-index"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let link = resp.headers()["link"].to_str()?;
+    assert!(link.contains(r#"rel="alternate""#));
+    assert!(link.contains(r#"type="application/json""#));
+
+    let target = link
+        .strip_prefix('<')
+        .and_then(|link| link.split_once('>'))
+        .map(|(target, _)| target)
+        .expect("Link header has no target");
+    let resp = reqwest::blocking::get(server.url().join(target)?)?.error_for_status()?;
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    let index: Value = serde_json::from_str(&resp.text()?)?;
+
+    assert_eq!(index["complete"], true);
+    let entries = index["entries"].as_array().expect("entries are missing");
+    let entry = |name: &str| entries.iter().find(|entry| entry["name"] == name);
+    for file in FILES {
+        assert_eq!(entry(file).expect("file is missing")["type"], "file");
+    }
+    for dir in DIRECTORIES {
+        let name = dir.trim_end_matches('/');
+        assert_eq!(
+            entry(name).expect("directory is missing")["type"],
+            "directory"
+        );
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn json_index_disabled_by_default(server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url())?.error_for_status()?;
+    assert!(resp.headers().get("link").is_none());
+
+    let status = reqwest::blocking::get(server.url().join("?format=json")?)?.status();
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    Ok(())
+}
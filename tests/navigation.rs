@@ -186,3 +186,87 @@ fn can_specify_default_sorting_order(
 
     Ok(())
 }
+
+#[rstest]
+fn listings_are_limited_to_max_depth(
+    #[with(&["--max-depth", "2"])] server: TestServer,
+) -> Result<(), Error> {
+    let status = reqwest::blocking::get(server.url().join("very/deeply/")?)?.status();
+    assert_eq!(status, reqwest::StatusCode::OK);
+
+    let status = reqwest::blocking::get(server.url().join("very/deeply/nested/")?)?.status();
+    assert_eq!(status, reqwest::StatusCode::FORBIDDEN);
+
+    // Files in deeper directories can still be downloaded
+    let status = reqwest::blocking::get(server.url().join(DEEPLY_NESTED_FILE)?)?.status();
+    assert_eq!(status, reqwest::StatusCode::OK);
+
+    Ok(())
+}
+
+#[rstest]
+/// Links are relative to the listed directory with --relative-links.
+fn links_are_relative_if_enabled(
+    #[with(&["--relative-links", "--title", "root"])] server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    for &directory in DIRECTORIES {
+        let link = get_link_from_text(&parsed, directory).expect("Dir not found.");
+        assert_eq!(link, directory);
+    }
+    assert_eq!(
+        get_link_from_text(&parsed, "test.txt").expect("File not found."),
+        "test.txt"
+    );
+
+    let nested_url = server.url().join("very/deeply/nested/")?;
+    let body = reqwest::blocking::get(nested_url.as_str())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    for (name, expected) in [("root", "../../../"), ("very", "../../"), ("deeply", "../")] {
+        let link = get_link_from_text(&parsed, name).expect("Breadcrumb not found.");
+        assert_eq!(link, expected);
+    }
+    assert_eq!(
+        nested_url.join("../../../")?.as_str(),
+        server.url().as_str()
+    );
+
+    Ok(())
+}
+
+#[rstest]
+/// Chains of single-child directories are collapsed with --collapse-single-dirs.
+fn single_child_dirs_are_collapsed_if_enabled(
+    #[with(&["--collapse-single-dirs"])] server: TestServer,
+) -> Result<(), Error> {
+    let nested_dir = DEEPLY_NESTED_FILE.rsplit_once('/').unwrap().0;
+
+    // The listing jumps straight to the deepest directory of the chain
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let link = get_link_from_text(&parsed, &format!("{nested_dir}/"))
+        .expect("Collapsed dir link not found.");
+    assert_eq!(link, format!("/{nested_dir}/"));
+    assert_eq!(get_link_from_text(&parsed, "very/"), None);
+
+    // Other directories aren't affected
+    for &directory in DIRECTORIES {
+        assert_eq!(
+            get_link_from_text(&parsed, directory),
+            Some(format!("/{directory}"))
+        );
+    }
+
+    // The breadcrumbs show the chain as a single path
+    let nested_url = server.url().join(&format!("{nested_dir}/"))?;
+    let body = reqwest::blocking::get(nested_url)?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed
+        .find(select::predicate::Name("bdi"))
+        .any(|x| x.text() == nested_dir));
+    assert_eq!(get_link_from_text(&parsed, "very"), None);
+    assert_eq!(get_link_from_text(&parsed, "deeply"), None);
+
+    Ok(())
+}
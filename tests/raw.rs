@@ -7,8 +7,8 @@ use pretty_assertions::assert_eq;
 use reqwest::blocking::Client;
 use rstest::rstest;
 use select::document::Document;
-use select::predicate::Class;
 use select::predicate::Name;
+use select::predicate::{Attr, Class, Predicate};
 
 /// The footer displays the correct wget command to download the folder recursively
 // This test can't test all aspects of the wget footer,
@@ -61,6 +61,49 @@ fn ui_displays_wget_element(
     Ok(())
 }
 
+/// The wget footer, the breadcrumbs and other links use the external base URL if it's configured
+#[rstest]
+fn ui_uses_external_base_url(
+    #[with(&["-W", "-u", "--mkdir", "--external-base-url", "https://example.com/files"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let body = Client::new()
+        .get(server.url().join("very/deeply/")?)
+        .send()?
+        .error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    let wget_url = parsed
+        .find(Class("downloadDirectory").descendant(Class("cmd")))
+        .next()
+        .unwrap()
+        .text();
+    assert_eq!(
+        wget_url,
+        "wget -rcnHp -R 'index.html*' --cut-dirs=2 'https://example.com/files/very/deeply/?raw=true'"
+    );
+
+    let breadcrumbs: Vec<_> = parsed
+        .find(Class("title").descendant(Name("a")))
+        .filter_map(|a| a.attr("href"))
+        .collect();
+    assert_eq!(breadcrumbs, ["/files/", "/files/very/"]);
+
+    let title = parsed.find(Class("title")).next().unwrap().text();
+    assert!(title.starts_with("example.com"));
+
+    // So do the stylesheet, the favicon and the upload forms
+    for link in parsed.find(Name("link")) {
+        assert!(link.attr("href").unwrap().starts_with("/files/"));
+    }
+    assert_eq!(parsed.find(Name("form")).count(), 2);
+    for form in parsed.find(Name("form")) {
+        assert!(form.attr("action").unwrap().starts_with("/files/upload?"));
+    }
+
+    Ok(())
+}
+
 /// All hrefs in raw mode are links to directories or files & directories end with ?raw=true
 #[rstest(
     dir,
@@ -104,3 +147,47 @@ fn raw_mode_links_to_directories_end_with_raw_true(
 
     Ok(())
 }
+
+/// With --lazy-listing, only the first batch is rendered and the rest is available in pages
+#[rstest]
+fn lazy_listing_is_served_in_batches(
+    #[with(&["--lazy-listing", "3"])] server: TestServer,
+) -> Result<(), Error> {
+    fn entry_names(parsed: &Document) -> Vec<String> {
+        parsed
+            .find(Name("tbody").descendant(Name("a")))
+            .filter(|node| node.attr("class") != Some("root"))
+            .filter_map(|node| node.attr("href"))
+            .map(str::to_owned)
+            .collect()
+    }
+
+    let client = Client::new();
+
+    let body = client.get(server.url()).send()?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let first_batch = entry_names(&parsed);
+    assert_eq!(first_batch.len(), 3);
+    let next_page = parsed
+        .find(Attr("id", "lazy-listing"))
+        .next()
+        .and_then(|node| node.attr("data-next-page"));
+    assert_eq!(next_page, Some("1"));
+
+    let body = client
+        .get(server.url().join("?page=1&raw=true")?)
+        .send()?
+        .error_for_status()?;
+    let second_batch = entry_names(&Document::from_read(body)?);
+    assert_eq!(second_batch.len(), 3);
+    assert!(second_batch.iter().all(|name| !first_batch.contains(name)));
+
+    // Raw listings without a page are still complete
+    let body = client
+        .get(server.url().join("?raw=true")?)
+        .send()?
+        .error_for_status()?;
+    assert!(entry_names(&Document::from_read(body)?).len() > 6);
+
+    Ok(())
+}
@@ -2,7 +2,7 @@ mod fixtures;
 
 use fixtures::{server, Error, TestServer, DIRECTORIES, FILES};
 use rstest::rstest;
-use select::predicate::Attr;
+use select::predicate::{Attr, Class, Name};
 use select::{document::Document, node::Node};
 use std::fs::{remove_file, File};
 use std::io::Write;
@@ -125,3 +125,73 @@ fn show_nested_readme_contents(
     }
     Ok(())
 }
+
+/// Show the description file above the listing table, escaping HTML unless it's trusted
+#[rstest]
+#[case(server(&["--descriptions"]), ".description.txt", false)]
+#[case(server(&["--descriptions"]), ".description.html", false)]
+#[case(server(&["--descriptions", "--trust-description-html"]), ".description.html", true)]
+fn show_description_above_listing(
+    #[case] server: TestServer,
+    #[case] description_name: &str,
+    #[case] rendered_as_html: bool,
+) -> Result<(), Error> {
+    std::fs::write(
+        server.path().join(description_name),
+        "Annotated <b>drop</b>",
+    )?;
+
+    let body = reqwest::blocking::get(server.url())?
+        .error_for_status()?
+        .text()?;
+    let description_pos = body
+        .find(r#"<div class="description">"#)
+        .expect("Description is missing");
+    assert!(description_pos < body.find("<table>").unwrap());
+
+    let parsed = Document::from(body.as_str());
+    let description = parsed.find(Class("description")).next().unwrap();
+    assert_eq!(
+        description.find(Name("b")).next().is_some(),
+        rendered_as_html
+    );
+    if !rendered_as_html {
+        assert_eq!(description.text(), "Annotated <b>drop</b>");
+    }
+
+    Ok(())
+}
+
+/// Show a generated readme in directories without one
+#[rstest]
+fn show_auto_readme(
+    #[with(&["--auto-readme", "Directory {dir} holds {file_count} files of {total_size}"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let dir = server.path().join("generated");
+    std::fs::create_dir(&dir)?;
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        std::fs::write(dir.join(name), "1234")?;
+    }
+    std::fs::create_dir(dir.join("subdir"))?;
+
+    let body = reqwest::blocking::get(server.url().join("generated/")?)?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let contents = parsed
+        .find(Attr("id", "readme-contents"))
+        .next()
+        .expect("No generated readme")
+        .text();
+    assert_eq!(
+        contents.trim(),
+        "Directory /generated holds 3 files of 12 B"
+    );
+
+    // Actual readmes take precedence
+    write_readme_contents(dir, "README.md");
+    let body = reqwest::blocking::get(server.url().join("generated/")?)?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert_readme_contents(&parsed, "README.md");
+
+    Ok(())
+}
@@ -2,6 +2,7 @@ mod fixtures;
 
 use assert_cmd::prelude::*;
 use assert_fs::fixture::TempDir;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use fixtures::{
     port, server, server_no_stderr, tmpdir, Error, TestServer, DIRECTORIES, FILES,
     HIDDEN_DIRECTORIES, HIDDEN_FILES,
@@ -9,10 +10,17 @@ use fixtures::{
 use regex::Regex;
 use reqwest::StatusCode;
 use rstest::rstest;
-use select::{document::Document, node::Node, predicate::Attr};
+use select::{
+    document::Document,
+    node::Node,
+    predicate::{Attr, Class, Name, Predicate},
+};
+use sha2::{Digest, Sha256, Sha384};
+use std::io::Read;
+use std::net::TcpStream;
 use std::process::{Command, Stdio};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 #[cfg(unix)]
 use std::os::unix::fs::{symlink as symlink_dir, symlink as symlink_file};
@@ -285,6 +293,46 @@ fn serve_file_instead_of_404_in_pretty_urls_mode(
     Ok(())
 }
 
+#[rstest]
+fn serves_not_found_page_in_static_site_mode(
+    #[with(&["--static-site"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("index.html"), "Start page")?;
+    std::fs::write(server.path().join("404.html"), "Nothing to see here")?;
+
+    let resp = reqwest::blocking::get(server.url())?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.text()?, "Start page");
+
+    let resp = reqwest::blocking::get(server.url().join("test")?)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.text()?.contains("Test Hello Yes"));
+
+    let resp = reqwest::blocking::get(server.url().join("does-not-exist")?)?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.text()?, "Nothing to see here");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+#[case(server(&["--show-disk-space"]), true)]
+#[case(server(None::<&str>), false)]
+fn shows_disk_space(#[case] server: TestServer, #[case] shown: bool) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    let disk_space = parsed.find(Class("disk-space")).next();
+    assert_eq!(disk_space.is_some(), shown);
+    if let Some(disk_space) = disk_space {
+        let re = Regex::new(r"^\d+(\.\d+)? [KMGTPE]?i?B free of \d+(\.\d+)? [KMGTPE]?i?B$")?;
+        assert!(re.is_match(&disk_space.text()), "{}", disk_space.text());
+    }
+
+    Ok(())
+}
+
 #[rstest]
 #[case(server(&["--route-prefix", "foobar"]))]
 #[case(server(&["--route-prefix", "/foobar/"]))]
@@ -300,6 +348,31 @@ fn serves_requests_with_route_prefix(#[case] server: TestServer) -> Result<(), E
     Ok(())
 }
 
+#[rstest]
+#[case(server(&["--route-prefix", "foobar", "--redirect-root-to-prefix"]), true)]
+#[case(server(&["--route-prefix", "foobar"]), false)]
+fn root_can_redirect_to_route_prefix(
+    #[case] server: TestServer,
+    #[case] redirect: bool,
+) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let resp = client.get(server.url()).send()?;
+    if redirect {
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(resp.headers()["location"], "/foobar/");
+    } else {
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    // Other paths outside of the prefix are not redirected
+    let status = client.get(server.url().join("other")?).send()?.status();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
 #[rstest]
 #[case(server_no_stderr(&[] as &[&str]), "/[a-f0-9]+")]
 #[case(server_no_stderr(&["--random-route"]), "/[a-f0-9]+")]
@@ -356,3 +429,1381 @@ fn serves_file_requests_when_indexing_disabled(#[case] server: TestServer) -> Re
 
     Ok(())
 }
+
+#[rstest]
+#[case(server(&["--show-mimetype"]), true)]
+#[case(server(None::<&str>), false)]
+fn shows_mimetype_of_files(#[case] server: TestServer, #[case] enabled: bool) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    let node = parsed
+        .find(|x: &Node| x.name().unwrap_or_default() == "a" && x.text() == FILES[0])
+        .next()
+        .unwrap();
+    if enabled {
+        assert_eq!(node.attr("data-mime-type"), Some("text/plain"));
+    } else {
+        assert_eq!(node.attr("data-mime-type"), None);
+    }
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&["--show-broken-symlinks"]), true)]
+#[case(server(&["--show-broken-symlinks", "--no-symlinks"]), false)]
+#[case(server(None::<&str>), false)]
+fn shows_broken_symlinks(#[case] server: TestServer, #[case] shown: bool) -> Result<(), Error> {
+    let broken = "symlink broken";
+    symlink_file("should-not-exist.xxx", server.path().join(broken))
+        .expect("Couldn't create symlink");
+
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    let node = parsed
+        .find(|x: &Node| x.attr("class") == Some("broken-symlink"))
+        .next();
+    assert_eq!(node.is_some(), shown);
+    if let Some(node) = node {
+        assert!(node.text().starts_with(broken));
+        assert!(node.text().ends_with("should-not-exist.xxx"));
+        assert!(node.find(|x: &Node| x.name() == Some("a")).next().is_none());
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn listing_can_be_grouped_by_type(
+    #[with(&["--group-by", "type"])] server: TestServer,
+) -> Result<(), Error> {
+    symlink_dir(DIRECTORIES[0], server.path().join("link-dir")).expect("Couldn't create symlink");
+    symlink_file(FILES[0], server.path().join("link-file")).expect("Couldn't create symlink");
+
+    let body =
+        reqwest::blocking::get(server.url().join("?sort=name&order=asc")?)?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let links: Vec<_> = parsed
+        .find(Name("table").descendant(Class("directory").or(Class("file"))))
+        .filter_map(|x| x.attr("href"))
+        .map(|href| {
+            href.split('?')
+                .next()
+                .unwrap()
+                .trim_start_matches('/')
+                .to_string()
+        })
+        .collect();
+
+    let groups: Vec<_> = links
+        .iter()
+        .map(
+            |link| match (link.ends_with('/'), link.starts_with("link-")) {
+                (true, false) => 0,
+                (true, true) => 1,
+                (false, false) => 2,
+                (false, true) => 3,
+            },
+        )
+        .collect();
+    assert!(groups.is_sorted(), "{links:?}");
+    assert_eq!(groups.first(), Some(&0));
+    assert_eq!(groups.last(), Some(&3));
+    assert!(groups.contains(&1) && groups.contains(&2));
+    assert_eq!(
+        links[groups.iter().position(|&g| g == 1).unwrap()],
+        "link-dir/"
+    );
+    assert_eq!(links.last().unwrap(), "link-file");
+
+    Ok(())
+}
+
+#[rstest]
+fn theme_selector_only_offers_allowed_themes(
+    #[with(&["--themes", "squirrel,monokai", "-d", "monokai"])] server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let themes: Vec<_> = parsed
+        .find(Class("theme").descendant(Name("li")))
+        .filter_map(|x| x.attr("data-theme"))
+        .collect();
+    assert_eq!(themes, ["default", "squirrel", "monokai"]);
+
+    Ok(())
+}
+
+#[rstest]
+fn default_color_scheme_has_to_be_allowed(tmpdir: TempDir) -> Result<(), Error> {
+    Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("--themes")
+        .arg("squirrel,monokai")
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[rstest]
+fn download_links_force_attachments(
+    #[with(&["--show-download-links"])] server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let download_link = parsed
+        .find(Class("download-link"))
+        .filter_map(|x| x.attr("href"))
+        .find(|href| href.trim_start_matches('/').starts_with(FILES[0]))
+        .expect("No download link");
+    assert_eq!(
+        download_link.trim_start_matches('/'),
+        format!("{}?download-file=true", FILES[0])
+    );
+
+    let resp = reqwest::blocking::get(server.url().join(download_link)?)?.error_for_status()?;
+    assert_eq!(
+        resp.headers()["content-disposition"],
+        format!("attachment; filename=\"{}\"", FILES[0])
+    );
+    assert_eq!(resp.text()?, "Test Hello Yes");
+
+    let resp = reqwest::blocking::get(server.url().join(FILES[0])?)?.error_for_status()?;
+    assert!(resp.headers()["content-disposition"]
+        .to_str()?
+        .starts_with("inline"));
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[rstest]
+fn listing_shows_xattrs(
+    #[with(&["--show-xattrs", "user.comment,user.missing"])] server: TestServer,
+) -> Result<(), Error> {
+    xattr::set(server.path().join(FILES[0]), "user.comment", b"Greetings")?;
+
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let xattrs: Vec<_> = parsed
+        .find(Class("xattr"))
+        .map(|x| (x.attr("title").unwrap().to_string(), x.text()))
+        .collect();
+    assert_eq!(
+        xattrs,
+        [("user.comment".to_string(), "Greetings".to_string())]
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn listing_is_written_to_index_file(
+    #[with(&["--write-index", "index.html", "-u", "--upload-csrf"])] server: TestServer,
+) -> Result<(), Error> {
+    let index = server.path().join(DIRECTORIES[0]).join("index.html");
+    assert!(!index.exists());
+
+    // The written listing doesn't depend on the sorting of the request
+    reqwest::blocking::get(
+        server
+            .url()
+            .join(&format!("{}?sort=size", DIRECTORIES[0]))?,
+    )?
+    .error_for_status()?;
+    let written = wait_for_file(&index)?;
+    assert!(written.starts_with("<!-- Written by miniserve -->\n<!DOCTYPE html>"));
+    assert!(!written.contains("csrf_token"));
+    assert!(!written.contains("&sort=size"));
+
+    // The written file doesn't show up in the listing itself
+    let body = reqwest::blocking::get(server.url().join(DIRECTORIES[0])?)?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed.find(Name("a")).all(|x| x.text() != "index.html"));
+    assert!(parsed.find(Name("a")).any(|x| x.text() == FILES[0]));
+
+    Ok(())
+}
+
+#[rstest]
+fn index_file_not_written_by_miniserve_is_kept(
+    #[with(&["--write-index", "index.html"])] server: TestServer,
+) -> Result<(), Error> {
+    let index = server.path().join(DIRECTORIES[0]).join("index.html");
+    std::fs::write(&index, "handwritten")?;
+
+    let body = reqwest::blocking::get(server.url().join(DIRECTORIES[0])?)?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed.find(Name("a")).any(|x| x.text() == "index.html"));
+
+    sleep(Duration::from_millis(500));
+    assert_eq!(std::fs::read_to_string(&index)?, "handwritten");
+
+    Ok(())
+}
+
+/// Waits for a file written in the background to appear, returning its content
+fn wait_for_file(path: &std::path::Path) -> Result<String, Error> {
+    for _ in 0..20 {
+        if let Ok(content) = std::fs::read_to_string(path) {
+            if !content.is_empty() {
+                return Ok(content);
+            }
+        }
+        sleep(Duration::from_millis(100));
+    }
+    Err(format!("{} wasn't written", path.display()).into())
+}
+
+#[rstest]
+#[case("?sort=size&order=desc")]
+#[case("?sort=date&order=asc")]
+#[case("?sort=name&order=desc")]
+fn pinned_entries_are_listed_first(
+    #[case] query: &str,
+    #[with(&["--dirs-first", "--pin-first", "README.md,index.html"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("README.md"), "Read me")?;
+    std::fs::write(server.path().join("index.html"), "Index")?;
+    std::fs::write(server.path().join("huge.bin"), vec![0; 64 * 1024])?;
+
+    let body = reqwest::blocking::get(server.url().join(query)?)?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let links: Vec<_> = parsed
+        .find(Name("table").descendant(Class("directory").or(Class("file"))))
+        .filter_map(|x| x.attr("href"))
+        .map(|href| href.split('?').next().unwrap().trim_start_matches('/'))
+        .collect();
+
+    let mut pinned = links[..2].to_vec();
+    pinned.sort();
+    assert_eq!(pinned, ["README.md", "index.html"], "{links:?}");
+    assert!(links[2].ends_with('/'), "{links:?}");
+
+    Ok(())
+}
+
+#[rstest]
+#[case("")]
+#[case(DIRECTORIES[0])]
+fn head_request_to_directory_returns_headers_only(
+    server: TestServer,
+    #[case] dir: &str,
+) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::new();
+    let url = server.url().join(dir)?;
+
+    let get_resp = client.get(url.clone()).send()?.error_for_status()?;
+    let get_content_type = get_resp.headers()["content-type"].clone();
+    let get_body_len = get_resp.bytes()?.len();
+
+    let head_resp = client.head(url).send()?.error_for_status()?;
+    assert_eq!(head_resp.headers()["content-type"], get_content_type);
+    assert_eq!(
+        head_resp.headers()["content-length"],
+        get_body_len.to_string().as_str()
+    );
+    assert!(head_resp.bytes()?.is_empty());
+
+    Ok(())
+}
+
+#[rstest]
+fn stylesheet_has_matching_integrity(
+    #[with(&["--css-integrity"])] server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let link = parsed.find(Attr("rel", "stylesheet")).next().unwrap();
+    assert_eq!(link.attr("crossorigin"), Some("anonymous"));
+
+    let css = reqwest::blocking::get(server.url().join(link.attr("href").unwrap())?)?
+        .error_for_status()?
+        .bytes()?;
+    let expected = format!("sha384-{}", BASE64.encode(Sha384::digest(css)));
+    assert_eq!(link.attr("integrity"), Some(expected.as_str()));
+
+    Ok(())
+}
+
+#[rstest]
+fn favicon_depends_on_theme(
+    #[with(&["--favicon-for", "monokai=tests/data/favicon_dark.svg"])] server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let favicon_url = server.url().join(
+        parsed
+            .find(Attr("rel", "icon"))
+            .next()
+            .and_then(|link| link.attr("href"))
+            .expect("favicon link is missing"),
+    )?;
+    let favicon = |query: &str| -> Result<_, Error> {
+        let mut url = favicon_url.clone();
+        url.set_query(Some(query));
+        Ok(reqwest::blocking::get(url)?.error_for_status()?.bytes()?)
+    };
+
+    let themed = std::fs::read("tests/data/favicon_dark.svg")?;
+    assert_eq!(favicon("theme=monokai")?, themed);
+    let logo = favicon("")?;
+    assert_ne!(logo, themed);
+    assert_eq!(favicon("theme=zenburn")?, logo);
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&[] as &[&str]), false)]
+#[case(server(&["--listing-style", "grid"]), true)]
+fn listing_style_can_be_grid(#[case] server: TestServer, #[case] grid: bool) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    let body_class = if grid {
+        "listing-grid"
+    } else {
+        "listing-table"
+    };
+    assert!(parsed
+        .find(Name("body"))
+        .next()
+        .unwrap()
+        .is(Class(body_class)));
+    assert_eq!(parsed.find(Class("grid")).next().is_some(), grid);
+    assert_eq!(parsed.find(Name("table")).next().is_none(), grid);
+
+    if grid {
+        for &file in FILES {
+            assert!(parsed
+                .find(Class("grid-item").descendant(Name("a")))
+                .any(|link| link.text() == file));
+        }
+    }
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&[] as &[&str]), false)]
+#[case(server(&["--preview"]), true)]
+fn preview_modal_is_present_when_enabled(
+    #[case] server: TestServer,
+    #[case] preview: bool,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    assert_eq!(
+        parsed.find(Attr("id", "preview-modal")).next().is_some(),
+        preview
+    );
+    assert_eq!(
+        parsed
+            .find(Name("script"))
+            .any(|script| script.text().contains("previewElement")),
+        preview
+    );
+
+    // The preview relies on the content types, which are not shown in the listing itself
+    assert_eq!(
+        parsed
+            .find(Attr("data-mime-type", "text/plain"))
+            .next()
+            .is_some(),
+        preview
+    );
+    assert!(parsed.find(Class("mime-type")).next().is_none());
+
+    Ok(())
+}
+
+#[rstest]
+fn listings_are_cached_for_the_configured_ttl(
+    #[with(&["--listing-cache-ttl", "2"])] server: TestServer,
+) -> Result<(), Error> {
+    let file = FILES[0];
+    let size_of_file = || -> Result<String, Error> {
+        let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+        let parsed = Document::from_read(body)?;
+        let row = parsed
+            .find(Name("tr"))
+            .find(|row| row.find(Name("a")).any(|link| link.text() == file))
+            .unwrap();
+        Ok(row.find(Class("size-cell")).next().unwrap().text())
+    };
+
+    let original_size = size_of_file()?;
+
+    // Growing a file doesn't modify its directory, so the cached listing is still used
+    std::fs::write(server.path().join(file), "x".repeat(4096))?;
+    assert_eq!(size_of_file()?, original_size);
+
+    sleep(Duration::from_millis(2500));
+    assert_ne!(size_of_file()?, original_size);
+
+    Ok(())
+}
+
+#[rstest]
+#[case(serde_json::json!({"Invalidate": null}))]
+#[case(serde_json::json!({"Invalidate": "/dira"}))]
+fn cached_listings_can_be_invalidated(
+    #[with(&["--listing-cache-ttl", "60"])] server: TestServer,
+    #[case] command: serde_json::Value,
+) -> Result<(), Error> {
+    let file = "grown.txt";
+    std::fs::write(server.path().join("dira").join(file), "x")?;
+    let size_of_file = || -> Result<String, Error> {
+        let body = reqwest::blocking::get(server.url().join("dira/")?)?.error_for_status()?;
+        let parsed = Document::from_read(body)?;
+        let row = parsed
+            .find(Name("tr"))
+            .find(|row| row.find(Name("a")).any(|link| link.text() == file))
+            .unwrap();
+        Ok(row.find(Class("size-cell")).next().unwrap().text())
+    };
+
+    let original_size = size_of_file()?;
+    std::fs::write(server.path().join("dira").join(file), "x".repeat(4096))?;
+    assert_eq!(size_of_file()?, original_size);
+
+    // Invalidating another directory keeps the stale listing
+    let resp = reqwest::blocking::Client::new()
+        .post(server.url().join("api")?)
+        .header("Content-Type", "application/json")
+        .body(serde_json::json!({"Invalidate": "/dirb"}).to_string())
+        .send()?;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(size_of_file()?, original_size);
+
+    reqwest::blocking::Client::new()
+        .post(server.url().join("api")?)
+        .header("Content-Type", "application/json")
+        .body(command.to_string())
+        .send()?
+        .error_for_status()?;
+    assert_ne!(size_of_file()?, original_size);
+
+    Ok(())
+}
+
+#[rstest]
+fn slow_directory_reads_return_partial_listing(
+    #[with(&["--io-timeout", "1"])] server: TestServer,
+) -> Result<(), Error> {
+    // Reading this many entries takes far longer than the timeout
+    let large_dir = server.path().join("large_dir");
+    std::fs::create_dir(&large_dir)?;
+    for i in 0..10_000 {
+        std::fs::write(large_dir.join(format!("file_{i}")), "")?;
+    }
+
+    let start = std::time::Instant::now();
+    let body = reqwest::blocking::get(server.url().join("large_dir/")?)?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    assert!(parsed.find(Class("partial-listing")).next().is_some());
+    assert!(parsed.find(Name("tbody").descendant(Name("tr"))).count() < 10_000);
+
+    Ok(())
+}
+
+#[rstest]
+fn metadata_sidecar_titles_are_shown_and_sortable(
+    #[with(&["--metadata-sidecar"])] server: TestServer,
+) -> Result<(), Error> {
+    let sidecar = serde_json::json!({
+        FILES[0]: {"title": "Zebra"},
+        FILES[1]: {"title": "Aardvark", "description": "Comes first"},
+    });
+    std::fs::write(server.path().join(".meta.json"), sidecar.to_string())?;
+
+    let file_positions = |order: &str| -> Result<(usize, usize), Error> {
+        let url = server.url().join(&format!("?sort=title&order={order}"))?;
+        let body = reqwest::blocking::get(url)?.error_for_status()?;
+        let parsed = Document::from_read(body)?;
+
+        assert!(parsed
+            .find(Class("entry-title"))
+            .any(|x| x.text() == "Zebra"));
+        assert!(parsed
+            .find(Class("entry-description"))
+            .any(|x| x.text() == "Comes first"));
+
+        let links = parsed
+            .find(Name("a").and(Class("file")))
+            .map(|link| link.text())
+            .collect::<Vec<_>>();
+        let position = |file: &str| links.iter().position(|link| link == file).unwrap();
+        Ok((position(FILES[0]), position(FILES[1])))
+    };
+
+    let (zebra, aardvark) = file_positions("desc")?;
+    assert!(aardvark < zebra);
+    let (zebra, aardvark) = file_positions("asc")?;
+    assert!(zebra < aardvark);
+
+    Ok(())
+}
+
+#[rstest]
+fn banner_is_shown_atop_listing(
+    #[with(&["--banner", "Files are deleted <b>nightly</b>"])] server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    let banner = parsed.find(Class("banner")).next().unwrap();
+    assert_eq!(
+        banner.find(Name("p")).next().unwrap().text(),
+        "Files are deleted <b>nightly</b>"
+    );
+    assert!(banner.find(Name("b")).next().is_none());
+
+    Ok(())
+}
+
+#[rstest]
+fn chosen_sorting_is_remembered_by_cookie(server: TestServer) -> Result<(), Error> {
+    let dir = server.path().join("sorted");
+    std::fs::create_dir(&dir)?;
+    for (name, size) in [("a_large", 10_000), ("b_small", 1), ("c_medium", 100)] {
+        std::fs::write(dir.join(name), "x".repeat(size))?;
+    }
+
+    let file_names = |url: &str, cookies: &[String]| -> Result<Vec<String>, Error> {
+        let body = reqwest::blocking::Client::new()
+            .get(server.url().join(url)?)
+            .header("Cookie", cookies.join("; "))
+            .send()?
+            .error_for_status()?;
+        Ok(Document::from_read(body)?
+            .find(Name("a").and(Class("file")))
+            .map(|link| link.text())
+            .collect())
+    };
+
+    let resp = reqwest::blocking::get(server.url().join("?sort=size&order=asc")?)?;
+    let cookies = resp
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|cookie| {
+            cookie
+                .to_str()
+                .unwrap()
+                .split(';')
+                .next()
+                .unwrap()
+                .to_owned()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(cookies.len(), 2);
+
+    let by_size = file_names("sorted/?sort=size&order=asc", &[])?;
+    assert_ne!(file_names("sorted/", &[])?, by_size);
+    assert_eq!(file_names("sorted/", &cookies)?, by_size);
+
+    Ok(())
+}
+
+#[rstest]
+fn malformed_metadata_sidecar_is_ignored(
+    #[with(&["--metadata-sidecar"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join(".meta.json"), "{not json")?;
+
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    assert!(parsed.find(Class("entry-title")).next().is_none());
+    for &file in FILES {
+        assert!(parsed.find(Name("a")).any(|x| x.text() == file));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(None::<&str>), "", true)]
+#[case(server(&["--hide-version-footer"]), "", false)]
+#[case(server(&["--route-prefix", "foo"]), "/foo", true)]
+fn serves_version_info(
+    #[case] server: TestServer,
+    #[case] prefix: &str,
+    #[case] shown: bool,
+) -> Result<(), Error> {
+    let url = server
+        .url()
+        .join(&format!("{prefix}/__miniserve_internal/version"))?;
+    let resp = reqwest::blocking::get(url)?;
+    if !shown {
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        return Ok(());
+    }
+
+    let info: serde_json::Value = serde_json::from_str(&resp.error_for_status()?.text()?)?;
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["features"].is_array());
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(None::<&str>), "", &["/__miniserve_internal/version"])]
+#[case(
+    server(&["--listing-cache-ttl", "60", "-u", "--mkdir"]),
+    "",
+    &["/__miniserve_internal/version", "/api", "/upload", "/upload/progress"]
+)]
+#[case(server(&["--route-prefix", "foo", "--hide-version-footer"]), "/foo", &[])]
+#[case(server(&["--tree-api", "--hide-version-footer"]), "", &["/{path}"])]
+fn serves_openapi_document(
+    #[case] server: TestServer,
+    #[case] prefix: &str,
+    #[case] expected_paths: &[&str],
+) -> Result<(), Error> {
+    let url = server
+        .url()
+        .join(&format!("{prefix}/__miniserve_internal/openapi.json"))?;
+    let document: serde_json::Value =
+        serde_json::from_str(&reqwest::blocking::get(url)?.error_for_status()?.text()?)?;
+
+    assert_eq!(document["openapi"], "3.1.0");
+    let server_url = if prefix.is_empty() { "/" } else { prefix };
+    assert_eq!(document["servers"][0]["url"], server_url);
+
+    let mut paths = document["paths"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(paths, expected_paths);
+
+    // API commands are described if the API is available
+    if expected_paths.contains(&"/api") {
+        let commands = &document["components"]["schemas"]["ApiCommand"]["oneOf"];
+        assert_eq!(commands[0]["required"][0], "Invalidate");
+        let upload_fields = &document["paths"]["/upload"]["post"]["requestBody"]["content"]
+            ["multipart/form-data"]["schema"]["properties"];
+        assert!(upload_fields.get("mkdir").is_some());
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn connections_are_limited(
+    #[with(&["--max-connections", "2"])] server: TestServer,
+) -> Result<(), Error> {
+    // Every worker thread, of which there is one per core, accepts two connections
+    let workers = std::thread::available_parallelism()?.get();
+    // Retried requests must not reuse the connection of a successful one
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(1))
+        .pool_max_idle_per_host(0)
+        .build()?;
+
+    // The connection made by the readiness check may not be closed yet, and once it is, its
+    // worker becomes available again, so keep trying until the closed connection is gone
+    let start = SystemTime::now();
+    let idle_connections = loop {
+        let idle_connections = (0..2 * workers)
+            .map(|_| TcpStream::connect(("localhost", server.port())))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        if client.get(server.url()).send().is_err() {
+            break idle_connections;
+        }
+        assert!(
+            start.elapsed()? < Duration::from_secs(10),
+            "connections weren't limited"
+        );
+    };
+
+    drop(idle_connections);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(3))
+        .build()?;
+    client.get(server.url()).send()?.error_for_status()?;
+
+    Ok(())
+}
+
+#[rstest]
+fn shuts_down_after_being_idle(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--shutdown-after-idle")
+        .arg("2")
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    // Requests keep the server running
+    for _ in 0..3 {
+        sleep(Duration::from_secs(1));
+        reqwest::blocking::get(format!("http://localhost:{port}"))?.error_for_status()?;
+    }
+    assert!(child.try_wait()?.is_none());
+
+    // Once idle, it exits by itself
+    let start = SystemTime::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed()? > Duration::from_secs(10) {
+            child.kill()?;
+            panic!("miniserve didn't shut down after being idle");
+        }
+        sleep(Duration::from_millis(100));
+    };
+    assert!(status.success());
+    assert!(start.elapsed()? >= Duration::from_secs(1));
+
+    Ok(())
+}
+
+#[rstest]
+fn ongoing_downloads_keep_the_server_running(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let size = 64 * 1024 * 1024;
+    std::fs::write(tmpdir.path().join("large.bin"), vec![b'x'; size])?;
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--shutdown-after-idle")
+        .arg("1")
+        .stdout(Stdio::null())
+        .spawn()?;
+    sleep(Duration::from_millis(500));
+
+    // Read the download slower than the idle timeout
+    let mut resp =
+        reqwest::blocking::get(format!("http://localhost:{port}/large.bin"))?.error_for_status()?;
+    let mut start = [0; 1024];
+    resp.read_exact(&mut start)?;
+    sleep(Duration::from_secs(3));
+    let mut rest = Vec::new();
+    resp.read_to_end(&mut rest)?;
+    assert_eq!(start.len() + rest.len(), size);
+
+    // Once the download is done, the server is idle again
+    let done = SystemTime::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if done.elapsed()? > Duration::from_secs(10) {
+            child.kill()?;
+            panic!("miniserve didn't shut down after the download");
+        }
+        sleep(Duration::from_millis(100));
+    };
+    assert!(status.success());
+
+    Ok(())
+}
+
+#[rstest]
+fn single_file_landing_links_to_file(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let file = FILES[0];
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path().join(file))
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--single-file-landing")
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    sleep(Duration::from_secs(1));
+
+    let url = format!("http://localhost:{port}");
+    let resp = reqwest::blocking::get(&url)?.error_for_status()?;
+    assert!(resp.headers()["content-type"]
+        .to_str()?
+        .starts_with("text/html"));
+    let parsed = Document::from_read(resp)?;
+    let download_link = parsed
+        .find(Class("download").descendant(Name("a")))
+        .next()
+        .expect("No download link on landing page");
+    assert_eq!(
+        download_link.attr("href"),
+        Some(format!("/{file}").as_str())
+    );
+
+    let content = reqwest::blocking::get(format!("{url}/{file}"))?
+        .error_for_status()?
+        .text()?;
+    assert_eq!(content, "Test Hello Yes");
+
+    let status = reqwest::blocking::get(format!("{url}/other.txt"))?.status();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    child.kill()?;
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&["--enable-media-streaming", "--compress-response"]), "video/mp4")]
+#[case(server(&["--compress-response"]), "video/mp4")]
+fn media_can_be_seeked_with_range_requests(
+    #[case] server: TestServer,
+    #[case] content_type: &str,
+) -> Result<(), Error> {
+    let video: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    std::fs::write(server.path().join("video.mp4"), &video)?;
+
+    let client = reqwest::blocking::Client::new();
+    let resp = client
+        .get(server.url().join("video.mp4")?)
+        .header(reqwest::header::RANGE, "bytes=524288-524387")
+        .header(reqwest::header::ACCEPT_ENCODING, "gzip")
+        .send()?;
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.headers()["accept-ranges"], "bytes");
+    assert_eq!(
+        resp.headers()["content-range"],
+        "bytes 524288-524387/1048576"
+    );
+    assert_eq!(resp.headers()["content-type"], content_type);
+    assert_eq!(resp.bytes()?, video[524288..524388]);
+
+    Ok(())
+}
+
+#[rstest]
+fn streamed_media_is_not_compressed(
+    #[with(&["--enable-media-streaming", "--compress-response"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("song.mp3"), vec![0; 64 * 1024])?;
+
+    let client = reqwest::blocking::Client::new();
+    let resp = client
+        .get(server.url().join("song.mp3")?)
+        .header(reqwest::header::ACCEPT_ENCODING, "gzip")
+        .send()?
+        .error_for_status()?;
+    assert_eq!(resp.headers()["content-type"], "audio/mpeg");
+    assert_eq!(resp.headers()["content-encoding"], "identity");
+    assert_eq!(resp.bytes()?.len(), 64 * 1024);
+
+    Ok(())
+}
+
+#[rstest]
+fn only_listings_are_compressed(
+    #[with(&["--compress-listings"])] server: TestServer,
+) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::new();
+
+    let resp = client
+        .get(server.url())
+        .header("Accept-Encoding", "gzip")
+        .send()?
+        .error_for_status()?;
+    assert_eq!(resp.headers()["content-encoding"], "gzip");
+    let mut html = String::new();
+    libflate::gzip::Decoder::new(resp.bytes()?.as_ref())?.read_to_string(&mut html)?;
+    assert!(html.contains(FILES[0]));
+
+    let resp = client
+        .get(server.url().join(FILES[0])?)
+        .header("Accept-Encoding", "gzip")
+        .send()?
+        .error_for_status()?;
+    assert!(resp.headers().get("content-encoding").is_none());
+    assert_eq!(resp.text()?, "Test Hello Yes");
+
+    // Clients that don't accept gzip get the plain listing
+    let resp = client.get(server.url()).send()?.error_for_status()?;
+    assert!(resp.headers().get("content-encoding").is_none());
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&[] as &[&str]), "application/octet-stream")]
+#[case(server(&["--mime-sniff"]), "image/png")]
+fn content_type_can_be_sniffed(
+    #[case] server: TestServer,
+    #[case] expected_content_type: &str,
+) -> Result<(), Error> {
+    // A PNG signature followed by the start of an IHDR chunk, without a file extension
+    let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01";
+    std::fs::write(server.path().join("image"), png)?;
+
+    let resp = reqwest::blocking::get(server.url().join("image")?)?.error_for_status()?;
+    assert_eq!(resp.headers()["content-type"], expected_content_type);
+    assert_eq!(resp.bytes()?.as_ref(), png);
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&[] as &[&str]), "text/html; charset=utf-8")]
+#[case(server(&["--serve-html-as-text"]), "text/plain; charset=utf-8")]
+fn html_can_be_served_as_text(
+    #[case] server: TestServer,
+    #[case] expected_content_type: &str,
+) -> Result<(), Error> {
+    let html = "<script>alert(1)</script>";
+    std::fs::write(server.path().join("page.html"), html)?;
+
+    let resp = reqwest::blocking::get(server.url().join("page.html")?)?.error_for_status()?;
+    assert_eq!(resp.headers()["content-type"], expected_content_type);
+    assert_eq!(resp.text()?, html);
+
+    Ok(())
+}
+
+#[rstest]
+fn disabled_methods_are_rejected(
+    #[with(&["--disable-method", "TRACE", "--disable-method", "delete"])] server: TestServer,
+) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::new();
+
+    for method in [reqwest::Method::TRACE, reqwest::Method::DELETE] {
+        let status = client.request(method, server.url()).send()?.status();
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+    client.get(server.url()).send()?.error_for_status()?;
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&["--hide-files-older-than", "7d"]), false)]
+#[case(server(&["--hide-files-older-than", "7d", "--block-old-files"]), true)]
+fn old_files_are_hidden(#[case] server: TestServer, #[case] blocked: bool) -> Result<(), Error> {
+    let (old_file, fresh_file) = (FILES[0], FILES[1]);
+    let thirty_days_ago = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+    std::fs::File::options()
+        .write(true)
+        .open(server.path().join(old_file))?
+        .set_modified(thirty_days_ago)?;
+
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed.find(Name("a")).all(|link| link.text() != old_file));
+    assert!(parsed.find(Name("a")).any(|link| link.text() == fresh_file));
+    for &dir in DIRECTORIES {
+        assert!(parsed.find(Name("a")).any(|link| link.text() == dir));
+    }
+
+    let status = reqwest::blocking::get(server.url().join(old_file)?)?.status();
+    let expected = if blocked {
+        StatusCode::NOT_FOUND
+    } else {
+        StatusCode::OK
+    };
+    assert_eq!(status, expected);
+
+    Ok(())
+}
+
+#[rstest]
+fn only_files_matching_the_filter_are_served(
+    #[with(&["--filter", "*.pdf"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("report.pdf"), "%PDF")?;
+
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed
+        .find(Name("a"))
+        .any(|link| link.text() == "report.pdf"));
+    for &file in FILES {
+        assert!(parsed.find(Name("a")).all(|link| link.text() != file));
+    }
+    for &dir in DIRECTORIES {
+        assert!(parsed.find(Name("a")).any(|link| link.text() == dir));
+    }
+
+    reqwest::blocking::get(server.url().join("report.pdf")?)?.error_for_status()?;
+    let status = reqwest::blocking::get(server.url().join("test.txt")?)?.status();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[rstest]
+#[case("localhost", true)]
+#[case("Example.local:8080", true)]
+#[case("[::1]:8080", true)]
+#[case("attacker.example", false)]
+#[case("example.local.attacker.example", false)]
+fn only_allowed_hosts_are_served(
+    #[with(&["--allowed-hosts", "example.local,localhost,::1"])] server: TestServer,
+    #[case] host: &str,
+    #[case] allowed: bool,
+) -> Result<(), Error> {
+    let status = reqwest::blocking::Client::new()
+        .get(server.url())
+        .header("Host", host)
+        .send()?
+        .status();
+
+    if allowed {
+        assert_eq!(status, StatusCode::OK);
+    } else {
+        assert_eq!(status, StatusCode::MISDIRECTED_REQUEST);
+    }
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&[] as &[&str]), "1000 B", "1.0 KiB")]
+#[case(server(&["--si-units"]), "1.0 kB", "1.0 kB")]
+fn file_sizes_can_use_si_units(
+    #[case] server: TestServer,
+    #[case] size_of_1000_bytes: &str,
+    #[case] size_of_1024_bytes: &str,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("1000_bytes"), [0; 1000])?;
+    std::fs::write(server.path().join("1024_bytes"), [0; 1024])?;
+
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let size_of = |file: &str| {
+        parsed
+            .find(Name("tr"))
+            .find(|row| row.find(Name("a")).any(|link| link.text() == file))
+            .and_then(|row| row.find(Class("size-cell")).next())
+            .map(|cell| cell.text())
+    };
+
+    assert_eq!(size_of("1000_bytes").as_deref(), Some(size_of_1000_bytes));
+    assert_eq!(size_of("1024_bytes").as_deref(), Some(size_of_1024_bytes));
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&["--open-graph", "--title", "My Files"]), true)]
+#[case(server(&["--title", "My Files"]), false)]
+fn open_graph_tags_describe_listing(
+    #[case] server: TestServer,
+    #[case] open_graph: bool,
+) -> Result<(), Error> {
+    let url = server.url().join(DIRECTORIES[0])?;
+    let body = reqwest::blocking::get(url.clone())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let og = |property: &str| {
+        parsed
+            .find(Name("meta"))
+            .find(|meta| meta.attr("property") == Some(property))
+            .and_then(|meta| meta.attr("content"))
+            .map(str::to_string)
+    };
+
+    if open_graph {
+        assert_eq!(og("og:title").as_deref(), Some("My Files/dira"));
+        assert_eq!(og("og:url").as_deref(), Some(url.as_str()));
+        assert!(og("og:description").is_some());
+    } else {
+        assert_eq!(og("og:title"), None);
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[rstest]
+#[case(&["--user", "nobody"])]
+#[case(&["--user", "nobody", "--group", "nogroup", "--chroot"])]
+fn privileges_are_dropped_after_binding(
+    tmpdir: TempDir,
+    port: u16,
+    #[case] args: &[&str],
+) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Only root is allowed to switch users
+    if !nix::unistd::geteuid().is_root() {
+        return Ok(());
+    }
+
+    std::fs::set_permissions(tmpdir.path(), std::fs::Permissions::from_mode(0o755))?;
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args(args)
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    sleep(Duration::from_secs(1));
+
+    let status = std::fs::read_to_string(format!("/proc/{}/status", child.id()))?;
+    let uid = status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().nth(1))
+        .map(str::to_string);
+    let file = FILES[0];
+    let content = reqwest::blocking::get(format!("http://localhost:{port}/{file}"))?
+        .error_for_status()?
+        .text()?;
+
+    child.kill()?;
+
+    assert_eq!(uid.as_deref(), Some("65534"));
+    assert_eq!(content, "Test Hello Yes");
+
+    Ok(())
+}
+
+#[rstest]
+fn robots_txt_can_deny_crawlers(
+    #[with(&["--robots", "deny"])] server: TestServer,
+) -> Result<(), Error> {
+    // An on-disk robots.txt is shadowed by the configured one
+    std::fs::write(
+        server.path().join("robots.txt"),
+        "User-agent: *\nAllow: /\n",
+    )?;
+
+    let resp = reqwest::blocking::get(server.url().join("robots.txt")?)?.error_for_status()?;
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    let body = resp.text()?;
+    assert!(body.lines().any(|line| line == "Disallow: /"));
+
+    Ok(())
+}
+
+#[rstest]
+fn robots_txt_is_served_from_disk_by_default(server: TestServer) -> Result<(), Error> {
+    let status = reqwest::blocking::get(server.url().join("robots.txt")?)?.status();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    std::fs::write(
+        server.path().join("robots.txt"),
+        "User-agent: *\nAllow: /\n",
+    )?;
+    let body = reqwest::blocking::get(server.url().join("robots.txt")?)?
+        .error_for_status()?
+        .text()?;
+    assert_eq!(body, "User-agent: *\nAllow: /\n");
+
+    Ok(())
+}
+
+#[rstest]
+fn footer_links_are_shown(
+    #[with(&[
+        "--footer-link",
+        "Home=https://example.com",
+        "--footer-link",
+        "Help & FAQ=/help.html",
+    ])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    let links: Vec<_> = parsed
+        .find(Class("footer-links").descendant(Name("a")))
+        .map(|a| (a.text(), a.attr("href").unwrap().to_string()))
+        .collect();
+    assert_eq!(
+        links,
+        [
+            ("Home".to_string(), "https://example.com".to_string()),
+            ("Help & FAQ".to_string(), "/help.html".to_string()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn paths_can_be_looked_up_case_insensitively(
+    #[with(&["--case-insensitive"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("image.png"), "not really an image")?;
+
+    let resp = reqwest::blocking::get(server.url().join("Image.PNG")?)?.error_for_status()?;
+    assert_eq!(resp.text()?, "not really an image");
+
+    // Directories are redirected to their actual path
+    let resp = reqwest::blocking::get(server.url().join("DIRA/")?)?.error_for_status()?;
+    assert_eq!(resp.url().path(), "/dira/");
+
+    // Ambiguous paths resolve to the first match in lexicographic order
+    std::fs::write(server.path().join("notes.txt"), "lowercase")?;
+    std::fs::write(server.path().join("NOTES.txt"), "uppercase")?;
+    let resp = reqwest::blocking::get(server.url().join("Notes.TXT")?)?.error_for_status()?;
+    assert_eq!(resp.text()?, "uppercase");
+
+    let status = reqwest::blocking::get(server.url().join("missing.txt")?)?.status();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[rstest]
+fn old_files_are_blocked_case_insensitively(
+    #[with(&["--case-insensitive", "--hide-files-older-than", "7d", "--block-old-files"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("old.txt"), "old")?;
+    let thirty_days_ago = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+    std::fs::File::options()
+        .write(true)
+        .open(server.path().join("old.txt"))?
+        .set_modified(thirty_days_ago)?;
+
+    let status = reqwest::blocking::get(server.url().join("OLD.txt")?)?.status();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[rstest]
+fn downloads_are_logged(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--log-downloads")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    sleep(Duration::from_secs(1));
+
+    let file = FILES[0];
+    let size = std::fs::metadata(tmpdir.path().join(file))?.len();
+    reqwest::blocking::get(format!("http://localhost:{port}/{file}"))?
+        .error_for_status()?
+        .bytes()?;
+    // Listings aren't downloads
+    reqwest::blocking::get(format!("http://localhost:{port}/"))?.error_for_status()?;
+
+    sleep(Duration::from_millis(500));
+    child.kill()?;
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    let all_text = String::from_utf8(output.stdout)?;
+
+    let downloads: Vec<_> = all_text
+        .lines()
+        .filter(|line| line.contains("Served "))
+        .collect();
+    assert_eq!(downloads.len(), 1, "{all_text}");
+    // The client is either 127.0.0.1 or ::1, depending on how localhost resolves
+    let re = Regex::new(&format!(
+        r"Served /{file} to [\d.:]+ as - \({size} bytes\)$"
+    ))?;
+    assert!(re.is_match(downloads[0]), "{}", downloads[0]);
+
+    // The access log isn't enabled along with it
+    assert!(!all_text.contains("GET /"));
+
+    Ok(())
+}
+
+#[rstest]
+fn access_log_can_be_redacted(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("-v")
+        .arg("--log-redact")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    sleep(Duration::from_secs(1));
+
+    reqwest::blocking::Client::new()
+        .get(format!("http://localhost:{port}/?token=query-secret"))
+        .header("Referer", "http://example.com/page?sig=referer-secret")
+        .header("Authorization", "Bearer auth-secret")
+        .send()?
+        .error_for_status()?;
+
+    sleep(Duration::from_millis(500));
+    child.kill()?;
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    let all_text = String::from_utf8(output.stdout)?;
+
+    assert!(
+        all_text.contains(r#""GET /?[redacted] HTTP/1.1""#),
+        "{all_text}"
+    );
+    assert!(
+        all_text.contains(r#""http://example.com/page?[redacted]""#),
+        "{all_text}"
+    );
+    for secret in ["query-secret", "referer-secret", "auth-secret"] {
+        assert!(!all_text.contains(secret), "{all_text}");
+    }
+
+    Ok(())
+}
+
+#[rstest]
+#[case(false, false, true)]
+#[case(true, false, false)]
+#[case(true, true, true)]
+fn served_files_are_verified_against_manifest(
+    tmpdir: TempDir,
+    port: u16,
+    #[case] tampered: bool,
+    #[case] warn_only: bool,
+    #[case] starts: bool,
+) -> Result<(), Error> {
+    let manifest_dir = TempDir::new()?;
+    let manifest_path = manifest_dir.path().join("SHA256SUMS");
+    let file = FILES[0];
+    let checksum = hex::encode(Sha256::digest(std::fs::read(tmpdir.path().join(file))?));
+    std::fs::write(&manifest_path, format!("{checksum}  ./{file}\n"))?;
+
+    if tampered {
+        std::fs::write(tmpdir.path().join(file), "tampered content")?;
+    }
+
+    let mut child = Command::cargo_bin("miniserve")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--verify-manifest")
+        .arg(&manifest_path)
+        .args(warn_only.then_some("--verify-manifest-warn-only"))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    sleep(Duration::from_secs(1));
+
+    let exited = child.try_wait()?.is_some();
+    child.kill()?;
+    let output = child.wait_with_output()?;
+    // Errors are logged to stderr, warnings to stdout
+    let log = String::from_utf8(output.stdout)? + &String::from_utf8(output.stderr)?;
+
+    assert_eq!(exited, !starts, "{log}");
+    assert_eq!(
+        log.contains(&format!("{file} doesn't match the verification manifest")),
+        tampered
+    );
+
+    Ok(())
+}
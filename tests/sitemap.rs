@@ -0,0 +1,32 @@
+mod fixtures;
+
+use fixtures::{server, Error, TestServer, DEEPLY_NESTED_FILE, FILES, HIDDEN_FILES};
+use reqwest::StatusCode;
+use rstest::rstest;
+
+#[rstest]
+fn sitemap_lists_served_files(#[with(&["--sitemap"])] server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url().join("sitemap.xml")?)?.error_for_status()?;
+    assert_eq!(resp.headers()["content-type"], "text/xml");
+    let body = resp.text()?;
+
+    assert!(body.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+    for file in [FILES[0], DEEPLY_NESTED_FILE] {
+        let url = server.url().join(file)?;
+        assert!(body.contains(&format!("<loc>{url}</loc>")));
+    }
+    for file in HIDDEN_FILES {
+        assert!(!body.contains(file));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn sitemap_disabled_by_default(server: TestServer) -> Result<(), Error> {
+    let status = reqwest::blocking::get(server.url().join("sitemap.xml")?)?.status();
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+}
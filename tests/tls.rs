@@ -1,9 +1,15 @@
 mod fixtures;
 
+use std::{fs::File, io::BufReader};
+
 use assert_cmd::Command;
-use fixtures::{server, Error, TestServer, FILES};
+use fixtures::{port, server, Error, TestServer, FILES};
 use predicates::str::contains;
-use reqwest::blocking::ClientBuilder;
+use reqwest::{
+    blocking::ClientBuilder,
+    redirect,
+    tls::{TlsInfo, Version},
+};
 use rstest::rstest;
 use select::{document::Document, node::Node};
 
@@ -57,3 +63,164 @@ fn wrong_path_key() -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Clients that don't support the minimum TLS version are refused.
+#[rstest]
+#[case(server(&[
+        "--tls-cert", "tests/data/cert_rsa.pem",
+        "--tls-key", "tests/data/key_pkcs8.pem",
+        "--tls-min-version", "1.3",
+]), false)]
+#[case(server(&[
+        "--tls-cert", "tests/data/cert_rsa.pem",
+        "--tls-key", "tests/data/key_pkcs8.pem",
+        "--tls-min-version", "1.2",
+]), true)]
+fn tls_min_version_is_enforced(
+    #[case] server: TestServer,
+    #[case] tls12_allowed: bool,
+) -> Result<(), Error> {
+    let tls12_client = ClientBuilder::new()
+        .danger_accept_invalid_certs(true)
+        .max_tls_version(Version::TLS_1_2)
+        .build()?;
+    assert_eq!(tls12_client.get(server.url()).send().is_ok(), tls12_allowed);
+
+    let tls13_client = ClientBuilder::new()
+        .danger_accept_invalid_certs(true)
+        .min_tls_version(Version::TLS_1_3)
+        .build()?;
+    tls13_client.get(server.url()).send()?.error_for_status()?;
+
+    Ok(())
+}
+
+/// Unknown cipher suites throw errors.
+#[rstest]
+fn unsupported_cipher_suite() -> Result<(), Error> {
+    Command::cargo_bin("miniserve")?
+        .args([
+            "--tls-cert",
+            "tests/data/cert_rsa.pem",
+            "--tls-key",
+            "tests/data/key_pkcs8.pem",
+            "--tls-cipher-suites",
+            "TLS13_AES_256_GCM_SHA384,TLS_RSA_WITH_RC4_128_MD5",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Error: Unsupported TLS cipher suite \"TLS_RSA_WITH_RC4_128_MD5\"",
+        ));
+
+    Ok(())
+}
+
+/// Cipher suites must support the minimum TLS version.
+#[rstest]
+fn cipher_suites_incompatible_with_min_version() -> Result<(), Error> {
+    Command::cargo_bin("miniserve")?
+        .args([
+            "--tls-cert",
+            "tests/data/cert_rsa.pem",
+            "--tls-key",
+            "tests/data/key_pkcs8.pem",
+            "--tls-cipher-suites",
+            "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+            "--tls-min-version",
+            "1.3",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Error: The TLS cipher suites don't support the minimum TLS version",
+        ));
+
+    Ok(())
+}
+
+/// With multiple certificates, the one matching the requested server name is served.
+#[rstest]
+#[case("one.localhost", "tests/data/cert_sni_one.pem")]
+#[case("two.localhost", "tests/data/cert_sni_two.pem")]
+#[case("127.0.0.1", "tests/data/cert_sni_one.pem")]
+fn certificate_is_selected_by_sni(
+    #[case] host: &str,
+    #[case] expected_cert: &str,
+    #[with(&[
+        "--tls-cert", "tests/data/cert_sni_one.pem",
+        "--tls-key", "tests/data/key_sni_one.pem",
+        "--tls-cert", "tests/data/cert_sni_two.pem",
+        "--tls-key", "tests/data/key_sni_two.pem",
+    ])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let client = ClientBuilder::new()
+        .danger_accept_invalid_certs(true)
+        .tls_info(true)
+        .resolve(host, ([127, 0, 0, 1], server.port()).into())
+        .build()?;
+    let url = format!("https://{host}:{}/", server.port());
+    let response = client.get(url).send()?.error_for_status()?;
+    let peer_certificate = response
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(TlsInfo::peer_certificate)
+        .expect("No peer certificate");
+
+    let expected_cert = rustls_pemfile::certs(&mut BufReader::new(File::open(expected_cert)?))
+        .next()
+        .expect("No certificate found")?;
+    assert_eq!(peer_certificate, expected_cert.as_ref());
+
+    Ok(())
+}
+
+/// Every TLS certificate needs a key.
+#[rstest]
+fn tls_cert_without_key() -> Result<(), Error> {
+    Command::cargo_bin("miniserve")?
+        .args([
+            "--tls-cert",
+            "tests/data/cert_sni_one.pem",
+            "--tls-key",
+            "tests/data/key_sni_one.pem",
+            "--tls-cert",
+            "tests/data/cert_sni_two.pem",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Error: Every TLS certificate needs a matching TLS key, got 2 certificates and 1 keys",
+        ));
+
+    Ok(())
+}
+
+/// Plain HTTP requests on the --redirect-http port are redirected to HTTPS.
+#[rstest]
+fn plain_http_is_redirected_to_https() -> Result<(), Error> {
+    let redirect_port = port();
+    let server = server(&[
+        "--tls-cert",
+        "tests/data/cert_rsa.pem",
+        "--tls-key",
+        "tests/data/key_pkcs8.pem",
+        "--redirect-http",
+        &redirect_port.to_string(),
+    ]);
+
+    let client = ClientBuilder::new()
+        .redirect(redirect::Policy::none())
+        .build()?;
+    let resp = client
+        .get(format!("http://localhost:{redirect_port}/some/path?query"))
+        .send()?;
+    assert_eq!(resp.status(), reqwest::StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(
+        resp.headers()["location"],
+        format!("https://localhost:{}/some/path?query", server.port()).as_str()
+    );
+
+    Ok(())
+}
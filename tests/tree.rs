@@ -0,0 +1,60 @@
+mod fixtures;
+
+use fixtures::{server, Error, TestServer, DEEPLY_NESTED_FILE, HIDDEN_FILES};
+use reqwest::StatusCode;
+use rstest::rstest;
+use serde_json::Value;
+
+/// Looks up the node at `path` in the tree below `node`
+fn find_node<'a>(node: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('/').try_fold(node, |node, name| {
+        node["children"]
+            .as_array()?
+            .iter()
+            .find(|child| child["name"] == name)
+    })
+}
+
+#[rstest]
+fn tree_contains_nested_files(#[with(&["--tree-api"])] server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url().join("?tree=true")?)?.error_for_status()?;
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    let tree: Value = serde_json::from_str(&resp.text()?)?;
+
+    assert_eq!(tree["truncated"], false);
+    let nested = find_node(&tree["root"], DEEPLY_NESTED_FILE).expect("nested file is missing");
+    assert_eq!(nested["type"], "file");
+    assert!(nested["size"].is_u64());
+    assert_eq!(
+        find_node(&tree["root"], "very/deeply").unwrap()["type"],
+        "directory"
+    );
+    for file in HIDDEN_FILES {
+        assert!(find_node(&tree["root"], file).is_none());
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn tree_of_subdirectory_is_served(
+    #[with(&["--tree-api", "--route-prefix", "foo"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp =
+        reqwest::blocking::get(server.url().join("foo/very/?tree=true")?)?.error_for_status()?;
+    let tree: Value = serde_json::from_str(&resp.text()?)?;
+
+    let nested = DEEPLY_NESTED_FILE.strip_prefix("very/").unwrap();
+    assert_eq!(find_node(&tree["root"], nested).unwrap()["type"], "file");
+
+    Ok(())
+}
+
+#[rstest]
+fn tree_disabled_by_default(server: TestServer) -> Result<(), Error> {
+    let status = reqwest::blocking::get(server.url().join("?tree=true")?)?.status();
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    Ok(())
+}
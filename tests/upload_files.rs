@@ -1,13 +1,20 @@
 mod fixtures;
 
 use assert_fs::fixture::TempDir;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use fixtures::{server, server_no_stderr, tmpdir, Error, TestServer};
 use reqwest::blocking::{multipart, Client};
+use reqwest::{header, Method, StatusCode};
 use rstest::rstest;
 use select::document::Document;
 use select::predicate::{Attr, Text};
+use sha2::{Digest, Sha256};
 use std::fs::create_dir_all;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
 use std::path::Path;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+use zip::{write::SimpleFileOptions, ZipWriter};
 
 #[rstest]
 fn uploading_files_works(#[with(&["-u"])] server: TestServer) -> Result<(), Error> {
@@ -46,6 +53,173 @@ fn uploading_files_works(#[with(&["-u"])] server: TestServer) -> Result<(), Erro
     Ok(())
 }
 
+#[rstest]
+#[case(server(&["-u"]), None, StatusCode::SEE_OTHER, Some("/"))]
+#[case(server(&["-u", "--upload-redirect", "/done"]), None, StatusCode::SEE_OTHER, Some("/done"))]
+#[case(server(&["-u", "--upload-redirect", "/done"]), Some("/other"), StatusCode::SEE_OTHER, Some("/other"))]
+#[case(server(&["-u", "--upload-redirect", "none"]), None, StatusCode::OK, None)]
+#[case(server(&["-u"]), Some("none"), StatusCode::OK, None)]
+fn uploads_redirect_to_configured_location(
+    #[case] server: TestServer,
+    #[case] redirect_header: Option<&str>,
+    #[case] expected_status: StatusCode,
+    #[case] expected_location: Option<&str>,
+) -> Result<(), Error> {
+    let part = multipart::Part::text("this should be uploaded")
+        .file_name("redirected.txt")
+        .mime_str("text/plain")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+
+    let client = Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let mut req = client
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form);
+    if let Some(redirect) = redirect_header {
+        req = req.header("X-Upload-Redirect", redirect);
+    }
+    let resp = req.send()?;
+
+    assert_eq!(resp.status(), expected_status);
+    assert_eq!(
+        resp.headers()
+            .get(header::LOCATION)
+            .map(|location| location.to_str())
+            .transpose()?,
+        expected_location
+    );
+    assert!(server.path().join("redirected.txt").exists());
+
+    Ok(())
+}
+
+#[rstest]
+fn concurrent_duplicate_uploads_dont_mix(#[with(&["-u"])] server: TestServer) -> Result<(), Error> {
+    let uploads: Vec<_> = [b'a', b'b']
+        .into_iter()
+        .map(|byte| {
+            let url = server.url().join("/upload?path=/").unwrap();
+            spawn(move || {
+                let part = multipart::Part::bytes(vec![byte; 4 * 1024 * 1024])
+                    .file_name("duplicate.bin")
+                    .mime_str("application/octet-stream")
+                    .unwrap();
+                let form = multipart::Form::new().part("file_to_upload", part);
+                Client::new()
+                    .post(url)
+                    .multipart(form)
+                    .send()
+                    .unwrap()
+                    .status()
+            })
+        })
+        .collect();
+    let statuses: Vec<_> = uploads
+        .into_iter()
+        .map(|upload| upload.join().unwrap())
+        .collect();
+
+    // Exactly one upload claims the file, the other one is rejected as a duplicate
+    assert_eq!(
+        statuses.iter().filter(|status| status.is_success()).count(),
+        1,
+        "{statuses:?}"
+    );
+    assert!(statuses.contains(&StatusCode::CONFLICT), "{statuses:?}");
+
+    let uploaded = std::fs::read(server.path().join("duplicate.bin"))?;
+    assert_eq!(uploaded.len(), 4 * 1024 * 1024);
+    assert!(uploaded.iter().all(|&byte| byte == uploaded[0]));
+
+    Ok(())
+}
+
+#[rstest]
+fn uploads_require_csrf_token(
+    #[with(&["-u", "--upload-csrf"])] server_no_stderr: TestServer,
+) -> Result<(), Error> {
+    let form = || {
+        let part = multipart::Part::text("this should be uploaded")
+            .file_name("uploaded.txt")
+            .mime_str("text/plain")
+            .unwrap();
+        multipart::Form::new().part("file_to_upload", part)
+    };
+
+    // Without the token, the upload is rejected.
+    let resp = Client::new()
+        .post(server_no_stderr.url().join("/upload?path=/")?)
+        .multipart(form())
+        .send()?;
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    assert!(!server_no_stderr.path().join("uploaded.txt").exists());
+
+    // A forged token is rejected as well.
+    let resp = Client::new()
+        .post(server_no_stderr.url().join("/upload?path=/")?)
+        .header("X-Csrf-Token", "forged")
+        .multipart(form())
+        .send()?;
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // With the token embedded in the upload form, the upload succeeds.
+    let body = reqwest::blocking::get(server_no_stderr.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    let upload_form = parsed
+        .find(Attr("id", "file_submit"))
+        .next()
+        .expect("Couldn't find element with id=file_submit");
+    let upload_action = upload_form
+        .attr("action")
+        .expect("Upload form doesn't have action attribute");
+    assert!(!upload_action.contains("csrf_token"));
+    let token = upload_form
+        .find(Attr("name", "csrf_token"))
+        .next()
+        .and_then(|input| input.attr("value"))
+        .expect("Upload form doesn't contain the CSRF token")
+        .to_string();
+    Client::new()
+        .post(server_no_stderr.url().join(upload_action)?)
+        .multipart(
+            multipart::Form::new()
+                .text("csrf_token", token.clone())
+                .part(
+                    "file_to_upload",
+                    multipart::Part::text("this should be uploaded")
+                        .file_name("uploaded.txt")
+                        .mime_str("text/plain")?,
+                ),
+        )
+        .send()?
+        .error_for_status()?;
+    assert!(server_no_stderr.path().join("uploaded.txt").exists());
+
+    // The token isn't accepted in the query string.
+    std::fs::remove_file(server_no_stderr.path().join("uploaded.txt"))?;
+    let resp = Client::new()
+        .post(
+            server_no_stderr
+                .url()
+                .join(&format!("/upload?path=/&csrf_token={token}"))?,
+        )
+        .multipart(form())
+        .send()?;
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // The token can also be sent in a header.
+    Client::new()
+        .post(server_no_stderr.url().join("/upload?path=/")?)
+        .header("X-Csrf-Token", &token)
+        .multipart(form())
+        .send()?
+        .error_for_status()?;
+    assert!(server_no_stderr.path().join("uploaded.txt").exists());
+
+    Ok(())
+}
+
 #[rstest]
 fn uploading_files_is_prevented(server: TestServer) -> Result<(), Error> {
     let test_file_name = "uploaded test file.txt";
@@ -275,3 +449,939 @@ fn set_media_type(
 
     Ok(())
 }
+
+#[rstest]
+#[case(server(&["-u"]), true)]
+#[case(server(&[] as &[&str]), false)]
+fn upload_form_shows_total_progress(
+    #[case] server: TestServer,
+    #[case] expected: bool,
+) -> Result<(), Error> {
+    let body = reqwest::blocking::get(server.url())?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+
+    let progress = parsed.find(Attr("id", "upload-progress")).next();
+    assert_eq!(progress.is_some(), expected);
+    if let Some(progress) = progress {
+        assert_eq!(progress.name(), Some("progress"));
+        assert!(progress.attr("hidden").is_some());
+    }
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server_no_stderr(&["-u", "--max-filename-length", "32"]), 32)]
+#[case(server_no_stderr(&["-u", "--max-filename-length", "32", "--cas-upload"]), 20)]
+fn uploads_with_too_long_file_names_are_rejected(
+    #[case] server: TestServer,
+    #[case] max_len: usize,
+) -> Result<(), Error> {
+    let upload = |file_name: &str| -> Result<reqwest::blocking::Response, Error> {
+        let part = multipart::Part::text("this should be uploaded")
+            .file_name(file_name.to_string())
+            .mime_str("text/plain")?;
+        let form = multipart::Form::new().part("file_to_upload", part);
+        Ok(Client::new()
+            .post(server.url().join("/upload?path=/")?)
+            .multipart(form)
+            .send()?)
+    };
+
+    // Verified uploads lose the 12 bytes of their temporary name
+    let long_name = format!("{}.txt", "a".repeat(max_len - 3));
+    let resp = upload(&long_name)?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(resp.text()?.contains(&format!(
+        "File names must not be longer than {max_len} bytes"
+    )));
+    assert!(!server.path().join(&long_name).exists());
+
+    let short_name = format!("{}.txt", "a".repeat(max_len - 4));
+    upload(&short_name)?.error_for_status()?;
+    assert!(server.path().join(&short_name).exists());
+
+    // Directories are never renamed, so they may use the full length
+    let dir_name = "d".repeat(32);
+    create_dir_all(server.path().join(&dir_name))?;
+    upload(&format!("{dir_name}/b.txt"))?.error_for_status()?;
+    assert!(server.path().join(&dir_name).join("b.txt").exists());
+
+    Ok(())
+}
+
+#[rstest]
+fn identical_uploads_share_a_blob(
+    #[with(&["-u", "--cas-upload"])] server: TestServer,
+) -> Result<(), Error> {
+    let client = Client::new();
+    for file_name in ["first.txt", "second.txt"] {
+        let part = multipart::Part::text("same content")
+            .file_name(file_name)
+            .mime_str("text/plain")?;
+        let form = multipart::Form::new().part("file_to_upload", part);
+        client
+            .post(server.url().join("/upload?path=/")?)
+            .multipart(form)
+            .send()?
+            .error_for_status()?;
+    }
+
+    for file_name in ["first.txt", "second.txt"] {
+        let resp = reqwest::blocking::get(server.url().join(file_name)?)?.error_for_status()?;
+        assert_eq!(resp.text()?, "same content");
+    }
+    let blobs: Vec<_> = std::fs::read_dir(server.path().join(".blobs"))?.collect();
+    assert_eq!(blobs.len(), 1);
+
+    Ok(())
+}
+
+/// Builds an in-memory zip archive containing the given files
+fn zip_archive(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in files {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[rstest]
+#[case(server(&["-u", "--extract-uploads"]), true)]
+#[case(server(&["-u", "--extract-uploads", "--delete-extracted-archives"]), false)]
+fn uploaded_archives_are_extracted(
+    #[case] server: TestServer,
+    #[case] archive_kept: bool,
+) -> Result<(), Error> {
+    let archive = zip_archive(&[("extracted.txt", "first"), ("nested/file.txt", "second")]);
+    let part = multipart::Part::bytes(archive)
+        .file_name("archive.zip")
+        .mime_str("application/zip")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+
+    Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .error_for_status()?;
+
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("extracted.txt"))?,
+        "first"
+    );
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("nested/file.txt"))?,
+        "second"
+    );
+    assert_eq!(server.path().join("archive.zip").exists(), archive_kept);
+
+    Ok(())
+}
+
+#[rstest]
+fn uploaded_archives_escaping_the_target_are_rejected(
+    #[with(&["-u", "--extract-uploads"])] server_no_stderr: TestServer,
+) -> Result<(), Error> {
+    create_dir_all(server_no_stderr.path().join("foo"))?;
+
+    let archive = zip_archive(&[("harmless.txt", "ok"), ("../evil.txt", "gotcha")]);
+    let part = multipart::Part::bytes(archive)
+        .file_name("archive.zip")
+        .mime_str("application/zip")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+
+    let status = Client::new()
+        .post(server_no_stderr.url().join("/upload?path=foo")?)
+        .multipart(form)
+        .send()?
+        .status();
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+    // Nothing must have been extracted, and the rejected archive must be gone
+    assert!(!server_no_stderr.path().join("evil.txt").exists());
+    assert!(!server_no_stderr.path().join("foo/harmless.txt").exists());
+    assert!(!server_no_stderr.path().join("foo/archive.zip").exists());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+#[case("/etc/passwd")]
+#[case("../../outside.txt")]
+#[case("nested/../../outside.txt")]
+fn uploaded_archives_with_escaping_symlinks_are_rejected(
+    #[with(&["-u", "--extract-uploads"])] server_no_stderr: TestServer,
+    #[case] target: &str,
+) -> Result<(), Error> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("harmless.txt", SimpleFileOptions::default())?;
+    zip.write_all(b"ok")?;
+    zip.add_symlink("nested/link", target, SimpleFileOptions::default())?;
+    let archive = zip.finish()?.into_inner();
+
+    let part = multipart::Part::bytes(archive)
+        .file_name("archive.zip")
+        .mime_str("application/zip")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+
+    let status = Client::new()
+        .post(server_no_stderr.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .status();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The link must not have been created, and the rest of the archive must be gone as well
+    assert!(server_no_stderr
+        .path()
+        .join("nested/link")
+        .symlink_metadata()
+        .is_err());
+    assert!(!server_no_stderr.path().join("harmless.txt").exists());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+fn uploaded_archives_with_contained_symlinks_are_extracted(
+    #[with(&["-u", "--extract-uploads"])] server: TestServer,
+) -> Result<(), Error> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("target.txt", SimpleFileOptions::default())?;
+    zip.write_all(b"linked")?;
+    zip.add_symlink("nested/link", "../target.txt", SimpleFileOptions::default())?;
+    let archive = zip.finish()?.into_inner();
+
+    let part = multipart::Part::bytes(archive)
+        .file_name("archive.zip")
+        .mime_str("application/zip")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+    Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .error_for_status()?;
+
+    let resp = reqwest::blocking::get(server.url().join("nested/link")?)?.error_for_status()?;
+    assert_eq!(resp.text()?, "linked");
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server_no_stderr(&["-u", "--extract-uploads", "--extract-max-total-size", "64KB"]))]
+#[case(server_no_stderr(&["-u", "--extract-uploads", "--extract-max-ratio", "10"]))]
+fn uploaded_archives_exceeding_extraction_limits_are_aborted(
+    #[case] server: TestServer,
+) -> Result<(), Error> {
+    let bomb = "0".repeat(16 * 1024 * 1024);
+    let archive = zip_archive(&[("first.txt", "harmless"), ("nested/bomb.txt", &bomb)]);
+    assert!(archive.len() < 64 * 1024);
+    let part = multipart::Part::bytes(archive)
+        .file_name("archive.zip")
+        .mime_str("application/zip")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+
+    let status = Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .status();
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Everything extracted before the limit was hit must have been cleaned up
+    assert!(!server.path().join("first.txt").exists());
+    assert!(!server.path().join("nested").exists());
+    assert!(!server.path().join("archive.zip").exists());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+fn uploads_get_configured_permissions(
+    #[with(&["-u", "-U", "--upload-file-mode", "0640", "--upload-dir-mode", "0750"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let part = multipart::Part::text("this should be uploaded")
+        .file_name("uploaded.txt")
+        .mime_str("text/plain")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+    Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .error_for_status()?;
+
+    let form = multipart::Form::new().text("mkdir", "created/nested");
+    Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .error_for_status()?;
+
+    let mode = |path: &str| -> Result<u32, Error> {
+        Ok(std::fs::metadata(server.path().join(path))?
+            .permissions()
+            .mode()
+            & 0o7777)
+    };
+    assert_eq!(mode("uploaded.txt")?, 0o640);
+    assert_eq!(mode("created")?, 0o750);
+    assert_eq!(mode("created/nested")?, 0o750);
+
+    Ok(())
+}
+
+/// Reader handing out one byte per second, to keep an upload running for a while
+struct SlowReader(&'static [u8]);
+
+impl Read for SlowReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some((&first, rest)) = self.0.split_first() else {
+            return Ok(0);
+        };
+        sleep(Duration::from_secs(1));
+        buf[0] = first;
+        self.0 = rest;
+        Ok(1)
+    }
+}
+
+#[rstest]
+fn concurrent_uploads_are_limited(
+    #[with(&["-u", "--max-concurrent-uploads", "1"])] server_no_stderr: TestServer,
+) -> Result<(), Error> {
+    let upload_url = server_no_stderr.url().join("/upload?path=/")?;
+
+    let slow_upload = {
+        let upload_url = upload_url.clone();
+        spawn(move || {
+            let part = multipart::Part::reader(SlowReader(b"slow"))
+                .file_name("slow.txt")
+                .mime_str("text/plain")
+                .unwrap();
+            let form = multipart::Form::new().part("file_to_upload", part);
+            Client::new()
+                .post(upload_url)
+                .multipart(form)
+                .send()
+                .unwrap()
+                .status()
+        })
+    };
+
+    // Give the slow upload time to start
+    sleep(Duration::from_millis(500));
+
+    let part = multipart::Part::text("this should not be uploaded")
+        .file_name("rejected.txt")
+        .mime_str("text/plain")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+    let status = Client::new()
+        .post(upload_url)
+        .multipart(form)
+        .send()?
+        .status();
+
+    assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert!(slow_upload.join().unwrap().is_success());
+    assert!(server_no_stderr.path().join("slow.txt").exists());
+    assert!(!server_no_stderr.path().join("rejected.txt").exists());
+
+    Ok(())
+}
+
+#[rstest]
+fn upload_progress_is_broadcast(#[with(&["-u"])] server: TestServer) -> Result<(), Error> {
+    let events = reqwest::blocking::get(
+        server
+            .url()
+            .join("/upload/progress?upload_id=test-upload")?,
+    )?
+    .error_for_status()?;
+    assert_eq!(events.headers()["content-type"], "text/event-stream");
+
+    // Only the events of the subscribed upload are sent
+    for (file_name, upload_id) in [
+        ("other.txt", "other-upload"),
+        ("progress.txt", "test-upload"),
+    ] {
+        let part = multipart::Part::text("this should be uploaded")
+            .file_name(file_name)
+            .mime_str("text/plain")?;
+        let form = multipart::Form::new().part("file_to_upload", part);
+        Client::new()
+            .post(
+                server
+                    .url()
+                    .join(&format!("/upload?path=/&upload_id={upload_id}"))?,
+            )
+            .multipart(form)
+            .send()?
+            .error_for_status()?;
+    }
+
+    let event = BufReader::new(events)
+        .lines()
+        .map(Result::unwrap)
+        .find(|line| line.starts_with("data: "))
+        .expect("No progress event received");
+    assert!(event.contains(r#""upload_id":"test-upload""#));
+    assert!(event.contains(r#""file_name":"progress.txt""#));
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server_no_stderr(&["-u", "--upload-allowed-referer", "example.com"]), StatusCode::FORBIDDEN)]
+#[case(server_no_stderr(&["-u", "--upload-csrf"]), StatusCode::FORBIDDEN)]
+#[case(server_no_stderr(&["-u", "--auth", "joe:123", "--upload-requires-auth"]), StatusCode::UNAUTHORIZED)]
+fn upload_progress_requires_upload_permission(
+    #[case] server: TestServer,
+    #[case] expected: StatusCode,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(
+        server
+            .url()
+            .join("/upload/progress?upload_id=test-upload")?,
+    )?;
+    assert_eq!(resp.status(), expected);
+
+    Ok(())
+}
+
+#[rstest]
+fn uploaded_file_names_can_be_slugified(
+    #[with(&["-u", "--slugify-uploads"])] server: TestServer,
+) -> Result<(), Error> {
+    let part = multipart::Part::text("this should be uploaded")
+        .file_name("My File (1).TXT")
+        .mime_str("text/plain")?;
+    let form = multipart::Form::new().part("file_to_upload", part);
+    Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .error_for_status()?;
+
+    assert!(server.path().join("my-file-1.txt").exists());
+    assert!(!server.path().join("My File (1).TXT").exists());
+
+    Ok(())
+}
+
+#[rstest]
+#[case(false)]
+#[case(true)]
+fn oversized_request_bodies_are_rejected(
+    #[with(&["-u", "--max-request-body", "1KiB"])] server_no_stderr: TestServer,
+    #[case] chunked: bool,
+) -> Result<(), Error> {
+    let content = "x".repeat(2048);
+    let part = if chunked {
+        // Without a known length, the body can only be rejected once too much was received
+        multipart::Part::reader(Cursor::new(content))
+    } else {
+        multipart::Part::text(content)
+    };
+    let form = multipart::Form::new().part("file_to_upload", part.file_name("large.txt"));
+
+    let status = Client::new()
+        .post(server_no_stderr.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .status();
+
+    assert_eq!(status, reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(!server_no_stderr.path().join("large.txt").exists());
+
+    // Small uploads are still fine
+    let part = multipart::Part::text("small").file_name("small.txt");
+    let form = multipart::Form::new().part("file_to_upload", part);
+    Client::new()
+        .post(server_no_stderr.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+#[rstest]
+fn uploads_are_written_to_audit_log() -> Result<(), Error> {
+    let log_dir = TempDir::new()?;
+    let log_path = log_dir.path().join("audit.log");
+    let server = server(&[
+        "-u",
+        "-U",
+        "--auth",
+        "joe:123",
+        "--audit-log",
+        log_path.to_str().unwrap(),
+    ]);
+
+    let part = multipart::Part::text("audited").file_name("audited.txt");
+    let form = multipart::Form::new().part("file_to_upload", part);
+    Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .basic_auth("joe", Some("123"))
+        .multipart(form)
+        .send()?
+        .error_for_status()?;
+
+    let form = multipart::Form::new().text("mkdir", "audited_dir");
+    Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .basic_auth("joe", Some("123"))
+        .multipart(form)
+        .send()?
+        .error_for_status()?;
+
+    let log = std::fs::read_to_string(&log_path)?;
+    let lines = log
+        .lines()
+        .map(|line| line.split('\t').skip(1).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        vec![
+            vec!["127.0.0.1", "joe", "upload", "/audited.txt", "7"],
+            vec!["127.0.0.1", "joe", "mkdir", "/audited_dir", "-"],
+        ]
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn uploads_are_checked_against_hash_manifest() -> Result<(), Error> {
+    let manifest_dir = TempDir::new()?;
+    let manifest_path = manifest_dir.path().join("SHA256SUMS");
+    let checksum = hex::encode(Sha256::digest("expected content"));
+    std::fs::write(&manifest_path, format!("{checksum}  checked.txt\n"))?;
+    let server = server_no_stderr(&[
+        "-u",
+        "-o",
+        "--upload-hash-manifest",
+        manifest_path.to_str().unwrap(),
+    ]);
+    std::fs::write(server.path().join("checked.txt"), "original content")?;
+
+    let upload = |file_name: &str, content: &'static str| -> Result<reqwest::StatusCode, Error> {
+        let part = multipart::Part::text(content).file_name(file_name.to_owned());
+        let form = multipart::Form::new().part("file_to_upload", part);
+        let resp = Client::new()
+            .post(server.url().join("/upload?path=/")?)
+            .multipart(form)
+            .send()?;
+        Ok(resp.status())
+    };
+
+    // A mismatching upload is rejected without replacing the existing file
+    assert_eq!(upload("checked.txt", "tampered content")?, 400);
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("checked.txt"))?,
+        "original content"
+    );
+    assert!(!server.path().join(".checked.txt.unverified").exists());
+
+    // Files that aren't listed in the manifest are rejected as well
+    assert_eq!(upload("unlisted.txt", "expected content")?, 403);
+    assert!(!server.path().join("unlisted.txt").exists());
+
+    assert!(upload("checked.txt", "expected content")?.is_success());
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("checked.txt"))?,
+        "expected content"
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn uploads_can_require_auth(
+    #[with(&["-u", "--auth", "joe:123", "--upload-requires-auth"])] server_no_stderr: TestServer,
+) -> Result<(), Error> {
+    let server = server_no_stderr;
+
+    // Anonymous visitors can browse, but don't get an upload form
+    let body = Client::new().get(server.url()).send()?.error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed.find(Attr("id", "file_submit")).next().is_none());
+
+    // Instead, they are offered to log in, which asks for credentials
+    let login = parsed
+        .find(Attr("id", "login"))
+        .next()
+        .and_then(|link| link.attr("href"))
+        .expect("Listing doesn't link to the login");
+    let client = Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let resp = client.get(server.url().join(login)?).send()?;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers()[header::WWW_AUTHENTICATE]
+        .to_str()?
+        .starts_with("Basic"));
+    let resp = client
+        .get(server.url().join(login)?)
+        .basic_auth("joe", Some("123"))
+        .send()?;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers()[header::LOCATION], "/");
+
+    let upload = |credentials: Option<(&str, &str)>| -> Result<reqwest::StatusCode, Error> {
+        let part = multipart::Part::text("this should be uploaded").file_name("authenticated.txt");
+        let form = multipart::Form::new().part("file_to_upload", part);
+        let mut req = Client::new()
+            .post(server.url().join("/upload?path=/")?)
+            .multipart(form);
+        if let Some((user, password)) = credentials {
+            req = req.basic_auth(user, Some(password));
+        }
+        Ok(req.send()?.status())
+    };
+
+    assert_eq!(upload(None)?, 401);
+    assert!(!server.path().join("authenticated.txt").exists());
+
+    // Wrong credentials don't fall back to anonymous access
+    assert_eq!(upload(Some(("joe", "wrong")))?, 401);
+
+    assert!(upload(Some(("joe", "123")))?.is_success());
+    assert!(server.path().join("authenticated.txt").exists());
+
+    // Authenticated users get to see the form
+    let body = Client::new()
+        .get(server.url())
+        .basic_auth("joe", Some("123"))
+        .send()?
+        .error_for_status()?;
+    let parsed = Document::from_read(body)?;
+    assert!(parsed.find(Attr("id", "file_submit")).next().is_some());
+
+    Ok(())
+}
+
+#[rstest]
+fn tus_uploads_can_be_resumed(
+    #[with(&["-u", "--tus"])] server_no_stderr: TestServer,
+) -> Result<(), Error> {
+    let server = server_no_stderr;
+
+    let resp = Client::new()
+        .request(Method::OPTIONS, server.url().join("/upload/tus")?)
+        .send()?
+        .error_for_status()?;
+    assert_eq!(resp.headers()["Tus-Version"], "1.0.0");
+
+    let resp = Client::new()
+        .post(server.url().join("/upload/tus?path=/")?)
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Length", "11")
+        .header(
+            "Upload-Metadata",
+            format!("filename {}", BASE64.encode("resumed.txt")),
+        )
+        .send()?;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let location = server
+        .url()
+        .join(resp.headers()[header::LOCATION].to_str()?)?;
+
+    let patch = |offset: u64, body: &'static str| -> Result<reqwest::blocking::Response, Error> {
+        Ok(Client::new()
+            .patch(location.clone())
+            .header("Tus-Resumable", "1.0.0")
+            .header("Upload-Offset", offset.to_string())
+            .header(header::CONTENT_TYPE, "application/offset+octet-stream")
+            .body(body)
+            .send()?)
+    };
+
+    let resp = patch(0, "hello ")?;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(resp.headers()["Upload-Offset"], "6");
+    assert!(!server.path().join("resumed.txt").exists());
+
+    // Data sent at an outdated offset is rejected
+    assert_eq!(patch(0, "hello ")?.status(), StatusCode::CONFLICT);
+
+    let resp = Client::new()
+        .head(location.clone())
+        .header("Tus-Resumable", "1.0.0")
+        .send()?
+        .error_for_status()?;
+    assert_eq!(resp.headers()["Upload-Offset"], "6");
+    assert_eq!(resp.headers()["Upload-Length"], "11");
+
+    let resp = patch(6, "world")?;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(resp.headers()["Upload-Offset"], "11");
+
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("resumed.txt"))?,
+        "hello world"
+    );
+
+    // The upload is gone once it's complete
+    let resp = Client::new()
+        .head(location)
+        .header("Tus-Resumable", "1.0.0")
+        .send()?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[rstest]
+fn tus_uploads_larger_than_max_request_body_are_rejected(
+    #[with(&["-u", "--tus", "--max-request-body", "1KiB"])] server_no_stderr: TestServer,
+) -> Result<(), Error> {
+    let resp = Client::new()
+        .post(server_no_stderr.url().join("/upload/tus?path=/")?)
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Length", "1025")
+        .header(
+            "Upload-Metadata",
+            format!("filename {}", BASE64.encode("large.bin")),
+        )
+        .send()?;
+
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(
+        std::fs::read_dir(server_no_stderr.path())?.all(|entry| !entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .contains("large.bin"))
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server_no_stderr(&["-u"]), false)]
+#[case(server_no_stderr(&["-u", "--create-upload-dirs"]), true)]
+fn uploading_to_missing_dirs_can_create_them(
+    #[case] server: TestServer,
+    #[case] create_dirs: bool,
+) -> Result<(), Error> {
+    let part = multipart::Part::text("this should be uploaded").file_name("nested.txt");
+    let form = multipart::Form::new().part("file_to_upload", part);
+    let resp = Client::new()
+        .post(server.url().join("/upload?path=/new/nested%20dir")?)
+        .multipart(form)
+        .send()?;
+
+    let file_path = server
+        .path()
+        .join("new")
+        .join("nested dir")
+        .join("nested.txt");
+    if create_dirs {
+        resp.error_for_status()?;
+        assert_eq!(
+            std::fs::read_to_string(file_path)?,
+            "this should be uploaded"
+        );
+    } else {
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(!server.path().join("new").exists());
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn rejected_uploads_dont_create_missing_dirs(
+    #[with(&["-u", "--create-upload-dirs", "--upload-csrf"])] server_no_stderr: TestServer,
+) -> Result<(), Error> {
+    let part = multipart::Part::text("this should not be uploaded").file_name("nested.txt");
+    let form = multipart::Form::new().part("file_to_upload", part);
+    let resp = Client::new()
+        .post(
+            server_no_stderr
+                .url()
+                .join("/upload?path=/new/nested%20dir")?,
+        )
+        .header("X-Csrf-Token", "forged")
+        .multipart(form)
+        .send()?;
+
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(!server_no_stderr.path().join("new").exists());
+
+    Ok(())
+}
+
+#[rstest]
+#[case(None, StatusCode::FORBIDDEN)]
+#[case(Some((header::REFERER, "http://evil.com/")), StatusCode::FORBIDDEN)]
+#[case(Some((header::ORIGIN, "https://example.com.evil.com")), StatusCode::FORBIDDEN)]
+#[case(Some((header::REFERER, "http://example.com/")), StatusCode::SEE_OTHER)]
+#[case(Some((header::ORIGIN, "https://EXAMPLE.com")), StatusCode::SEE_OTHER)]
+fn uploads_are_restricted_to_allowed_referers(
+    #[with(&["-u", "--upload-allowed-referer", "example.com"])] server_no_stderr: TestServer,
+    #[case] referer: Option<(header::HeaderName, &str)>,
+    #[case] expected: StatusCode,
+) -> Result<(), Error> {
+    let server = server_no_stderr;
+    let part = multipart::Part::text("this should be uploaded").file_name("referred.txt");
+    let form = multipart::Form::new().part("file_to_upload", part);
+
+    // Successful uploads redirect back to the referer, which doesn't exist
+    let client = Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let mut req = client
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form);
+    if let Some((name, value)) = referer {
+        req = req.header(name, value);
+    }
+    assert_eq!(req.send()?.status(), expected);
+    assert_eq!(
+        server.path().join("referred.txt").exists(),
+        expected.is_redirection()
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[case(server_no_stderr(&["-u", "--sidecar-upload-control"]), "dira", true)]
+#[case(server_no_stderr(&["-u", "--sidecar-upload-control"]), "dirb", false)]
+#[case(server_no_stderr(&["-u", "dirb", "--sidecar-upload-control"]), "dira", false)]
+fn uploads_can_be_allowed_by_marker_files(
+    #[case] server: TestServer,
+    #[case] dir: &str,
+    #[case] allowed: bool,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("dira").join(".miniserve-upload"), "")?;
+
+    let body = reqwest::blocking::get(server.url().join(&format!("{dir}/"))?)?;
+    let parsed = Document::from_read(body)?;
+    assert_eq!(
+        parsed.find(Attr("id", "file_submit")).next().is_some(),
+        allowed
+    );
+
+    let part = multipart::Part::text("this should be uploaded").file_name("marked.txt");
+    let form = multipart::Form::new().part("file_to_upload", part);
+    let resp = Client::new()
+        .post(server.url().join(&format!("/upload?path=/{dir}"))?)
+        .multipart(form)
+        .send()?;
+
+    let file_path = server.path().join(dir).join("marked.txt");
+    if allowed {
+        resp.error_for_status()?;
+        assert!(file_path.exists());
+    } else {
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(!file_path.exists());
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+#[case("clean.txt", "harmless content", true)]
+#[case("infected.txt", "content with a VIRUS inside", false)]
+fn tus_uploads_are_streamed_into_upload_pipe(
+    #[with(&[
+        "-u",
+        "--tus",
+        "--upload-pipe",
+        "if grep -q VIRUS; then echo 'virus found' >&2; exit 1; fi",
+    ])]
+    server: TestServer,
+    #[case] file_name: &str,
+    #[case] content: &'static str,
+    #[case] accepted: bool,
+) -> Result<(), Error> {
+    let resp = Client::new()
+        .post(server.url().join("/upload/tus?path=/")?)
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Length", content.len().to_string())
+        .header(
+            "Upload-Metadata",
+            format!("filename {}", BASE64.encode(file_name)),
+        )
+        .send()?;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let location = server
+        .url()
+        .join(resp.headers()[header::LOCATION].to_str()?)?;
+
+    let resp = Client::new()
+        .patch(location)
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Offset", "0")
+        .header(header::CONTENT_TYPE, "application/offset+octet-stream")
+        .body(content)
+        .send()?;
+
+    let file_path = server.path().join(file_name);
+    if accepted {
+        resp.error_for_status()?;
+        assert_eq!(std::fs::read_to_string(file_path)?, content);
+    } else {
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(resp.text()?.contains("virus found"));
+        assert!(!file_path.exists());
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+#[case("clean.txt", "harmless content", true)]
+#[case("infected.txt", "content with a VIRUS inside", false)]
+fn uploads_are_streamed_into_upload_pipe(
+    #[with(&[
+        "-u",
+        "--upload-pipe",
+        "if grep -q VIRUS; then echo 'virus found' >&2; exit 1; fi",
+    ])]
+    server: TestServer,
+    #[case] file_name: &str,
+    #[case] content: &'static str,
+    #[case] accepted: bool,
+) -> Result<(), Error> {
+    let part = multipart::Part::text(content).file_name(file_name.to_string());
+    let form = multipart::Form::new().part("file_to_upload", part);
+    let resp = Client::new()
+        .post(server.url().join("/upload?path=/")?)
+        .multipart(form)
+        .send()?;
+
+    let file_path = server.path().join(file_name);
+    if accepted {
+        resp.error_for_status()?;
+        assert_eq!(std::fs::read_to_string(file_path)?, content);
+    } else {
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let text = resp.text()?;
+        assert!(text.contains("virus found"));
+        // Where the file is stored isn't revealed
+        assert!(!text.contains(&*server.path().to_string_lossy()));
+        assert!(!file_path.exists());
+    }
+    // No temporary file is left behind either way
+    assert!(!server
+        .path()
+        .join(format!(".{file_name}.unverified"))
+        .exists());
+
+    Ok(())
+}